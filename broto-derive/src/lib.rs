@@ -0,0 +1,126 @@
+//! `#[derive(BrotoRecord)]`: implements `broto::record::Record` for a
+//! struct with named fields.
+//!
+//! One field is marked `#[broto(index)]` and becomes the index; the others
+//! become channels in declaration order, named after the field unless
+//! renamed with `#[broto(rename = "NAME")]`. A field marked
+//! `#[broto(skip)]` is not stored and is `Default::default()` when read.
+//! Every stored field must implement `broto::record::Field`.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+extern crate syn;
+#[macro_use]
+extern crate quote;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use syn::{Data, DeriveInput, Fields, Ident, LitStr};
+
+enum Role {
+    Index,
+    Channel(String),
+    Skip,
+}
+
+fn role(field: &syn::Field) -> syn::Result<Role> {
+    let ident = field.ident.as_ref().unwrap();
+    let mut role = Role::Channel(ident.to_string().trim_start_matches("r#").to_string());
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("broto")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("index") {
+                role = Role::Index;
+            } else if meta.path.is_ident("skip") {
+                role = Role::Skip;
+            } else if meta.path.is_ident("rename") {
+                let name: LitStr = meta.value()?.parse()?;
+                role = Role::Channel(name.value());
+            } else {
+                return Err(meta.error("expected `index`, `skip` or `rename = \"...\"`"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(role)
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new(Span::call_site(),
+                                           "BrotoRecord requires a struct with named fields"));
+            },
+        },
+        _ => {
+            return Err(syn::Error::new(Span::call_site(), "BrotoRecord can only be derived for structs"));
+        },
+    };
+
+    let mut index: Option<&Ident> = None;
+    let mut channels: Vec<(&Ident, String)> = Vec::new();
+    let mut skipped: Vec<&Ident> = Vec::new();
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        match role(field)? {
+            Role::Index if index.is_some() => {
+                return Err(syn::Error::new_spanned(field, "more than one field is marked #[broto(index)]"));
+            },
+            Role::Index => { index = Some(ident); },
+            Role::Channel(name) => {
+                if channels.iter().any(|c| c.1 == name) {
+                    return Err(syn::Error::new_spanned(field, format!("duplicate channel name `{}`", name)));
+                }
+                channels.push((ident, name));
+            },
+            Role::Skip => { skipped.push(ident); },
+        }
+    }
+    let index = match index {
+        Some(index) => index,
+        None => {
+            return Err(syn::Error::new(Span::call_site(), "BrotoRecord requires a field marked #[broto(index)]"));
+        },
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let idents: Vec<&Ident> = channels.iter().map(|c| c.0).collect();
+    let names: Vec<&str> = channels.iter().map(|c| c.1.as_str()).collect();
+    let positions = 0..channels.len();
+    let count = channels.len();
+    Ok(quote! {
+        impl #impl_generics ::broto::record::Record for #name #ty_generics #where_clause {
+            const CHANNELS: &'static [&'static str] = &[#(#names),*];
+
+            fn index(&self) -> f64 {
+                ::broto::record::Field::to_f64(&self.#index)
+            }
+
+            fn write_values(&self, values: &mut ::std::vec::Vec<f64>) {
+                #( values.push(::broto::record::Field::to_f64(&self.#idents)); )*
+            }
+
+            fn from_entry(index: f64, values: &[f64]) -> ::broto::Result<Self> {
+                if values.len() != #count {
+                    return ::std::result::Result::Err(::broto::Error::Shape);
+                }
+                ::std::result::Result::Ok(#name {
+                    #index: ::broto::record::Field::from_f64(index)?,
+                    #( #idents: ::broto::record::Field::from_f64(values[#positions])?, )*
+                    #( #skipped: ::std::default::Default::default(), )*
+                })
+            }
+        }
+    })
+}
+
+#[proc_macro_derive(BrotoRecord, attributes(broto))]
+pub fn derive_broto_record(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
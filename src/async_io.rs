@@ -0,0 +1,501 @@
+//! Reading and writing with tokio, with the `tokio` feature.
+//!
+//! `AsyncReader` and `AsyncWriter` mirror `Reader` and `Writer` over
+//! `AsyncRead` and `AsyncWrite` streams. Blocks and entries are encoded and
+//! decoded by the same functions in `header` as the blocking types; only the
+//! I/O differs. The entries of a f64ts block are read as a `Stream`.
+//!
+//! A future of a method taking `&mut self` which is dropped before it
+//! completes leaves the stream as it was: a partly read block is read again
+//! by the next `next_block`, and the bytes not yet written are written by
+//! the next write. `write_f64ts`, `write_f64ts_with_seek` and `finalize`
+//! take the writer, which is lost with their future, and `finalize` may
+//! leave the stream at the block when dropped.
+
+use std::future::{self, Future};
+use std::io::{self, SeekFrom};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use byteorder::{ByteOrder, LittleEndian};
+use futures_core::Stream;
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+use header::{self, Header, BlockHeader, LogBlock, F64TSBlock, GroupBlock, AttrBlock, EventBlock};
+use reader::{self, Block};
+use error::{Result, Error};
+use group;
+use Entry;
+
+// Entries are buffered up to this many bytes before they are written, and
+// a block is read at most this many bytes at a time.
+const BUFFER_SIZE: usize = 1 << 16;
+
+// The largest block read into memory, with its header.
+const MAX_BLOCK_SIZE: u64 = 1 << 30;
+
+macro_rules! ready {
+    ($e:expr) => {
+        match $e {
+            Poll::Ready(t) => t,
+            Poll::Pending => { return Poll::Pending; },
+        }
+    }
+}
+
+/// Reads a stream like `Reader`.
+#[derive(Debug)]
+pub struct AsyncReader<R> {
+    stream : R,
+    header : Option<Header>,
+    group  : String,
+    // The bytes of the block or the entry being read.
+    buf    : Vec<u8>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncReader<R> {
+    pub fn new(stream: R) -> Self {
+        AsyncReader {
+            stream,
+            header : None,
+            group  : group::ROOT.to_string(),
+            buf    : Vec::new(),
+        }
+    }
+
+    pub fn initialize(&mut self) -> impl Future<Output = Result<()>> + '_ {
+        future::poll_fn(move |cx| {
+            let result = ready!(self.poll_fill(cx, Header::new().size()))
+                .and_then(|_| Header::read_from(&mut &self.buf[..]));
+            self.buf.clear();
+            self.header = Some(result?);
+            Poll::Ready(Ok(()))
+        })
+    }
+
+    /// Returns the header read by `initialize`.
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+
+    pub fn next_block(&mut self) -> impl Future<Output = Result<Block>> + '_ {
+        future::poll_fn(move |cx| {
+            loop {
+                let result = ready!(self.poll_block(cx));
+                self.buf.clear();
+                match result {
+                    Ok(Block::Log(ref log)) if log.is_padding() => continue,
+                    Ok(Block::Group(ref g)) => { self.group = g.path().to_string(); },
+                    _ => {},
+                }
+                return Poll::Ready(result);
+            }
+        })
+    }
+
+    /// Path of the group which the last block read belongs to.
+    pub fn current_group(&self) -> &str {
+        &self.group
+    }
+
+    /// Returns a stream of the entries of the f64ts block `data`, which is
+    /// the block read last.
+    pub fn f64ts_entries(&mut self, data: &F64TSBlock) -> AsyncF64TSReader<'_, R> {
+        AsyncF64TSReader {
+            index_len : data.index_len() as usize,
+            value_len : data.value_len() as usize,
+            remaining : data.length().unwrap(),
+            reader    : self,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.stream
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.stream
+    }
+
+    pub fn into_inner(self) -> R {
+        self.stream
+    }
+
+    // Reads the block header and the body into `buf`, which is kept if the
+    // stream is not ready so that the next poll continues from there.
+    fn poll_block(&mut self, cx: &mut Context) -> Poll<Result<Block>> {
+        ready!(self.poll_fill(cx, 16))?;
+        let header_size = if self.buf.len() == 16 && BlockHeader::check_magic(&self.buf) {
+            let name_len = LittleEndian::read_u64(&self.buf[8..]);
+            match name_len.checked_add(24) {
+                Some(size) if size <= MAX_BLOCK_SIZE => size,
+                _ => { return Poll::Ready(Err(Error::Parse(format!("block name of {} bytes", name_len)))); },
+            }
+        } else {
+            0
+        };
+        ready!(self.poll_fill(cx, header_size as usize))?;
+        // Fails as `Reader` does if the block header is not complete.
+        let bheader = BlockHeader::read_from(&mut &self.buf[..])?;
+        let size = match header_size.checked_add(bheader.block_size()) {
+            Some(size) if size <= MAX_BLOCK_SIZE => size as usize,
+            _ => { return Poll::Ready(Err(Error::Parse(format!("block of {} bytes", bheader.block_size())))); },
+        };
+        ready!(self.poll_fill(cx, size))?;
+        if self.buf.len() < size {
+            return Poll::Ready(Err(Error::EndOfFile));
+        }
+        Poll::Ready(reader::decode_block(&bheader.clone_name(), &mut &self.buf[header_size as usize..]))
+    }
+
+    // Reads until `buf` holds `len` bytes or the stream ends.
+    fn poll_fill(&mut self, cx: &mut Context, len: usize) -> Poll<Result<()>> {
+        while self.buf.len() < len {
+            let start = self.buf.len();
+            self.buf.resize(len.min(start + BUFFER_SIZE), 0);
+            let mut read_buf = ReadBuf::new(&mut self.buf[start..]);
+            let result = Pin::new(&mut self.stream).poll_read(cx, &mut read_buf);
+            let n = read_buf.filled().len();
+            self.buf.truncate(start + n);
+            match result {
+                Poll::Ready(Ok(())) if n == 0 => break,
+                Poll::Ready(Ok(())) => {},
+                Poll::Ready(Err(e)) => { return Poll::Ready(Err(e.into())); },
+                Poll::Pending => { return Poll::Pending; },
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// A stream of the entries of a f64ts block, like `F64TSReader`.
+#[derive(Debug)]
+pub struct AsyncF64TSReader<'a, R: 'a> {
+    reader    : &'a mut AsyncReader<R>,
+    index_len : usize,
+    value_len : usize,
+    remaining : u64,
+}
+
+impl<'a, R: AsyncRead + Unpin> AsyncF64TSReader<'a, R> {
+    /// Reads the next entry, or `None` after the last one.
+    pub fn next_entry(&mut self) -> NextEntry<'_, 'a, R> {
+        NextEntry { entries: self }
+    }
+}
+
+/// The future returned by `AsyncF64TSReader::next_entry`.
+#[derive(Debug)]
+pub struct NextEntry<'b, 'a: 'b, R: 'a> {
+    entries : &'b mut AsyncF64TSReader<'a, R>,
+}
+
+impl<'b, 'a, R: AsyncRead + Unpin> Future for NextEntry<'b, 'a, R> {
+    type Output = Option<Result<Entry>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Result<Entry>>> {
+        Pin::new(&mut *self.get_mut().entries).poll_next(cx)
+    }
+}
+
+impl<'a, R: AsyncRead + Unpin> Stream for AsyncF64TSReader<'a, R> {
+    type Item = Result<Entry>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Result<Entry>>> {
+        let this = self.get_mut();
+        if this.remaining == 0 {
+            return Poll::Ready(None);
+        }
+        let (index_len, value_len) = (this.index_len, this.value_len);
+        let reader = &mut *this.reader;
+        let result = ready!(reader.poll_fill(cx, 8 * (index_len + value_len)))
+            .and_then(|_| header::read_entry_from(&mut &reader.buf[..], index_len, value_len));
+        reader.buf.clear();
+        if result.is_ok() {
+            this.remaining -= 1;
+        }
+        Poll::Ready(Some(result))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining as usize, Some(self.remaining as usize))
+    }
+}
+
+/// Writes a stream like `Writer`.
+///
+/// Each future completes when its data has been passed to the stream,
+/// except that entries are buffered; `flush` writes everything.
+#[derive(Debug)]
+pub struct AsyncWriter<W> {
+    stream    : W,
+    position  : u64,
+    alignment : u64,
+    // Encoded bytes, of which `written` have been written.
+    out       : Vec<u8>,
+    written   : usize,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWriter<W> {
+    pub fn new(stream: W) -> Self {
+        AsyncWriter {
+            stream,
+            position  : 0,
+            alignment : 1,
+            out       : Vec::new(),
+            written   : 0,
+        }
+    }
+
+    /// Pads f64ts block headers like `Writer::with_alignment`.
+    pub fn with_alignment(mut self, alignment: u64) -> Self {
+        assert!(alignment > 0);
+        self.alignment = alignment;
+        self
+    }
+
+    pub fn alignment(&self) -> u64 {
+        self.alignment
+    }
+
+    /// Number of bytes written through this writer.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn write_header(&mut self) -> impl Future<Output = Result<()>> + '_ {
+        let mut buf = Vec::new();
+        let result = Header::new().write_into(&mut buf).map(|_| self.queue(&buf));
+        self.drain(result)
+    }
+
+    pub fn write_log(&mut self, log: &LogBlock) -> impl Future<Output = Result<()>> + '_ {
+        let result = header::encode_block("log", |b| log.write_into(b)).map(|buf| self.queue(&buf));
+        self.drain(result)
+    }
+
+    /// Makes the blocks written after this belong to the group at `path`.
+    pub fn write_group(&mut self, path: &str) -> impl Future<Output = Result<()>> + '_ {
+        let group = GroupBlock::new(path);
+        let result = header::encode_block("group", |b| group.write_into(b)).map(|buf| self.queue(&buf));
+        self.drain(result)
+    }
+
+    pub fn write_attr(&mut self, attr: &AttrBlock) -> impl Future<Output = Result<()>> + '_ {
+        let result = header::encode_block("attr", |b| attr.write_into(b)).map(|buf| self.queue(&buf));
+        self.drain(result)
+    }
+
+    pub fn write_event(&mut self, event: &EventBlock) -> impl Future<Output = Result<()>> + '_ {
+        let result = header::encode_block("event", |b| event.write_into(b)).map(|buf| self.queue(&buf));
+        self.drain(result)
+    }
+
+    /// Writes the f64ts block `block`, whose length must be set, and returns
+    /// a writer of its entries. Like `Writer::write_f64ts`, the padding
+    /// assumes that the stream started at offset 0 with this writer.
+    pub fn write_f64ts(mut self, block: F64TSBlock) -> impl Future<Output = Result<AsyncF64TSWriter<W>>> {
+        assert!(block.length().is_some());
+        let offset = self.position;
+        let result = self.queue_f64ts(&block, offset);
+        let mut result = Some(result);
+        let mut this = Some(self);
+        future::poll_fn(move |cx| {
+            if let Some(result) = result.take() {
+                result?;
+            }
+            ready!(this.as_mut().unwrap().poll_drain(cx))?;
+            Poll::Ready(Ok(AsyncF64TSWriter::new(this.take().unwrap(), block.clone(), None)))
+        })
+    }
+
+    /// Writes everything and flushes the stream.
+    pub fn flush(&mut self) -> impl Future<Output = Result<()>> + '_ {
+        future::poll_fn(move |cx| {
+            ready!(self.poll_drain(cx))?;
+            Poll::Ready(ready!(Pin::new(&mut self.stream).poll_flush(cx)).map_err(Error::from))
+        })
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.stream
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.stream
+    }
+
+    pub fn into_stream(self) -> W {
+        assert!(self.out.is_empty(), "fn flush() must be called");
+        self.stream
+    }
+
+    fn queue(&mut self, buf: &[u8]) {
+        self.out.extend_from_slice(buf);
+        self.position += buf.len() as u64;
+    }
+
+    // Queues the padding, the block header and the f64ts block to be written
+    // at `offset` from the beginning of the stream. Returns the number of
+    // bytes in front of the f64ts block.
+    fn queue_f64ts(&mut self, block: &F64TSBlock, offset: u64) -> Result<u64> {
+        let padding = header::f64ts_padding(block, offset, self.alignment);
+        if padding > 0 {
+            self.queue(&header::encode_padding(padding)?);
+        }
+        let buf = header::encode_block("f64ts", |b| block.write_into(b))?;
+        self.queue(&buf);
+        Ok(padding + BlockHeader::new("f64ts", 0).size() as u64)
+    }
+
+    // Returns a future which fails with `result` or writes the queued bytes.
+    fn drain(&mut self, result: Result<()>) -> impl Future<Output = Result<()>> + '_ {
+        let mut result = Some(result);
+        future::poll_fn(move |cx| {
+            if let Some(result) = result.take() {
+                result?;
+            }
+            self.poll_drain(cx)
+        })
+    }
+
+    fn poll_drain(&mut self, cx: &mut Context) -> Poll<Result<()>> {
+        while self.written < self.out.len() {
+            let n = ready!(Pin::new(&mut self.stream).poll_write(cx, &self.out[self.written..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::Error::from(io::ErrorKind::WriteZero).into()));
+            }
+            self.written += n;
+        }
+        self.out.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + AsyncSeek + Unpin> AsyncWriter<W> {
+    /// Writes the f64ts block `block` and returns a writer of its entries,
+    /// which sets the length in `finalize`.
+    pub fn write_f64ts_with_seek(self, block: F64TSBlock) -> impl Future<Output = Result<AsyncF64TSWriter<W>>> {
+        let mut this = Some(self);
+        let mut seeking = false;
+        let mut block_pos = None;
+        future::poll_fn(move |cx| {
+            let writer = this.as_mut().unwrap();
+            if block_pos.is_none() {
+                ready!(writer.poll_drain(cx))?;
+                let start = ready!(writer.poll_seek(cx, SeekFrom::Current(0), &mut seeking))?;
+                block_pos = Some(start + writer.queue_f64ts(&block, start)?);
+            }
+            ready!(writer.poll_drain(cx))?;
+            Poll::Ready(Ok(AsyncF64TSWriter::new(this.take().unwrap(), block.clone(), block_pos)))
+        })
+    }
+
+    fn poll_seek(&mut self, cx: &mut Context, pos: SeekFrom, seeking: &mut bool) -> Poll<Result<u64>> {
+        if !*seeking {
+            Pin::new(&mut self.stream).start_seek(pos)?;
+            *seeking = true;
+        }
+        let result = ready!(Pin::new(&mut self.stream).poll_complete(cx));
+        *seeking = false;
+        Poll::Ready(result.map_err(Error::from))
+    }
+}
+
+/// Writes the entries of a f64ts block, like `F64TSWriter`.
+#[derive(Debug)]
+pub struct AsyncF64TSWriter<W> {
+    writer    : AsyncWriter<W>,
+    block     : F64TSBlock,
+    block_pos : Option<u64>,
+    value_len : usize,
+    count     : u64,
+    finalized : bool,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncF64TSWriter<W> {
+    fn new(writer: AsyncWriter<W>, block: F64TSBlock, block_pos: Option<u64>) -> Self {
+        AsyncF64TSWriter {
+            writer,
+            value_len : block.value_len() as usize,
+            finalized : block_pos.is_none(),
+            block,
+            block_pos,
+            count     : 0,
+        }
+    }
+
+    pub fn value_len(&self) -> usize {
+        self.value_len
+    }
+
+    /// Number of entries written.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Writes an entry. The entries are buffered, and the future waits
+    /// only when the buffer is full.
+    pub fn write_entry(&mut self, index: f64, values: &[f64]) -> impl Future<Output = Result<()>> + '_ {
+        let result = if values.len() != self.value_len {
+            Err(Error::Shape)
+        } else {
+            let mut buf = Vec::with_capacity(8 * (1 + values.len()));
+            header::write_entry_into(&mut buf, index, values).map(|_| {
+                self.writer.queue(&buf);
+                self.count += 1;
+            })
+        };
+        let full = self.writer.out.len() >= BUFFER_SIZE;
+        let mut result = Some(result);
+        let writer = &mut self.writer;
+        future::poll_fn(move |cx| {
+            if let Some(result) = result.take() {
+                result?;
+            }
+            if full { writer.poll_drain(cx) } else { Poll::Ready(Ok(())) }
+        })
+    }
+
+    pub fn finish(self) -> AsyncWriter<W> {
+        assert!(self.finalized, "fn finalize() must be called");
+        self.writer
+    }
+}
+
+impl<W: AsyncWrite + AsyncSeek + Unpin> AsyncF64TSWriter<W> {
+    /// Writes the buffered entries and sets the length of the block.
+    pub fn finalize(self) -> impl Future<Output = Result<Self>> {
+        let mut this = Some(self);
+        let mut seeking = false;
+        let mut stage = 0;
+        let mut end = 0;
+        future::poll_fn(move |cx| {
+            let w = this.as_mut().unwrap();
+            loop {
+                match stage {
+                    0 => {
+                        ready!(w.writer.poll_drain(cx))?;
+                        end = ready!(w.writer.poll_seek(cx, SeekFrom::Current(0), &mut seeking))?;
+                    },
+                    1 => {
+                        ready!(w.writer.poll_seek(cx, SeekFrom::Start(w.block_pos.unwrap()), &mut seeking))?;
+                        w.block.set_length(w.count);
+                        let mut buf = Vec::new();
+                        w.block.write_into(&mut buf)?;
+                        // Overwrites bytes already counted in the position.
+                        w.writer.out.extend_from_slice(&buf);
+                    },
+                    2 => {
+                        ready!(w.writer.poll_drain(cx))?;
+                        ready!(w.writer.poll_seek(cx, SeekFrom::Start(end), &mut seeking))?;
+                    },
+                    _ => {
+                        w.finalized = true;
+                        return Poll::Ready(Ok(this.take().unwrap()));
+                    },
+                }
+                stage += 1;
+            }
+        })
+    }
+}
@@ -0,0 +1,233 @@
+//! Writing entries on a background thread.
+//!
+//! A `BackgroundWriter` moves a `F64TSWriter` to a thread of its own and hands
+//! the entries over through a bounded lock-free queue, so that a stalled
+//! disk delays the thread instead of the producer. What happens when the
+//! queue is full is chosen by `Backpressure`.
+
+use std::io;
+use std::str::FromStr;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+use crossbeam_queue::ArrayQueue;
+use writer::F64TSWriter;
+use error::{Result, Error};
+use Entry;
+
+// How long the thread sleeps when the queue is empty, and a producer when it
+// is full, unless woken up.
+const IDLE_MS: u64 = 10;
+
+/// What `BackgroundWriter::write_entry` does when the queue is full.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Backpressure {
+    /// Wait until there is room.
+    Block,
+    /// Drop the oldest entry in the queue to make room.
+    DropOldest,
+    /// Drop the new entry and fail with an `io::ErrorKind::WouldBlock` error.
+    Error,
+}
+
+impl FromStr for Backpressure {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "block" => Ok(Backpressure::Block),
+            "drop-oldest" => Ok(Backpressure::DropOldest),
+            "error" => Ok(Backpressure::Error),
+            _ => Err(Error::Parse(format!("unknown backpressure policy: {}", s))),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    queue      : ArrayQueue<Entry>,
+    closed     : AtomicBool,
+    failed     : AtomicBool,
+    high_water : AtomicUsize,
+    dropped    : AtomicU64,
+    written    : AtomicU64,
+    // Signalled when the thread has made room in the queue or has stopped.
+    room       : (Mutex<()>, Condvar),
+}
+
+impl Shared {
+    fn notify_room(&self) {
+        let _guard = self.room.0.lock().unwrap();
+        self.room.1.notify_all();
+    }
+}
+
+/// Writes the entries of a series on a background thread.
+///
+/// `write_entry` takes `&self`, so the writer can be shared by several
+/// producers. Dropping it without `finish` still writes the queued entries
+/// but discards the `F64TSWriter` and any error.
+#[derive(Debug)]
+pub struct BackgroundWriter<W: io::Write> {
+    shared       : Arc<Shared>,
+    thread       : Option<thread::JoinHandle<Result<F64TSWriter<W>>>>,
+    value_len    : usize,
+    backpressure : Backpressure,
+}
+
+impl<W: io::Write + Send + 'static> BackgroundWriter<W> {
+    /// Starts a thread writing into `writer` the entries passed through a
+    /// queue of `capacity` entries, which must not be zero.
+    pub fn new(writer: F64TSWriter<W>, capacity: usize, backpressure: Backpressure) -> Result<Self> {
+        if capacity == 0 {
+            return Err(Error::Io(io::Error::new(io::ErrorKind::InvalidInput, "the queue capacity is zero")));
+        }
+        let shared = Arc::new(Shared {
+            queue      : ArrayQueue::new(capacity),
+            closed     : AtomicBool::new(false),
+            failed     : AtomicBool::new(false),
+            high_water : AtomicUsize::new(0),
+            dropped    : AtomicU64::new(0),
+            written    : AtomicU64::new(0),
+            room       : (Mutex::new(()), Condvar::new()),
+        });
+        let value_len = writer.value_len();
+        let thread = {
+            let shared = shared.clone();
+            thread::Builder::new()
+                .name("broto-writer".to_string())
+                .spawn(move || run(writer, &shared))?
+        };
+        Ok(BackgroundWriter { shared, thread: Some(thread), value_len, backpressure })
+    }
+}
+
+impl<W: io::Write> BackgroundWriter<W> {
+    /// Queues an entry. Fails if the thread has stopped on an error, which
+    /// `finish` returns, or if the queue is full with `Backpressure::Error`.
+    pub fn write_entry(&self, index: f64, values: &[f64]) -> Result<()> {
+        if values.len() != self.value_len {
+            return Err(Error::Shape);
+        }
+        let shared = &*self.shared;
+        let mut entry = (index, values.to_vec());
+        loop {
+            if shared.failed.load(Ordering::Acquire) {
+                return Err(Error::Io(io::Error::other("the writer thread has stopped on an error")));
+            }
+            let full = match self.backpressure {
+                Backpressure::DropOldest => {
+                    if shared.queue.force_push(entry).is_some() {
+                        shared.dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                    break;
+                },
+                Backpressure::Error => match shared.queue.push(entry) {
+                    Ok(()) => break,
+                    Err(_) => {
+                        shared.dropped.fetch_add(1, Ordering::Relaxed);
+                        return Err(Error::Io(io::Error::new(io::ErrorKind::WouldBlock, "the queue is full")));
+                    },
+                },
+                Backpressure::Block => match shared.queue.push(entry) {
+                    Ok(()) => break,
+                    Err(full) => {
+                        // Retrying under the lock, the signal of the thread
+                        // cannot come between the push and the wait.
+                        let guard = shared.room.0.lock().unwrap();
+                        match shared.queue.push(full) {
+                            Ok(()) => break,
+                            Err(full) => {
+                                self.wake();
+                                let _ = shared.room.1.wait_timeout(guard, Duration::from_millis(IDLE_MS)).unwrap();
+                                full
+                            },
+                        }
+                    },
+                },
+            };
+            entry = full;
+        }
+        shared.high_water.fetch_max(shared.queue.len(), Ordering::Relaxed);
+        self.wake();
+        Ok(())
+    }
+
+    /// Number of entries waiting in the queue.
+    pub fn queued(&self) -> usize {
+        self.shared.queue.len()
+    }
+
+    /// Largest number of entries which have been waiting in the queue.
+    pub fn high_water(&self) -> usize {
+        self.shared.high_water.load(Ordering::Relaxed)
+    }
+
+    /// Number of entries dropped because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Number of entries written by the thread.
+    pub fn written(&self) -> u64 {
+        self.shared.written.load(Ordering::Relaxed)
+    }
+
+    /// Writes the queued entries, stops the thread and returns the
+    /// `F64TSWriter`, or the error the thread stopped on.
+    pub fn finish(mut self) -> Result<F64TSWriter<W>> {
+        self.stop().unwrap()
+    }
+
+    fn wake(&self) {
+        if let Some(ref thread) = self.thread {
+            thread.thread().unpark();
+        }
+    }
+
+    fn stop(&mut self) -> Option<Result<F64TSWriter<W>>> {
+        let thread = self.thread.take()?;
+        self.shared.closed.store(true, Ordering::Release);
+        thread.thread().unpark();
+        Some(thread.join().unwrap_or_else(|_| {
+            Err(Error::Io(io::Error::other("the writer thread panicked")))
+        }))
+    }
+}
+
+impl<W: io::Write> Drop for BackgroundWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+fn run<W: io::Write>(mut writer: F64TSWriter<W>, shared: &Shared) -> Result<F64TSWriter<W>> {
+    let result = write_queued(&mut writer, shared);
+    if result.is_err() {
+        shared.failed.store(true, Ordering::Release);
+        shared.notify_room();
+    }
+    result.map(|_| writer)
+}
+
+fn write_queued<W: io::Write>(writer: &mut F64TSWriter<W>, shared: &Shared) -> Result<()> {
+    loop {
+        // Read `closed` first so that no entry queued before it is missed.
+        let closed = shared.closed.load(Ordering::Acquire);
+        let mut popped = false;
+        while let Some((index, values)) = shared.queue.pop() {
+            popped = true;
+            writer.write_entry(index, &values)?;
+            shared.written.fetch_add(1, Ordering::Relaxed);
+        }
+        if popped {
+            shared.notify_room();
+        }
+        if closed {
+            writer.stream_mut().flush()?;
+            return Ok(());
+        }
+        thread::park_timeout(Duration::from_millis(IDLE_MS));
+    }
+}
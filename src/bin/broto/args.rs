@@ -0,0 +1,84 @@
+use std::str::FromStr;
+use CliError;
+
+/// Command line arguments of a subcommand.
+#[derive(Debug)]
+pub struct Args {
+    positional : Vec<String>,
+    options    : Vec<(String, Option<String>)>,
+}
+
+impl Args {
+    /// Parses `args`, accepting the options in `flags` (without a value) and
+    /// in `valued` (with a value, given as `--opt VALUE` or `--opt=VALUE`).
+    pub fn parse(args: &[String], flags: &[&str], valued: &[&str]) -> Result<Args, CliError> {
+        let mut positional = Vec::new();
+        let mut options = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                positional.extend(iter.by_ref().cloned());
+                break;
+            }
+            if !arg.starts_with('-') || arg == "-" {
+                positional.push(arg.clone());
+                continue;
+            }
+            let (name, inline) = match arg.find('=') {
+                Some(pos) => (&arg[..pos], Some(arg[pos + 1..].to_string())),
+                None => (arg.as_str(), None),
+            };
+            if flags.contains(&name) && inline.is_none() {
+                options.push((name.to_string(), None));
+            } else if valued.contains(&name) {
+                let value = match inline {
+                    Some(v) => v,
+                    None => iter.next().cloned().ok_or_else(|| {
+                        CliError::Usage(format!("option {} requires a value", name))
+                    })?,
+                };
+                options.push((name.to_string(), Some(value)));
+            } else {
+                return Err(CliError::Usage(format!("unknown option: {}", arg)));
+            }
+        }
+        Ok(Args { positional, options })
+    }
+
+    /// Returns the only positional argument.
+    pub fn single(&self, what: &str) -> Result<&str, CliError> {
+        match self.positional.len() {
+            1 => Ok(&self.positional[0]),
+            0 => Err(CliError::Usage(format!("missing {}", what))),
+            _ => Err(CliError::Usage(format!("too many arguments: {}", self.positional[1..].join(" ")))),
+        }
+    }
+
+    /// Returns the positional arguments, of which there must be at least one.
+    pub fn many(&self, what: &str) -> Result<&[String], CliError> {
+        if self.positional.is_empty() {
+            return Err(CliError::Usage(format!("missing {}", what)));
+        }
+        Ok(&self.positional)
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.options.iter().any(|(n, _)| n == name)
+    }
+
+    /// Returns the value of the last occurrence of the option.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.options.iter().rev()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, v)| v.as_deref())
+    }
+
+    pub fn parsed<T: FromStr>(&self, name: &str) -> Result<Option<T>, CliError> {
+        match self.value(name) {
+            Some(v) => v.parse().map(Some).map_err(|_| {
+                CliError::Usage(format!("invalid value for {}: {}", name, v))
+            }),
+            None => Ok(None),
+        }
+    }
+}
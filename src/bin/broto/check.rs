@@ -0,0 +1,49 @@
+use std::fs;
+use broto::check;
+use args::Args;
+use CliError;
+
+pub const USAGE: &str = "broto check [OPTIONS] FILE...
+
+Verifies the magic numbers, the block sizes, the sizes of the entries of
+each series and the encoding of the strings of each file, and reports
+bytes after the last block, decreasing indices and NaN or infinite numbers
+with their offsets. The last two are warnings; everything else is an error.
+Exits with status 1 if a file has errors.
+  --strict          treat warnings as errors
+  --quiet           print only the issues, without a summary per file";
+
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--strict", "--quiet"], &[])?;
+    let paths = args.many("FILE")?;
+    let strict = args.flag("--strict");
+
+    let mut failed = 0;
+    for path in paths {
+        let report = match fs::File::open(path).map_err(|e| e.into()).and_then(check::check) {
+            Ok(report) => report,
+            Err(e) => {
+                println!("{}: {}", path, e);
+                failed += 1;
+                continue;
+            },
+        };
+        for issue in report.issues() {
+            let kind = if issue.problem.is_error() { "error" } else { "warning" };
+            println!("{}: {}: {}", path, kind, issue);
+        }
+        let errors = report.issues().iter().filter(|i| i.problem.is_error()).count();
+        let warnings = report.issues().len() - errors;
+        if !args.flag("--quiet") {
+            println!("{}: {} blocks, {} series, {} rows, {} errors, {} warnings",
+                     path, report.blocks(), report.series(), report.rows(), errors, warnings);
+        }
+        if errors > 0 || (strict && warnings > 0) {
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        return Err(CliError::Failed(format!("{} of {} files failed the check", failed, paths.len())));
+    }
+    Ok(())
+}
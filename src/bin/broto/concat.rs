@@ -0,0 +1,31 @@
+use broto::{self, LogBlockBuilder};
+use broto::merge;
+use args::Args;
+use {CliError, open_lazy};
+
+pub const USAGE: &str = "broto concat [OPTIONS] -o OUTPUT FILE...
+
+Joins files end to end. Series with the same group, name and channel layout
+are combined into one series with the entries of each file in turn. Logs,
+attributes and events are carried over, and a log recording the
+concatenation is added.
+  --segments        read files joined with cat as one file
+  -o OUTPUT         output file";
+
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--segments"], &["-o"])?;
+    let paths = args.many("FILE")?;
+    let out = args.value("-o").ok_or_else(|| CliError::Usage("missing -o OUTPUT".to_string()))?;
+
+    let mut inputs = Vec::new();
+    for path in paths {
+        inputs.push(open_lazy(path, args.flag("--segments"))?);
+    }
+    let log = LogBlockBuilder::new()
+        .program("broto concat")
+        .info(format!("concatenated {}", paths.join(", ")))
+        .build();
+    let writer = broto::File::create(out)?;
+    merge::concat(&mut inputs, writer, Some(&log))?.close()?;
+    Ok(())
+}
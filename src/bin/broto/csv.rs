@@ -0,0 +1,122 @@
+use std::io::Write;
+use broto::{self, F64TSBlockBuilder, LogBlockBuilder};
+use broto::csv::{self, Column, CsvFormat, CsvImport};
+use args::Args;
+use {CliError, select_series, output, input, stem, open_lazy};
+
+pub const USAGE: &str = "broto csv export [OPTIONS] FILE
+       broto csv import [OPTIONS] -o OUTPUT INPUT
+
+export writes a series as delimited text with the index in the first column.
+  --series NAME     series name, path or #N (required if there are several)
+  --tsv             separate columns by tabs
+  --delimiter C     separate columns by C (default ,)
+  --precision N     write N digits after the decimal point
+  --scientific      write numbers in scientific notation
+  --no-header       do not write the header row of channel names
+  --segments        read files joined with cat as one file
+  -o OUTPUT         output file (default: standard output)
+
+import reads delimited text (INPUT may be - for the standard input).
+  --tsv, --delimiter C, --no-header   as for export
+  --index COL       index column, by number from 0 or by name (default 0)
+  --columns A,B,..  value columns (default: all but the index)
+  --name NAME       series name (default: the file name of INPUT)
+  --group PATH      group of the series";
+
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    match args.first().map(|s| s.as_str()) {
+        Some("export") => export(&args[1..]),
+        Some("import") => import(&args[1..]),
+        _ => Err(CliError::Usage("expected csv export or csv import".to_string())),
+    }
+}
+
+fn delimiter(args: &Args) -> Result<char, CliError> {
+    if args.flag("--tsv") {
+        return Ok('\t');
+    }
+    match args.value("--delimiter") {
+        None => Ok(','),
+        Some("\\t") => Ok('\t'),
+        Some(d) if d.chars().count() == 1 => Ok(d.chars().next().unwrap()),
+        Some(d) => Err(CliError::Usage(format!("invalid delimiter: {}", d))),
+    }
+}
+
+fn export(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--tsv", "--scientific", "--no-header", "--segments"],
+                           &["--series", "--delimiter", "--precision", "-o"])?;
+    let path = args.single("FILE")?;
+
+    let mut format = CsvFormat::csv()
+        .delimiter(delimiter(&args)?)
+        .scientific(args.flag("--scientific"))
+        .header(!args.flag("--no-header"));
+    if let Some(p) = args.parsed("--precision")? {
+        format = format.precision(p);
+    }
+
+    let mut lazy = open_lazy(path, args.flag("--segments"))?;
+    let n = select_series(&lazy, args.value("--series"))?;
+    let channels = lazy.series()[n].block().channel_names().to_vec();
+    let mut out = output(args.value("-o"))?;
+    csv::write_csv(&mut out, &channels, lazy.entries(n)?, &format)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn import(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--tsv", "--no-header"],
+                           &["--delimiter", "--index", "--columns", "--name", "--group", "-o"])?;
+    let path = args.single("INPUT")?;
+    let out = args.value("-o").ok_or_else(|| CliError::Usage("missing -o OUTPUT".to_string()))?;
+
+    let mut spec = CsvImport::csv()
+        .delimiter(delimiter(&args)?)
+        .header(!args.flag("--no-header"));
+    if let Some(index) = args.value("--index") {
+        spec = spec.index_column(index.parse::<Column>()?);
+    }
+    if let Some(columns) = args.value("--columns") {
+        let columns = columns.split(',').map(|c| c.trim().parse()).collect::<broto::Result<_>>()?;
+        spec = spec.value_columns(columns);
+    }
+    let name = args.value("--name").map(|s| s.to_string()).unwrap_or_else(|| stem(path));
+
+    let mut reader = csv::CsvReader::new(input(path)?, &spec)?;
+    let first = match reader.next() {
+        Some(entry) => Some(entry?),
+        None => None,
+    };
+
+    let mut writer = broto::File::create(out)?;
+    let log = LogBlockBuilder::new()
+        .program("broto csv import")
+        .info(format!("imported {} from {}", name, path))
+        .build();
+    writer.write_log(&log)?;
+    if let Some(group) = args.value("--group") {
+        writer.write_group(group)?;
+    }
+    let mut block = F64TSBlockBuilder::new()
+        .index_len(1)
+        .value_len(reader.value_len() as u64)
+        .name(name);
+    if !reader.channel_names().is_empty() {
+        block = block.channel_names(reader.channel_names().to_vec());
+    }
+    let mut w = writer.write_f64ts_with_seek(block.build())?;
+    if let Some((index, values)) = first {
+        w.write_entry(index, &values)?;
+    }
+    for entry in reader {
+        let (index, values) = entry?;
+        if values.len() != w.value_len() {
+            return Err(broto::Error::Shape.into());
+        }
+        w.write_entry(index, &values)?;
+    }
+    w.finalize()?.finish().close()?;
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use broto;
+use args::Args;
+use {info, CliError, open_lazy};
+
+pub const USAGE: &str = "broto dump [-n ROWS] [--segments] FILE
+
+Prints the logs, the attributes and the events of each group, and the first
+and the last ROWS rows (default 5) of each series.
+  --segments        read files joined with cat as one file";
+
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--segments"], &["-n", "--rows"])?;
+    let path = args.single("FILE")?;
+    let rows = match args.parsed::<u64>("-n")? {
+        Some(n) => n,
+        None => args.parsed::<u64>("--rows")?.unwrap_or(5),
+    };
+
+    let mut lazy = open_lazy(path, args.flag("--segments"))?;
+
+    let paths: Vec<String> = lazy.group_paths().iter().map(|p| p.to_string()).collect();
+    for path in paths.iter() {
+        let metadata = match lazy.group(path) {
+            Some(metadata) => metadata,
+            None => continue,
+        };
+        if metadata.get_logs().is_empty() && metadata.get_attrs().is_empty()
+            && metadata.get_events().is_empty() {
+            continue;
+        }
+        println!("group {}", path);
+        for log in metadata.get_logs() {
+            println!("  log   {}", broto::timestamp::format_rfc3339(log.time()));
+            println!("        program: {}", log.program());
+            println!("        info   : {}", log.info());
+        }
+        for attr in metadata.get_attrs() {
+            println!("  attr  {} = {}", attr.key(), attr.value());
+        }
+        for event in metadata.get_events() {
+            println!("  event {}", info::describe_event(event));
+        }
+    }
+
+    for n in 0..lazy.series().len() {
+        let (length, title) = {
+            let info = &lazy.series()[n];
+            let name = info.name().map(|s| broto::group::join(info.group(), s))
+                .unwrap_or_else(|| format!("#{}", n));
+            (info.length(), format!("series {} ({} values x {} rows)", name, info.value_len(), info.length()))
+        };
+        println!("{}", title);
+        let head = rows.min(length);
+        for entry in lazy.entries(n)?.take(head as usize) {
+            print_entry(&entry?);
+        }
+        let tail_start = length.saturating_sub(rows).max(head);
+        if tail_start > head {
+            println!("  ... {} rows ...", tail_start - head);
+        }
+        for entry in lazy.entries_from(n, tail_start)? {
+            print_entry(&entry?);
+        }
+    }
+    Ok(())
+}
+
+fn print_entry(entry: &broto::Entry) {
+    let values: Vec<String> = entry.1.iter().map(|x| x.to_string()).collect();
+    println!("  {}\t{}", entry.0, values.join("\t"));
+}
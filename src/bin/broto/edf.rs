@@ -0,0 +1,39 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use broto::{self, LogBlockBuilder, PendingFile};
+use broto::edf;
+use args::Args;
+use CliError;
+
+pub const USAGE: &str = "broto edf import [--group PATH] -o OUTPUT INPUT
+
+Reads an EDF or EDF+ recording. Each signal becomes a series named by its
+label, the header fields become edf.* attributes and the EDF+ annotations
+become events.
+  --group PATH      group of the series, the attributes and the events";
+
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    match args.first().map(|s| s.as_str()) {
+        Some("import") => import(&args[1..]),
+        _ => Err(CliError::Usage("expected edf import".to_string())),
+    }
+}
+
+fn import(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &[], &["--group", "-o"])?;
+    let path = args.single("INPUT")?;
+    let out = args.value("-o").ok_or_else(|| CliError::Usage("missing -o OUTPUT".to_string()))?;
+    let group = args.value("--group").unwrap_or(broto::group::ROOT);
+
+    let input = io::BufReader::new(fs::File::open(path)?);
+    let mut dataset = edf::read_edf(input, group)?;
+    let log = LogBlockBuilder::new()
+        .program("broto edf import")
+        .info(format!("imported {}", path))
+        .build();
+    dataset.metadata_mut().get_logs_mut().push(log);
+
+    broto::save(PendingFile::create(Path::new(out))?, &dataset)?.commit()?;
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use std::io::Write;
+use broto::{self, LogBlockBuilder};
+use broto::influx::{self, Precision};
+use args::Args;
+use {CliError, select_series, output, input, open_lazy};
+
+pub const USAGE: &str = "broto influx export [OPTIONS] FILE
+       broto influx import [OPTIONS] -o OUTPUT INPUT
+
+export writes series as InfluxDB line protocol: the series name is the
+measurement, the channels are fields and the attributes of the group are
+tags; imported series keep their measurement and tags. The index is taken
+to be seconds since the UNIX epoch.
+  --series NAME     series name, path or #N (default: all series)
+  --precision P     timestamp precision: ns, us, ms or s (default ns)
+  --segments        read files joined with cat as one file
+  -o OUTPUT         output file (default: standard output)
+
+import reads line protocol (INPUT may be - for the standard input).
+  --precision P     timestamp precision as for export
+  --chunk ROWS      rows of a series kept in memory before they are written
+                    (default 65536)";
+
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    match args.first().map(|s| s.as_str()) {
+        Some("export") => export(&args[1..]),
+        Some("import") => import(&args[1..]),
+        _ => Err(CliError::Usage("expected influx export or influx import".to_string())),
+    }
+}
+
+fn precision(args: &Args) -> Result<Precision, CliError> {
+    Ok(args.parsed::<Precision>("--precision")?.unwrap_or(Precision::Nanoseconds))
+}
+
+fn export(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--segments"], &["--series", "--precision", "-o"])?;
+    let path = args.single("FILE")?;
+    let precision = precision(&args)?;
+
+    let mut lazy = open_lazy(path, args.flag("--segments"))?;
+    let mut out = output(args.value("-o"))?;
+    if args.value("--series").is_some() {
+        let n = select_series(&lazy, args.value("--series"))?;
+        let (measurement, tags, fields) = {
+            let info = &lazy.series()[n];
+            let metadata = lazy.group(info.group()).unwrap();
+            let (measurement, tags) = influx::series_key(info.group(), info.name().unwrap_or("series"), metadata);
+            (measurement, tags, influx::field_names(info.block().channel_names(), info.value_len() as usize))
+        };
+        influx::write_lines(&mut out, &measurement, &tags, &fields, lazy.entries(n)?, precision)?;
+    } else {
+        influx::export(&mut lazy, &mut out, precision)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn import(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &[], &["--precision", "--chunk", "-o"])?;
+    let path = args.single("INPUT")?;
+    let out = args.value("-o").ok_or_else(|| CliError::Usage("missing -o OUTPUT".to_string()))?;
+    let precision = precision(&args)?;
+    let chunk = args.parsed::<usize>("--chunk")?.unwrap_or(65_536);
+    if chunk == 0 {
+        return Err(CliError::Usage("--chunk must be positive".to_string()));
+    }
+
+    let mut writer = broto::File::create(out)?;
+    let log = LogBlockBuilder::new()
+        .program("broto influx import")
+        .info(format!("imported {}", path))
+        .build();
+    writer.write_log(&log)?;
+    let mut importer = influx::Importer::new(writer, precision).chunk_rows(chunk);
+    importer.read_lines(input(path)?)?;
+    importer.finish()?.close()?;
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use std::io::Seek;
+use broto::{self, Block, Error};
+use args::Args;
+use {CliError, open};
+
+pub const USAGE: &str = "broto info [--segments] FILE
+
+Prints the header version, the list of blocks and the shape of each series.
+  --segments        read files joined with cat as one file";
+
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--segments"], &[])?;
+    let path = args.single("FILE")?;
+
+    let mut reader = open(path, args.flag("--segments"))?;
+    reader.initialize()?;
+    {
+        let header = reader.header().unwrap();
+        println!("{}: broto format {}.{}", path, header.major_version(), header.minor_version());
+    }
+
+    println!("{:>12}  {:<6} {:<16} description", "offset", "block", "group");
+    let mut n_series = 0;
+    let mut n_rows = 0;
+    loop {
+        let offset = reader.get_mut().stream_position()?;
+        let block = match reader.next_block() {
+            Ok(block) => block,
+            Err(Error::EndOfFile) => break,
+            Err(e) => { return Err(e.into()); },
+        };
+        let (kind, description) = match block {
+            Block::Log(ref log) => {
+                ("log", format!("{} {}: {}", broto::timestamp::format_rfc3339(log.time()),
+                                log.program(), log.info()))
+            },
+            Block::Attr(ref attr) => ("attr", format!("{} = {}", attr.key(), attr.value())),
+            Block::Event(ref event) => ("event", describe_event(event)),
+            Block::Group(ref group) => ("group", group.path().to_string()),
+            Block::F64TS(ref fts) => {
+                reader.skip_f64ts(fts)?;
+                let length = fts.length().unwrap_or(0);
+                n_series += 1;
+                n_rows += length;
+                let name = match fts.name() {
+                    Some(name) => format!("{} (id {})", name, fts.id()),
+                    None => "(unnamed)".to_string(),
+                };
+                ("f64ts", format!("{}: {} index + {} values, {} rows",
+                                  name, fts.index_len(), fts.value_len(), length))
+            },
+        };
+        println!("{:>12}  {:<6} {:<16} {}", offset, kind, reader.current_group(), description);
+    }
+    println!("{} series, {} rows", n_series, n_rows);
+    Ok(())
+}
+
+/// Describes an event as `ONSET[+DURATION] TEXT`.
+pub fn describe_event(event: &broto::EventBlock) -> String {
+    match event.duration() {
+        Some(d) => format!("{}+{} {}", event.onset(), d, event.text()),
+        None => format!("{} {}", event.onset(), event.text()),
+    }
+}
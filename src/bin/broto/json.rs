@@ -0,0 +1,56 @@
+use std::io::Write;
+use broto::json;
+use args::Args;
+use {CliError, select_series, output, open_lazy};
+
+pub const USAGE: &str = "broto json [OPTIONS] FILE
+       broto json rows [OPTIONS] FILE
+
+json writes the header, the logs, attributes and events of every group and
+the descriptors of the series as one JSON document.
+  --pretty          indent the document
+  --segments        read files joined with cat as one file
+  -o OUTPUT         output file (default: standard output)
+
+json rows writes the entries as NDJSON, one object per line:
+  {\"series\": PATH, \"index\": INDEX, \"values\": {CHANNEL: VALUE, ...}}
+The values are an array if the channels are unnamed. NaN and infinite
+numbers are written as null.
+  --series NAME     series name, path or #N (default: all series)
+  --segments        read files joined with cat as one file
+  -o OUTPUT         output file (default: standard output)";
+
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    match args.first().map(|s| s.as_str()) {
+        Some("rows") => rows(&args[1..]),
+        _ => describe(args),
+    }
+}
+
+fn describe(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--pretty", "--segments"], &["-o"])?;
+    let path = args.single("FILE")?;
+
+    let lazy = open_lazy(path, args.flag("--segments"))?;
+    let mut out = output(args.value("-o"))?;
+    json::write_json(&lazy, &mut out, args.flag("--pretty"))?;
+    out.flush()?;
+    Ok(())
+}
+
+fn rows(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--segments"], &["--series", "-o"])?;
+    let path = args.single("FILE")?;
+
+    let mut lazy = open_lazy(path, args.flag("--segments"))?;
+    let mut out = output(args.value("-o"))?;
+    if args.value("--series").is_some() {
+        let n = select_series(&lazy, args.value("--series"))?;
+        let (series, channels, value_len) = json::series_key(&lazy, n);
+        json::write_ndjson(&mut out, &series, &channels, value_len, lazy.entries(n)?)?;
+    } else {
+        json::export_ndjson(&mut lazy, &mut out)?;
+    }
+    out.flush()?;
+    Ok(())
+}
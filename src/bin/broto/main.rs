@@ -0,0 +1,234 @@
+extern crate broto;
+
+mod args;
+mod info;
+mod dump;
+mod csv;
+mod npy;
+mod wav;
+mod edf;
+mod mat;
+mod influx;
+mod json;
+mod check;
+mod merge;
+mod concat;
+mod slice;
+mod split;
+mod tail;
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+
+const USAGE: &str = "usage: broto COMMAND [OPTIONS] FILE...
+
+Commands:
+  info    print the header, the blocks and the series shapes of a file
+  dump    print the logs and the first and last rows of each series
+  csv     export a series to CSV/TSV or import one from it
+  npy     export series to NumPy .npy/.npz or import them from it
+  wav     export a series as WAV audio or import one from it
+  edf     import an EDF/EDF+ recording
+  mat     export a file to a MATLAB MAT-file or import one from it
+  influx  export series as InfluxDB line protocol or import them from it
+  json    print the structure of a file as JSON or its rows as NDJSON
+  check   verify the integrity of files
+  merge   merge files by interleaving their series on the index
+  concat  join files end to end
+  slice   copy the entries in an index range to a new file
+  split   split a file into parts of a number of rows or bytes
+  tail    print the last rows of each series, or follow a growing file
+  help    print the usage of a command
+
+Exit status:
+  0  success          3  I/O error                6  undefined block
+  1  check failed     4  unexpected end of file   7  invalid UTF-8 string
+  2  usage error      5  bad magic number         8  inconsistent series shape
+                                                  9  invalid input format";
+
+/// An error of a subcommand.
+#[derive(Debug)]
+pub enum CliError {
+    Usage(String),
+    Broto(broto::Error),
+    /// The command ran but found the input to be invalid.
+    Failed(String),
+}
+
+impl CliError {
+    fn exit_code(&self) -> i32 {
+        match *self {
+            CliError::Failed(_) => 1,
+            CliError::Usage(_) => 2,
+            CliError::Broto(ref e) => match *e {
+                broto::Error::Io(_) => 3,
+                broto::Error::EndOfFile => 4,
+                broto::Error::Magic => 5,
+                broto::Error::UndefinedBlock => 6,
+                broto::Error::FromUtf8(_) => 7,
+                broto::Error::Shape => 8,
+                broto::Error::Parse(_) => 9,
+            },
+        }
+    }
+}
+
+impl From<broto::Error> for CliError {
+    fn from(err: broto::Error) -> Self {
+        CliError::Broto(err)
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(err: io::Error) -> Self {
+        CliError::Broto(err.into())
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CliError::Usage(ref msg) => write!(f, "{}", msg),
+            CliError::Broto(ref err) => write!(f, "{}", err),
+            CliError::Failed(ref msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+/// Finds the series given by `--series`: a name, a path or `#N` for the
+/// `N`-th series. Without it, the file must contain exactly one series.
+pub fn select_series<R>(lazy: &broto::LazyReader<R>, which: Option<&str>) -> Result<usize, CliError>
+    where R: io::Read + io::Seek
+{
+    let series = lazy.series();
+    let found = match which {
+        None if series.len() == 1 => Some(0),
+        None if series.is_empty() => {
+            return Err(CliError::Usage("the file contains no series".to_string()));
+        },
+        None => {
+            return Err(CliError::Usage("the file contains several series; choose one with --series".to_string()));
+        },
+        Some(which) if which.starts_with('#') => {
+            which[1..].parse::<usize>().ok().filter(|&n| n < series.len())
+        },
+        Some(which) if which.starts_with('/') => {
+            let path = broto::group::normalize(which);
+            series.iter().position(|s| {
+                s.name().map(|name| broto::group::join(s.group(), name)) == Some(path.clone())
+            })
+        },
+        Some(which) => series.iter().position(|s| s.name() == Some(which)),
+    };
+    found.ok_or_else(|| CliError::Usage(format!("no such series: {}", which.unwrap_or(""))))
+}
+
+/// Opens a broto file for reading; with `segments` (`--segments`), files
+/// joined with `cat` are read as one.
+pub fn open(path: &str, segments: bool) -> Result<broto::Reader<io::BufReader<fs::File>>, CliError> {
+    let file = io::BufReader::new(fs::File::open(path)?);
+    Ok(broto::Reader::new(file).accept_segments(segments))
+}
+
+/// Opens a broto file like `open` and scans it with a `LazyReader`.
+pub fn open_lazy(path: &str, segments: bool) -> Result<broto::LazyReader<io::BufReader<fs::File>>, CliError> {
+    Ok(broto::LazyReader::from_reader(open(path, segments)?)?)
+}
+
+/// Opens `path` for writing, or the standard output if it is `None` or `-`.
+pub fn output(path: Option<&str>) -> Result<Box<dyn io::Write>, CliError> {
+    match path {
+        None | Some("-") => Ok(Box::new(io::BufWriter::new(io::stdout()))),
+        Some(path) => Ok(Box::new(io::BufWriter::new(fs::File::create(path)?))),
+    }
+}
+
+/// Opens `path` for reading, or the standard input if it is `-`.
+pub fn input(path: &str) -> Result<Box<dyn io::BufRead>, CliError> {
+    match path {
+        "-" => Ok(Box::new(io::BufReader::new(io::stdin()))),
+        path => Ok(Box::new(io::BufReader::new(fs::File::open(path)?))),
+    }
+}
+
+/// Returns the file stem of `path`, used to name imported series.
+pub fn stem(path: &str) -> String {
+    Path::new(path).file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "series".to_string())
+}
+
+fn command_usage(command: &str) -> Option<&'static str> {
+    match command {
+        "info" => Some(info::USAGE),
+        "dump" => Some(dump::USAGE),
+        "csv" => Some(csv::USAGE),
+        "npy" => Some(npy::USAGE),
+        "wav" => Some(wav::USAGE),
+        "edf" => Some(edf::USAGE),
+        "mat" => Some(mat::USAGE),
+        "influx" => Some(influx::USAGE),
+        "json" => Some(json::USAGE),
+        "check" => Some(check::USAGE),
+        "merge" => Some(merge::USAGE),
+        "concat" => Some(concat::USAGE),
+        "slice" => Some(slice::USAGE),
+        "split" => Some(split::USAGE),
+        "tail" => Some(tail::USAGE),
+        _ => None,
+    }
+}
+
+fn run(args: &[String]) -> Result<(), CliError> {
+    let command = match args.first() {
+        Some(command) => command.as_str(),
+        None => { return Err(CliError::Usage("missing command".to_string())); },
+    };
+    let rest = &args[1..];
+    // Only right after the command, where it cannot be the value of an
+    // option.
+    if rest.first().is_some_and(|a| a == "-h" || a == "--help") {
+        println!("usage: {}", command_usage(command).unwrap_or(USAGE));
+        return Ok(());
+    }
+    match command {
+        "info" => info::run(rest),
+        "dump" => dump::run(rest),
+        "csv" => csv::run(rest),
+        "npy" => npy::run(rest),
+        "wav" => wav::run(rest),
+        "edf" => edf::run(rest),
+        "mat" => mat::run(rest),
+        "influx" => influx::run(rest),
+        "json" => json::run(rest),
+        "check" => check::run(rest),
+        "merge" => merge::run(rest),
+        "concat" => concat::run(rest),
+        "slice" => slice::run(rest),
+        "split" => split::run(rest),
+        "tail" => tail::run(rest),
+        "help" | "-h" | "--help" => {
+            match rest.first().and_then(|c| command_usage(c)) {
+                Some(usage) => println!("usage: {}", usage),
+                None => println!("{}", USAGE),
+            }
+            Ok(())
+        },
+        _ => Err(CliError::Usage(format!("unknown command: {}", command))),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(err) = run(&args) {
+        eprintln!("broto: {}", err);
+        if let CliError::Usage(_) = err {
+            eprintln!("{}", USAGE);
+        }
+        process::exit(err.exit_code());
+    }
+}
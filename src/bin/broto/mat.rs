@@ -0,0 +1,53 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use broto::{self, LogBlockBuilder, PendingFile};
+use broto::mat;
+use args::Args;
+use {CliError, output, open};
+
+pub const USAGE: &str = "broto mat export [--segments] -o OUTPUT FILE
+       broto mat import -o OUTPUT INPUT
+
+export writes a MATLAB Level 5 MAT-file (uncompressed). Each series is a
+double matrix with the index in the first column, and the cell arrays
+series, attrs, logs and events describe the file.
+  --segments        read files joined with cat as one file
+
+import reads such a MAT-file. In other MAT-files, each double matrix
+becomes a series named after its variable.";
+
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    match args.first().map(|s| s.as_str()) {
+        Some("export") => export(&args[1..]),
+        Some("import") => import(&args[1..]),
+        _ => Err(CliError::Usage("expected mat export or mat import".to_string())),
+    }
+}
+
+fn export(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--segments"], &["-o"])?;
+    let path = args.single("FILE")?;
+    let out = args.value("-o").ok_or_else(|| CliError::Usage("missing -o OUTPUT".to_string()))?;
+
+    let dataset = broto::load_from_reader(open(path, args.flag("--segments"))?)?;
+    let mut out = mat::write_mat(output(Some(out))?, &dataset)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn import(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &[], &["-o"])?;
+    let path = args.single("INPUT")?;
+    let out = args.value("-o").ok_or_else(|| CliError::Usage("missing -o OUTPUT".to_string()))?;
+
+    let mut dataset = mat::read_mat(&mut io::BufReader::new(fs::File::open(path)?))?;
+    let log = LogBlockBuilder::new()
+        .program("broto mat import")
+        .info(format!("imported {}", path))
+        .build();
+    dataset.metadata_mut().get_logs_mut().push(log);
+
+    broto::save(PendingFile::create(Path::new(out))?, &dataset)?.commit()?;
+    Ok(())
+}
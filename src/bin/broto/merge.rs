@@ -0,0 +1,35 @@
+use broto::{self, LogBlockBuilder};
+use broto::merge::{self, Duplicates};
+use args::Args;
+use {CliError, open_lazy};
+
+pub const USAGE: &str = "broto merge [OPTIONS] -o OUTPUT FILE...
+
+Merges files into one. Series with the same group, name and channel layout
+are combined into one series ordered by index; the entries of each file
+must be in order. Logs, attributes and events are carried over, and a log
+recording the merge is added.
+  --duplicates P    what to do with entries of a series with the same index:
+                    first (keep the earliest file's), last or error
+                    (default first)
+  --segments        read files joined with cat as one file
+  -o OUTPUT         output file";
+
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--segments"], &["--duplicates", "-o"])?;
+    let paths = args.many("FILE")?;
+    let out = args.value("-o").ok_or_else(|| CliError::Usage("missing -o OUTPUT".to_string()))?;
+    let duplicates = args.parsed::<Duplicates>("--duplicates")?.unwrap_or(Duplicates::First);
+
+    let mut inputs = Vec::new();
+    for path in paths {
+        inputs.push(open_lazy(path, args.flag("--segments"))?);
+    }
+    let log = LogBlockBuilder::new()
+        .program("broto merge")
+        .info(format!("merged {}", paths.join(", ")))
+        .build();
+    let writer = broto::File::create(out)?;
+    merge::merge(&mut inputs, writer, duplicates, Some(&log))?.close()?;
+    Ok(())
+}
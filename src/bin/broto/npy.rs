@@ -0,0 +1,83 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use broto::{self, Dataset, LogBlockBuilder, PendingFile};
+use broto::npy::{self, Part};
+use args::Args;
+use {CliError, select_series, output, stem, open_lazy};
+
+pub const USAGE: &str = "broto npy export [OPTIONS] -o OUTPUT FILE
+       broto npy import [OPTIONS] -o OUTPUT INPUT
+
+export writes NumPy arrays. If OUTPUT ends with .npz, every series is
+written as the arrays PATH/index and PATH/values; otherwise one series is
+written as a .npy array with the index in the first column.
+  --series NAME     series name, path or #N (required if there are several)
+  --segments        read files joined with cat as one file
+
+import reads a .npy array with the index in the first column, or the
+index/values pairs of an uncompressed .npz archive.
+  --name NAME       series name (default: the file name of INPUT)
+  --group PATH      group of a series read from a .npy file";
+
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    match args.first().map(|s| s.as_str()) {
+        Some("export") => export(&args[1..]),
+        Some("import") => import(&args[1..]),
+        _ => Err(CliError::Usage("expected npy export or npy import".to_string())),
+    }
+}
+
+fn is_npz(path: &str) -> bool {
+    path.to_lowercase().ends_with(".npz")
+}
+
+fn export(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--segments"], &["--series", "-o"])?;
+    let path = args.single("FILE")?;
+    let out = args.value("-o").ok_or_else(|| CliError::Usage("missing -o OUTPUT".to_string()))?;
+
+    let mut lazy = open_lazy(path, args.flag("--segments"))?;
+    if is_npz(out) {
+        let mut out = npy::write_npz(output(Some(out))?, &mut lazy)?;
+        out.flush()?;
+    } else {
+        let n = select_series(&lazy, args.value("--series"))?;
+        let (rows, value_len) = {
+            let info = &lazy.series()[n];
+            (info.length() as usize, info.value_len() as usize)
+        };
+        let mut out = output(Some(out))?;
+        npy::write_npy_entries(&mut out, rows, value_len, lazy.entries(n)?, Part::Both)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+fn import(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &[], &["--name", "--group", "-o"])?;
+    let path = args.single("INPUT")?;
+    let out = args.value("-o").ok_or_else(|| CliError::Usage("missing -o OUTPUT".to_string()))?;
+    let name = args.value("--name").map(|s| s.to_string()).unwrap_or_else(|| stem(path));
+
+    let mut dataset = if is_npz(path) {
+        npy::read_npz_dataset(&mut fs::File::open(path)?, &name)?
+    } else {
+        let array = npy::read_npy(&mut io::BufReader::new(fs::File::open(path)?))?;
+        let mut series = npy::series_from_array(&name, &array)?;
+        if let Some(group) = args.value("--group") {
+            series = series.with_group(group);
+        }
+        let mut dataset = Dataset::new();
+        dataset.add_series(series);
+        dataset
+    };
+    let log = LogBlockBuilder::new()
+        .program("broto npy import")
+        .info(format!("imported {}", path))
+        .build();
+    dataset.metadata_mut().get_logs_mut().push(log);
+
+    broto::save(PendingFile::create(Path::new(out))?, &dataset)?.commit()?;
+    Ok(())
+}
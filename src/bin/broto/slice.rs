@@ -0,0 +1,44 @@
+use broto::{self, LogBlockBuilder};
+use broto::slice::{self, Range};
+use args::Args;
+use {CliError, open_lazy};
+
+pub const USAGE: &str = "broto slice [OPTIONS] -o OUTPUT FILE
+
+Writes the entries of each series whose index is in [T0, T1) to a new file
+with all the logs, attributes and events, and a log recording the range.
+T0 and T1 are numbers or RFC 3339 timestamps, which stand for seconds since
+the UNIX epoch.
+  --from T0         first index (default: unbounded)
+  --to T1           end of the range (default: unbounded)
+  --segments        read files joined with cat as one file
+  -o OUTPUT         output file";
+
+/// Parses an index given as a number or an RFC 3339 timestamp.
+pub fn parse_index(name: &str, s: &str) -> Result<f64, CliError> {
+    if let Ok(x) = s.parse::<f64>() {
+        return Ok(x);
+    }
+    match broto::timestamp::parse_rfc3339(s) {
+        Some(time) => Ok(time.as_secs() as f64 + time.subsec_nanos() as f64 * 1e-9),
+        None => Err(CliError::Usage(format!("invalid value for {}: {}", name, s))),
+    }
+}
+
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--segments"], &["--from", "--to", "-o"])?;
+    let path = args.single("FILE")?;
+    let out = args.value("-o").ok_or_else(|| CliError::Usage("missing -o OUTPUT".to_string()))?;
+    let from = args.value("--from").map(|s| parse_index("--from", s)).transpose()?;
+    let to = args.value("--to").map(|s| parse_index("--to", s)).transpose()?;
+
+    let mut input = open_lazy(path, args.flag("--segments"))?;
+    let log = LogBlockBuilder::new()
+        .program("broto slice")
+        .info(format!("index [{}, {}) of {}",
+                      args.value("--from").unwrap_or("-inf"), args.value("--to").unwrap_or("inf"), path))
+        .build();
+    let writer = broto::File::create(out)?;
+    slice::extract(&mut input, writer, Range::Index { from, to }, Some(&log))?.close()?;
+    Ok(())
+}
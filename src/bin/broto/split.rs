@@ -0,0 +1,56 @@
+use std::path::Path;
+use broto::{self, LogBlockBuilder};
+use broto::slice::{self, Range, Split};
+use args::Args;
+use {CliError, stem, open_lazy};
+
+pub const USAGE: &str = "broto split (--rows N | --bytes N) [-o PATTERN] FILE
+
+Splits a file into parts, each with all the logs, attributes and events and
+a log recording the rows it holds. Part k holds rows (k-1)*R up to k*R of
+each series.
+  --rows N          R = N
+  --bytes N         R such that no part exceeds N bytes
+  --segments        read files joined with cat as one file
+  -o PATTERN        output files, where {} is replaced by the part number
+                    (default: STEM-{}.broto next to FILE)";
+
+fn log(start: u64, end: u64, path: &str) -> broto::LogBlock {
+    LogBlockBuilder::new()
+        .program("broto split")
+        .info(format!("rows {}..{} of {}", start, end, path))
+        .build()
+}
+
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--segments"], &["--rows", "--bytes", "-o"])?;
+    let path = args.single("FILE")?;
+    let split = match (args.parsed::<u64>("--rows")?, args.parsed::<u64>("--bytes")?) {
+        (Some(n), None) if n > 0 => Split::Rows(n),
+        (None, Some(n)) if n > 0 => Split::Bytes(n),
+        _ => { return Err(CliError::Usage("expected either a positive --rows or --bytes".to_string())); },
+    };
+    let pattern = match args.value("-o") {
+        Some(pattern) if pattern.contains("{}") => pattern.to_string(),
+        Some(_) => { return Err(CliError::Usage("-o PATTERN must contain {}".to_string())); },
+        None => {
+            let name = format!("{}-{{}}.broto", stem(path));
+            Path::new(path).with_file_name(name).to_string_lossy().into_owned()
+        },
+    };
+
+    let mut input = open_lazy(path, args.flag("--segments"))?;
+    // The numbers in the log of any part are at most the largest length.
+    let max_len = (0..input.series().len())
+        .map(|n| input.chunks(n).iter().map(|&m| input.series()[m].length()).sum::<u64>())
+        .max().unwrap_or(0);
+    let (rows, parts) = slice::rows_per_part(&mut input, split, &log(max_len, max_len, path))?;
+    let width = parts.to_string().len();
+    for part in 0..parts {
+        let (start, end) = (part * rows, ((part + 1) * rows).min(max_len));
+        let out = pattern.replace("{}", &format!("{:0width$}", part + 1, width = width));
+        let writer = broto::File::create(&out)?;
+        slice::extract(&mut input, writer, Range::Rows { start, end }, Some(&log(start, end, path)))?.close()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,97 @@
+use std::collections::VecDeque;
+use broto::{self, Block, Entry};
+use broto::follow::{Follower, Item};
+use args::Args;
+use CliError;
+
+pub const USAGE: &str = "broto tail [-f] [-n ROWS] [--series SERIES] FILE
+
+Prints the last ROWS rows (default 10) of each series. With -f, keeps
+waiting for the rows and the series appended to a file still being written
+until interrupted.
+  -f                follow the file as it grows
+  --series SERIES   only this series: a name, a path or #N";
+
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["-f"], &["-n", "--series"])?;
+    let path = args.single("FILE")?;
+    let rows = args.parsed::<usize>("-n")?.unwrap_or(10);
+    let which = args.value("--series");
+
+    let mut follower = broto::File::follow(path)?;
+    let mut tails: Vec<(String, VecDeque<Entry>)> = Vec::new();
+    let mut count = 0;
+    while let Some(item) = follower.try_next()? {
+        match item {
+            Item::Block(Block::F64TS(_)) => {
+                let title = title(&follower, count);
+                count += 1;
+                if selected(&title, count - 1, which) {
+                    tails.push((title, VecDeque::new()));
+                }
+            },
+            Item::Entry(entry) if selected(&title(&follower, count - 1), count - 1, which) => {
+                let tail = &mut tails.last_mut().unwrap().1;
+                if tail.len() == rows {
+                    tail.pop_front();
+                }
+                if rows > 0 {
+                    tail.push_back(entry);
+                }
+            },
+            _ => {},
+        }
+    }
+    for (title, tail) in tails.iter() {
+        println!("series {}", title);
+        for entry in tail {
+            print_entry(entry);
+        }
+    }
+    if !args.flag("-f") {
+        return Ok(());
+    }
+
+    let mut current = tails.last().map(|t| t.0.clone());
+    loop {
+        match follower.next_item()? {
+            Item::Block(Block::F64TS(_)) => { count += 1; },
+            Item::Entry(entry) => {
+                let title = title(&follower, count - 1);
+                if !selected(&title, count - 1, which) {
+                    continue;
+                }
+                if current.as_ref() != Some(&title) {
+                    println!("series {}", title);
+                    current = Some(title);
+                }
+                print_entry(&entry);
+            },
+            _ => {},
+        }
+    }
+}
+
+// The name of the series being read, as printed by `broto dump`.
+fn title<R>(follower: &Follower<R>, n: usize) -> String
+    where R: ::std::io::Read + ::std::io::Seek
+{
+    match follower.current_series().and_then(|b| b.name()) {
+        Some(name) => broto::group::join(follower.current_group(), name),
+        None => format!("#{}", n),
+    }
+}
+
+fn selected(title: &str, n: usize, which: Option<&str>) -> bool {
+    match which {
+        None => true,
+        Some(which) if which.starts_with('#') => which[1..].parse::<usize>().ok() == Some(n),
+        Some(which) if which.starts_with('/') => broto::group::normalize(which) == title,
+        Some(which) => broto::group::base_name(title) == which && !title.starts_with('#'),
+    }
+}
+
+fn print_entry(entry: &Entry) {
+    let values: Vec<String> = entry.1.iter().map(|x| x.to_string()).collect();
+    println!("  {}\t{}", entry.0, values.join("\t"));
+}
@@ -0,0 +1,114 @@
+use std::fs;
+use std::io::{self, Write};
+use broto::{self, F64TSBlockBuilder, LogBlockBuilder};
+use broto::wav::{self, SampleFormat, WavReader, WavSpec};
+use args::Args;
+use {CliError, select_series, output, stem, open_lazy};
+
+pub const USAGE: &str = "broto wav export [OPTIONS] -o OUTPUT FILE
+       broto wav import [OPTIONS] -o OUTPUT INPUT
+
+export writes a series as a WAV file with one channel per value. The
+header is taken from the wav.* attributes of the group of the series.
+  --series NAME     series name, path or #N (required if there are several)
+  --format F        pcm16, pcm24 or float32 (default: as imported, or float32)
+  --rate HZ         sample rate (default: as imported, or from the index step)
+  --segments        read files joined with cat as one file
+
+import reads a PCM16, PCM24 or float32 WAV file.
+  --name NAME       series name (default: the file name of INPUT)
+  --group PATH      group of the series and of the wav.* attributes";
+
+pub fn run(args: &[String]) -> Result<(), CliError> {
+    match args.first().map(|s| s.as_str()) {
+        Some("export") => export(&args[1..]),
+        Some("import") => import(&args[1..]),
+        _ => Err(CliError::Usage("expected wav export or wav import".to_string())),
+    }
+}
+
+fn export(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--segments"], &["--series", "--format", "--rate", "-o"])?;
+    let path = args.single("FILE")?;
+    let out = args.value("-o").ok_or_else(|| CliError::Usage("missing -o OUTPUT".to_string()))?;
+
+    let mut lazy = open_lazy(path, args.flag("--segments"))?;
+    let n = select_series(&lazy, args.value("--series"))?;
+    let (frames, value_len, group) = {
+        let info = &lazy.series()[n];
+        (info.length(), info.value_len(), info.group().to_string())
+    };
+    if value_len == 0 || value_len > u16::MAX as u64 {
+        return Err(CliError::Usage(format!("cannot write {} channels", value_len)));
+    }
+    let imported = match lazy.group(&group) {
+        Some(metadata) => WavSpec::from_metadata(metadata)?,
+        None => None,
+    };
+    let imported = imported.filter(|spec| spec.channels() as u64 == value_len);
+
+    let format = match args.value("--format") {
+        Some(format) => format.parse()?,
+        None => imported.as_ref().map(|spec| spec.format()).unwrap_or(SampleFormat::Float32),
+    };
+    let rate = match args.parsed::<u32>("--rate")? {
+        Some(rate) => rate,
+        None => match imported {
+            Some(ref spec) => spec.sample_rate(),
+            None => {
+                let mut entries = lazy.entries(n)?;
+                match (entries.next(), entries.next()) {
+                    (Some(first), Some(second)) => (1.0 / (second?.0 - first?.0)).round() as u32,
+                    _ => 0,
+                }
+            },
+        },
+    };
+    if rate == 0 {
+        return Err(CliError::Usage("cannot determine the sample rate; give --rate".to_string()));
+    }
+    let mut spec = WavSpec::new(value_len as u16, rate, format);
+    if let Some(mask) = imported.and_then(|spec| spec.get_channel_mask()) {
+        spec = spec.channel_mask(mask);
+    }
+
+    let mut out = output(Some(out))?;
+    wav::write_wav(&mut out, &spec, frames, lazy.entries(n)?)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn import(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &[], &["--name", "--group", "-o"])?;
+    let path = args.single("INPUT")?;
+    let out = args.value("-o").ok_or_else(|| CliError::Usage("missing -o OUTPUT".to_string()))?;
+    let name = args.value("--name").map(|s| s.to_string()).unwrap_or_else(|| stem(path));
+
+    let reader = WavReader::new(io::BufReader::new(fs::File::open(path)?))?;
+    let spec = reader.spec().clone();
+
+    let mut writer = broto::File::create(out)?;
+    let log = LogBlockBuilder::new()
+        .program("broto wav import")
+        .info(format!("imported {} from {}", name, path))
+        .build();
+    writer.write_log(&log)?;
+    if let Some(group) = args.value("--group") {
+        writer.write_group(group)?;
+    }
+    for attr in spec.to_attrs().iter() {
+        writer.write_attr(attr)?;
+    }
+    let block = F64TSBlockBuilder::new()
+        .index_len(1)
+        .value_len(spec.channels() as u64)
+        .name(name)
+        .build();
+    let mut w = writer.write_f64ts_with_seek(block)?;
+    for entry in reader {
+        let (index, values) = entry?;
+        w.write_entry(index, &values)?;
+    }
+    w.finalize()?.finish().close()?;
+    Ok(())
+}
@@ -0,0 +1,364 @@
+//! Integrity check of a file.
+//!
+//! `check` walks every block without trusting the file: it verifies the
+//! magic numbers, that each block contains exactly what its size declares
+//! (plus zero padding), that the entries of each series fit in the file,
+//! and looks at every entry for decreasing indices and NaN or infinite
+//! numbers. Each problem is reported with the offset where it was found.
+//!
+//! After a bad block magic number the check resumes at the next block magic
+//! number; bytes after the last block are reported as trailing garbage.
+
+use std::fmt;
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+use byteorder::{LittleEndian, ReadBytesExt};
+use header::{Header, BlockHeader, LogBlock, F64TSBlock, GroupBlock, AttrBlock, EventBlock};
+use error::{Result, Error};
+
+/// A problem found by `check`.
+#[derive(Debug,Clone,PartialEq)]
+pub enum Problem {
+    /// The file ends inside the header or a block.
+    Truncated { expected: u64, available: u64 },
+    /// The file does not start with the magic number.
+    Magic,
+    /// The size in the header is not the size of the header.
+    HeaderSize(u64),
+    /// A block does not start with the block magic number; the bytes up to
+    /// the next one were skipped.
+    BlockMagic { skipped: u64 },
+    UndefinedBlock(String),
+    InvalidUtf8,
+    /// A field of a block has a value which cannot be read.
+    InvalidField(String),
+    /// The contents of a block extend past its declared size.
+    BlockOverrun { declared: u64 },
+    /// A block declares more bytes than its contents, and the rest is not
+    /// zero padding.
+    BlockSize { declared: u64, consumed: u64 },
+    /// The entries of a series extend past the end of the file.
+    PayloadSize { expected: u64, available: u64 },
+    /// A series of length 0 is followed by `available` bytes which are not
+    /// a block, as left by a writer which stopped before finalizing the
+    /// length. `File::recover` truncates them.
+    Unfinalized { available: u64 },
+    TrailingGarbage(u64),
+    /// The index decreases, first at `row`; `count` is the number of rows
+    /// where it does.
+    NonMonotonicIndex { row: u64, previous: f64, index: f64, count: u64 },
+    /// A number is NaN or infinite, first at `row` and `column` (0 is the
+    /// index); `count` is the number of such numbers in the series.
+    NonFinite { row: u64, column: u64, count: u64 },
+}
+
+impl Problem {
+    /// Whether the problem makes the file unreadable in part. Decreasing
+    /// indices and non-finite numbers are only suspicious.
+    pub fn is_error(&self) -> bool {
+        !matches!(*self, Problem::NonMonotonicIndex { .. } | Problem::NonFinite { .. })
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Problem::Truncated { expected, available } =>
+                write!(f, "truncated: {} bytes expected, {} available", expected, available),
+            Problem::Magic => write!(f, "bad magic number"),
+            Problem::HeaderSize(size) => write!(f, "header size is {}", size),
+            Problem::BlockMagic { skipped } =>
+                write!(f, "bad block magic number, skipped {} bytes", skipped),
+            Problem::UndefinedBlock(ref name) => write!(f, "undefined block {:?}", name),
+            Problem::InvalidUtf8 => write!(f, "invalid UTF-8 string"),
+            Problem::InvalidField(ref message) => write!(f, "invalid field: {}", message),
+            Problem::BlockOverrun { declared } =>
+                write!(f, "contents extend past the block size {}", declared),
+            Problem::BlockSize { declared, consumed } =>
+                write!(f, "block size is {} but the contents take {} bytes", declared, consumed),
+            Problem::PayloadSize { expected, available } =>
+                write!(f, "entries take {} bytes but {} remain", expected, available),
+            Problem::Unfinalized { available } =>
+                write!(f, "series of length 0 followed by {} bytes of entries (unfinalized; see File::recover)",
+                       available),
+            Problem::TrailingGarbage(len) => write!(f, "{} bytes of trailing garbage", len),
+            Problem::NonMonotonicIndex { row, previous, index, count } =>
+                write!(f, "index decreases from {} to {} at row {} ({} rows)", previous, index, row, count),
+            Problem::NonFinite { row, column, count } =>
+                write!(f, "non-finite number at row {} column {} ({} numbers)", row, column, count),
+        }
+    }
+}
+
+/// A problem and the offset in bytes where it was found.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Issue {
+    pub offset  : u64,
+    pub problem : Problem,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "offset {}: {}", self.offset, self.problem)
+    }
+}
+
+/// The result of `check`.
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct Report {
+    blocks : u64,
+    series : u64,
+    rows   : u64,
+    issues : Vec<Issue>,
+}
+
+impl Report {
+    /// Number of blocks read, including undefined ones.
+    pub fn blocks(&self) -> u64 {
+        self.blocks
+    }
+
+    pub fn series(&self) -> u64 {
+        self.series
+    }
+
+    pub fn rows(&self) -> u64 {
+        self.rows
+    }
+
+    pub fn issues(&self) -> &[Issue] {
+        &self.issues
+    }
+
+    /// Whether no issue is an error.
+    pub fn is_ok(&self) -> bool {
+        self.issues.iter().all(|i| !i.problem.is_error())
+    }
+
+    fn push(&mut self, offset: u64, problem: Problem) {
+        self.issues.push(Issue { offset, problem });
+    }
+}
+
+/// Checks the file read from `stream`. Only I/O errors are returned as
+/// errors; everything wrong with the contents is in the report.
+pub fn check<R: io::Read + io::Seek>(stream: R) -> Result<Report> {
+    let mut stream = io::BufReader::new(stream);
+    let end = stream.seek(SeekFrom::End(0))?;
+    stream.seek(SeekFrom::Start(0))?;
+    let mut report = Report::default();
+
+    let header_size = Header::new().size() as u64;
+    let mut magic = [0; 8];
+    if end >= 8 {
+        stream.read_exact(&mut magic)?;
+    }
+    if end >= 8 && !Header::check_magic(&magic) {
+        report.push(0, Problem::Magic);
+        return Ok(report);
+    }
+    if end < header_size {
+        report.push(0, Problem::Truncated { expected: header_size, available: end });
+        return Ok(report);
+    }
+    stream.seek(SeekFrom::Start(0))?;
+    let header = match Header::read_from(&mut stream) {
+        Ok(header) => header,
+        Err(Error::Parse(message)) => {
+            report.push(16, Problem::InvalidField(message));
+            return Ok(report);
+        },
+        Err(e) => { return Err(e); },
+    };
+    if header.header_size() != header_size {
+        report.push(8, Problem::HeaderSize(header.header_size()));
+    }
+
+    let mut pos = header_size;
+    while pos < end {
+        match check_block(&mut stream, pos, end, &mut report)? {
+            Some(next) => { pos = next; },
+            None => break,
+        }
+    }
+    Ok(report)
+}
+
+// Returns the offset of the first block magic number in `from..end`.
+fn find_block_magic<R: io::Read + io::Seek>(stream: &mut R, from: u64, end: u64) -> Result<Option<u64>> {
+    let magic = BlockHeader::clone_magic();
+    let mut pos = from;
+    let mut buf = vec![0; 1 << 16];
+    while pos + 8 <= end {
+        stream.seek(SeekFrom::Start(pos))?;
+        let len = ((end - pos) as usize).min(buf.len());
+        stream.read_exact(&mut buf[..len])?;
+        if let Some(n) = buf[..len].windows(8).position(|w| w == magic) {
+            return Ok(Some(pos + n as u64));
+        }
+        // The magic number may straddle the end of the buffer.
+        pos += (len as u64).saturating_sub(7).max(1);
+    }
+    Ok(None)
+}
+
+fn is_eof(err: &Error) -> bool {
+    match *err {
+        Error::EndOfFile => true,
+        Error::Io(ref e) => e.kind() == io::ErrorKind::UnexpectedEof,
+        _ => false,
+    }
+}
+
+// Checks the block at `pos`. Returns the offset of the next block, or
+// `None` if the rest of the file cannot be checked.
+fn check_block<R>(stream: &mut R, pos: u64, end: u64, report: &mut Report) -> Result<Option<u64>>
+    where R: io::Read + io::Seek
+{
+    stream.seek(SeekFrom::Start(pos))?;
+    let mut magic = [0; 8];
+    if end - pos >= 8 {
+        stream.read_exact(&mut magic)?;
+    }
+    if end - pos < 8 || !BlockHeader::check_magic(&magic) {
+        return match find_block_magic(stream, pos + 1, end)? {
+            Some(next) => {
+                report.push(pos, Problem::BlockMagic { skipped: next - pos });
+                Ok(Some(next))
+            },
+            None => {
+                report.push(pos, Problem::TrailingGarbage(end - pos));
+                Ok(None)
+            },
+        };
+    }
+
+    let truncated = |report: &mut Report, expected: u64| {
+        report.push(pos, Problem::Truncated { expected, available: end - pos });
+        Ok(None)
+    };
+    if end - pos < 24 {
+        return truncated(report, 24);
+    }
+    let name_len = stream.read_u64::<LittleEndian>()?;
+    if name_len > end - pos - 24 {
+        return truncated(report, name_len.saturating_add(24));
+    }
+    let mut name = vec![0; name_len as usize];
+    stream.read_exact(&mut name)?;
+    let name = String::from_utf8(name).unwrap_or_else(|e| {
+        report.push(pos, Problem::InvalidUtf8);
+        String::from_utf8_lossy(e.as_bytes()).into_owned()
+    });
+    let size = stream.read_u64::<LittleEndian>()?;
+    let body_pos = pos + 24 + name_len;
+    if size > end - body_pos {
+        return truncated(report, (body_pos - pos).saturating_add(size));
+    }
+    let mut body = vec![0; size as usize];
+    stream.read_exact(&mut body)?;
+    report.blocks += 1;
+
+    let mut cursor = io::Cursor::new(&body[..]);
+    let parsed = match name.as_str() {
+        "log" => LogBlock::read_from(&mut cursor).map(|_| None),
+        "f64ts" => F64TSBlock::read_from(&mut cursor).map(Some),
+        "group" => GroupBlock::read_from(&mut cursor).map(|_| None),
+        "attr" => AttrBlock::read_from(&mut cursor).map(|_| None),
+        "event" => EventBlock::read_from(&mut cursor).map(|_| None),
+        _ => {
+            report.push(pos, Problem::UndefinedBlock(name));
+            return Ok(Some(body_pos + size));
+        },
+    };
+    let fts = match parsed {
+        Ok(fts) => {
+            let consumed = cursor.position();
+            if body[consumed as usize..].iter().any(|&b| b != 0) {
+                report.push(pos, Problem::BlockSize { declared: size, consumed });
+            }
+            fts
+        },
+        Err(Error::FromUtf8(_)) => { report.push(pos, Problem::InvalidUtf8); None },
+        Err(Error::Parse(message)) => { report.push(pos, Problem::InvalidField(message)); None },
+        Err(ref e) if is_eof(e) => { report.push(pos, Problem::BlockOverrun { declared: size }); None },
+        Err(e) => { return Err(e); },
+    };
+
+    let payload_pos = body_pos + size;
+    let fts = match fts {
+        Some(fts) => fts,
+        // The entries of a f64ts block which cannot be parsed cannot be
+        // skipped either.
+        None if name == "f64ts" => { return Ok(None); },
+        None => { return Ok(Some(payload_pos)); },
+    };
+    let available = end - payload_pos;
+    let (row_size, expected) = match (fts.row_size(), fts.data_size()) {
+        (Ok(row_size), Ok(expected)) => (row_size, expected),
+        _ => {
+            report.push(payload_pos, Problem::PayloadSize { expected: u64::MAX, available });
+            return Ok(None);
+        },
+    };
+    let length = fts.length().unwrap_or(0);
+    if length == 0 && payload_pos < end && row_size > 0 {
+        stream.seek(SeekFrom::Start(payload_pos))?;
+        let mut magic = Vec::new();
+        stream.by_ref().take(8).read_to_end(&mut magic)?;
+        if magic.len() < 8 || !BlockHeader::check_magic(&magic) {
+            report.push(payload_pos, Problem::Unfinalized { available });
+            return Ok(None);
+        }
+    }
+    if expected > available {
+        report.push(payload_pos, Problem::PayloadSize { expected, available });
+        return Ok(None);
+    }
+    check_entries(stream, payload_pos, &fts, report)?;
+    report.series += 1;
+    report.rows += length;
+    Ok(Some(payload_pos + expected))
+}
+
+fn check_entries<R>(stream: &mut R, pos: u64, fts: &F64TSBlock, report: &mut Report) -> Result<()>
+    where R: io::Read + io::Seek
+{
+    stream.seek(SeekFrom::Start(pos))?;
+    let columns = fts.row_size()? / 8;
+    if columns == 0 {
+        return Ok(());
+    }
+    let mut previous: Option<f64> = None;
+    let mut decreasing: Option<(u64, Problem)> = None;
+    let mut non_finite: Option<(u64, Problem)> = None;
+    for row in 0..fts.length().unwrap_or(0) {
+        let offset = pos + row * columns * 8;
+        for column in 0..columns {
+            let x = stream.read_f64::<LittleEndian>()?;
+            if !x.is_finite() {
+                match non_finite {
+                    Some((_, Problem::NonFinite { ref mut count, .. })) => { *count += 1; },
+                    _ => { non_finite = Some((offset + column * 8, Problem::NonFinite { row, column, count: 1 })); },
+                }
+            }
+            if column > 0 || fts.index_len() == 0 || x.is_nan() {
+                continue;
+            }
+            if let Some(prev) = previous.filter(|&prev| x < prev) {
+                match decreasing {
+                    Some((_, Problem::NonMonotonicIndex { ref mut count, .. })) => { *count += 1; },
+                    _ => {
+                        let problem = Problem::NonMonotonicIndex { row, previous: prev, index: x, count: 1 };
+                        decreasing = Some((offset, problem));
+                    },
+                }
+            }
+            previous = Some(x);
+        }
+    }
+    for (offset, problem) in decreasing.into_iter().chain(non_finite) {
+        report.push(offset, problem);
+    }
+    Ok(())
+}
@@ -0,0 +1,359 @@
+//! Export of series to delimited text (CSV, TSV) and import from it.
+
+use std::io;
+use std::str::FromStr;
+use dataset::TimeSeries;
+use error::{Result, Error};
+use Entry;
+
+/// How entries are written as delimited text.
+#[derive(Debug,Clone,PartialEq)]
+pub struct CsvFormat {
+    delimiter  : char,
+    precision  : Option<usize>,
+    scientific : bool,
+    header     : bool,
+}
+
+impl Default for CsvFormat {
+    fn default() -> Self {
+        Self::csv()
+    }
+}
+
+impl CsvFormat {
+    /// Comma-separated values with a header row and the shortest
+    /// representation of each number that reads back exactly.
+    pub fn csv() -> Self {
+        CsvFormat {
+            delimiter  : ',',
+            precision  : None,
+            scientific : false,
+            header     : true,
+        }
+    }
+
+    /// Tab-separated values.
+    pub fn tsv() -> Self {
+        Self::csv().delimiter('\t')
+    }
+
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Writes numbers with `digits` digits after the decimal point.
+    pub fn precision(mut self, digits: usize) -> Self {
+        self.precision = Some(digits);
+        self
+    }
+
+    /// Writes numbers in scientific notation such as `1.5e3`.
+    pub fn scientific(mut self, scientific: bool) -> Self {
+        self.scientific = scientific;
+        self
+    }
+
+    /// Writes a header row if the series has channel names.
+    pub fn header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    pub fn format_value(&self, x: f64) -> String {
+        match (self.precision, self.scientific) {
+            (Some(p), true) => format!("{:.*e}", p, x),
+            (Some(p), false) => format!("{:.*}", p, x),
+            (None, true) => format!("{:e}", x),
+            (None, false) => format!("{}", x),
+        }
+    }
+
+    fn quote(&self, field: &str) -> String {
+        if field.contains(self.delimiter) || field.contains('"') || field.contains('\n') {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    }
+}
+
+/// Writes entries as delimited text, one row per entry with the index in
+/// the first column. Returns the number of rows written.
+///
+/// The header row consists of `index` and the channel names, and it is
+/// written only if `channel_names` is not empty.
+pub fn write_csv<W, I>(out: &mut W, channel_names: &[String], entries: I, format: &CsvFormat) -> Result<u64>
+    where W: io::Write, I: Iterator<Item = Result<Entry>>
+{
+    let delimiter = format.delimiter.to_string();
+    if format.header && !channel_names.is_empty() {
+        let mut fields = vec![format.quote("index")];
+        fields.extend(channel_names.iter().map(|s| format.quote(s)));
+        writeln!(out, "{}", fields.join(&delimiter))?;
+    }
+    let mut rows = 0;
+    for entry in entries {
+        let (index, values) = entry?;
+        let mut line = format.format_value(index);
+        for &x in values.iter() {
+            line.push_str(&delimiter);
+            line.push_str(&format.format_value(x));
+        }
+        writeln!(out, "{}", line)?;
+        rows += 1;
+    }
+    Ok(rows)
+}
+
+/// A column of delimited text given by its position (starting at 0) or by
+/// its name in the header row.
+#[derive(Debug,Clone,PartialEq)]
+pub enum Column {
+    Number(usize),
+    Name(String),
+}
+
+impl FromStr for Column {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.parse() {
+            Ok(n) => Ok(Column::Number(n)),
+            Err(_) if !s.is_empty() => Ok(Column::Name(s.to_string())),
+            Err(_) => Err(Error::Parse("empty column name".to_string())),
+        }
+    }
+}
+
+/// How delimited text is read into a series.
+#[derive(Debug,Clone,PartialEq)]
+pub struct CsvImport {
+    delimiter : char,
+    header    : bool,
+    index     : Column,
+    values    : Option<Vec<Column>>,
+}
+
+impl Default for CsvImport {
+    fn default() -> Self {
+        Self::csv()
+    }
+}
+
+impl CsvImport {
+    /// Comma-separated values with a header row, the index in the first
+    /// column and values in all other columns.
+    pub fn csv() -> Self {
+        CsvImport {
+            delimiter : ',',
+            header    : true,
+            index     : Column::Number(0),
+            values    : None,
+        }
+    }
+
+    pub fn tsv() -> Self {
+        Self::csv().delimiter('\t')
+    }
+
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    pub fn index_column(mut self, column: Column) -> Self {
+        self.index = column;
+        self
+    }
+
+    pub fn value_columns(mut self, columns: Vec<Column>) -> Self {
+        self.values = Some(columns);
+        self
+    }
+}
+
+// Splits a line into fields, removing the quotes around quoted fields.
+fn split_fields(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            if c == '"' {
+                if chars.peek() == Some(&'"') {
+                    field.push('"');
+                    chars.next();
+                } else {
+                    quoted = false;
+                }
+            } else {
+                field.push(c);
+            }
+        } else if c == '"' && field.trim().is_empty() {
+            field.clear();
+            quoted = true;
+        } else if c == delimiter {
+            fields.push(field.trim().to_string());
+            field.clear();
+        } else {
+            field.push(c);
+        }
+    }
+    fields.push(field.trim().to_string());
+    fields
+}
+
+/// Reads entries from delimited text line by line.
+///
+/// Blank lines are skipped and an empty field is read as NaN.
+#[derive(Debug)]
+pub struct CsvReader<R: io::BufRead> {
+    input     : R,
+    delimiter : char,
+    index     : usize,
+    values    : Vec<usize>,
+    channels  : Vec<String>,
+    line      : usize,
+}
+
+impl<R: io::BufRead> CsvReader<R> {
+    /// Reads the header row, if any, and resolves the columns.
+    pub fn new(input: R, spec: &CsvImport) -> Result<Self> {
+        let mut reader = CsvReader {
+            input,
+            delimiter : spec.delimiter,
+            index     : 0,
+            values    : Vec::new(),
+            channels  : Vec::new(),
+            line      : 0,
+        };
+        let names = if spec.header {
+            match reader.next_line()? {
+                Some(line) => Some(split_fields(&line, reader.delimiter)),
+                None => { return Err(Error::Parse("missing header row".to_string())); },
+            }
+        } else {
+            None
+        };
+        let resolve = |column: &Column| -> Result<usize> {
+            match (column, names.as_ref()) {
+                (&Column::Number(n), _) => Ok(n),
+                (Column::Name(name), Some(names)) => names.iter().position(|s| s == name)
+                    .ok_or_else(|| Error::Parse(format!("no column named {}", name))),
+                (Column::Name(name), None) => {
+                    Err(Error::Parse(format!("column {} is named but there is no header row", name)))
+                },
+            }
+        };
+        reader.index = resolve(&spec.index)?;
+        reader.values = match spec.values {
+            Some(ref columns) => columns.iter().map(resolve).collect::<Result<_>>()?,
+            None => match names {
+                Some(ref names) => (0..names.len()).filter(|&n| n != reader.index).collect(),
+                None => Vec::new(),
+            },
+        };
+        if let Some(names) = names {
+            reader.channels = reader.values.iter()
+                .map(|&n| names.get(n).cloned().unwrap_or_default())
+                .collect();
+        }
+        Ok(reader)
+    }
+
+    /// Names of the value columns from the header row.
+    pub fn channel_names(&self) -> &[String] {
+        &self.channels
+    }
+
+    /// Number of value columns. Without a header row or value columns given
+    /// explicitly, it is known only after the first row has been read.
+    pub fn value_len(&self) -> usize {
+        self.values.len()
+    }
+
+    // Returns the next line which is not blank.
+    fn next_line(&mut self) -> Result<Option<String>> {
+        loop {
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            self.line += 1;
+            let line = line.trim_end_matches(&['\r', '\n'][..]);
+            if !line.trim().is_empty() {
+                return Ok(Some(line.to_string()));
+            }
+        }
+    }
+
+    fn parse_field(&self, fields: &[String], column: usize) -> Result<f64> {
+        let field = fields.get(column).ok_or_else(|| {
+            Error::Parse(format!("line {}: missing column {}", self.line, column))
+        })?;
+        if field.is_empty() {
+            return Ok(f64::NAN);
+        }
+        field.parse().map_err(|_| {
+            Error::Parse(format!("line {}: invalid number: {}", self.line, field))
+        })
+    }
+
+    fn read_entry(&mut self) -> Result<Option<Entry>> {
+        let line = match self.next_line()? {
+            Some(line) => line,
+            None => { return Ok(None); },
+        };
+        let fields = split_fields(&line, self.delimiter);
+        if self.values.is_empty() {
+            let index = self.index;
+            self.values = (0..fields.len()).filter(|&n| n != index).collect();
+        }
+        let index = self.parse_field(&fields, self.index)?;
+        let values = self.values.iter()
+            .map(|&n| self.parse_field(&fields, n))
+            .collect::<Result<Vec<f64>>>()?;
+        Ok(Some((index, values)))
+    }
+}
+
+impl<R: io::BufRead> Iterator for CsvReader<R> {
+    type Item = Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_entry() {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Reads delimited text into a series named `name`.
+pub fn read_csv<R: io::BufRead>(input: R, spec: &CsvImport, name: &str) -> Result<TimeSeries> {
+    let mut reader = CsvReader::new(input, spec)?;
+    let first = match reader.next() {
+        Some(entry) => Some(entry?),
+        None => None,
+    };
+    let mut series = TimeSeries::new(name, reader.value_len() as u64);
+    if !reader.channel_names().is_empty() {
+        series = series.with_channel_names(reader.channel_names().to_vec());
+    }
+    if let Some((index, values)) = first {
+        series.push(index, values);
+    }
+    for entry in reader {
+        let (index, values) = entry?;
+        series.push(index, values);
+    }
+    Ok(series)
+}
@@ -0,0 +1,361 @@
+use std::io;
+use std::collections::BTreeMap;
+use reader::{Reader, Block};
+use writer::Writer;
+use header::{F64TSBlock, F64TSBlockBuilder};
+use error::{Result, Error};
+use group;
+use {Metadata, Entry};
+
+/// A named time-series held in memory.
+#[derive(Debug,PartialEq,Clone)]
+pub struct TimeSeries {
+    id        : u64,
+    name      : String,
+    group     : String,
+    value_len : u64,
+    channels  : Vec<String>,
+    entries   : Vec<Entry>,
+}
+
+impl TimeSeries {
+    pub fn new<S: Into<String>>(name: S, value_len: u64) -> Self {
+        TimeSeries {
+            id: 0,
+            name: name.into(),
+            group: group::ROOT.to_string(),
+            value_len,
+            channels: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Places the series in the group at `path`.
+    pub fn with_group(mut self, path: &str) -> Self {
+        self.group = group::normalize(path);
+        self
+    }
+
+    pub fn group(&self) -> &str {
+        &self.group
+    }
+
+    /// Path of the series: its group joined with its name.
+    pub fn path(&self) -> String {
+        group::join(&self.group, &self.name)
+    }
+
+    pub fn value_len(&self) -> u64 {
+        self.value_len
+    }
+
+    /// Names the values of the series.
+    pub fn with_channel_names<S: Into<String>>(mut self, names: Vec<S>) -> Self {
+        assert_eq!(names.len() as u64, self.value_len);
+        self.channels = names.into_iter().map(|s| s.into()).collect();
+        self
+    }
+
+    pub fn channel_names(&self) -> &[String] {
+        &self.channels
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn entries_mut(&mut self) -> &mut Vec<Entry> {
+        &mut self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn push(&mut self, index: f64, values: Vec<f64>) {
+        assert_eq!(values.len() as u64, self.value_len);
+        self.entries.push((index, values));
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        if name.starts_with('/') {
+            self.path() == group::normalize(name)
+        } else {
+            self.name == name
+        }
+    }
+
+    /// Returns the f64ts block describing this series.
+    pub fn block(&self) -> F64TSBlock {
+        let builder = F64TSBlockBuilder::new()
+            .index_len(1)
+            .value_len(self.value_len)
+            .length(self.entries.len() as u64)
+            .id(self.id)
+            .channel_names(self.channels.clone());
+        // A series with an empty name is unnamed, as `load` makes them.
+        if self.name.is_empty() {
+            builder.build()
+        } else {
+            builder.name(self.name.clone()).build()
+        }
+    }
+}
+
+/// A group or a series in a dataset.
+#[derive(Debug,PartialEq)]
+pub enum Node<'a> {
+    Group(&'a str, &'a Metadata),
+    Series(&'a TimeSeries),
+}
+
+/// The metadata and the series of a file.
+///
+/// The metadata of the root group is returned by `metadata`; other groups
+/// are created by `group_mut` or by adding a series which belongs to them.
+#[derive(Debug,PartialEq,Default)]
+pub struct Dataset {
+    metadata : Metadata,
+    groups   : BTreeMap<String, Metadata>,
+    series   : Vec<TimeSeries>,
+}
+
+impl Dataset {
+    pub fn new() -> Self {
+        Dataset {
+            metadata: Metadata::new(),
+            groups: BTreeMap::new(),
+            series: Vec::new(),
+        }
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
+    }
+
+    /// Returns the metadata of the group at `path` if the group exists.
+    pub fn group(&self, path: &str) -> Option<&Metadata> {
+        let path = group::normalize(path);
+        if path == group::ROOT {
+            return Some(&self.metadata);
+        }
+        self.groups.get(&path)
+    }
+
+    /// Returns the metadata of the group at `path`, creating the group and
+    /// its ancestors if they do not exist.
+    pub fn group_mut(&mut self, path: &str) -> &mut Metadata {
+        let path = group::normalize(path);
+        if path == group::ROOT {
+            return &mut self.metadata;
+        }
+        let mut parent = group::parent(&path);
+        while let Some(p) = parent {
+            if p == group::ROOT {
+                break;
+            }
+            parent = group::parent(&p);
+            self.groups.entry(p).or_default();
+        }
+        self.groups.entry(path).or_default()
+    }
+
+    /// Returns the paths of all groups including the root group.
+    pub fn group_paths(&self) -> Vec<&str> {
+        let mut paths = vec![group::ROOT];
+        paths.extend(self.groups.keys().map(|p| p.as_str()));
+        paths
+    }
+
+    /// Returns the groups and the series directly under the group at `path`.
+    pub fn children(&self, path: &str) -> Vec<Node<'_>> {
+        let path = group::normalize(path);
+        let mut nodes = Vec::new();
+        for (p, metadata) in self.groups.iter() {
+            if group::parent(p).as_ref() == Some(&path) {
+                nodes.push(Node::Group(p.as_str(), metadata));
+            }
+        }
+        for series in self.series.iter().filter(|s| s.group == path) {
+            nodes.push(Node::Series(series));
+        }
+        nodes
+    }
+
+    /// Returns the group at `path` and everything under it, depth first.
+    pub fn subtree(&self, path: &str) -> Vec<Node<'_>> {
+        let path = group::normalize(path);
+        let mut nodes = Vec::new();
+        if path == group::ROOT {
+            nodes.push(Node::Group(group::ROOT, &self.metadata));
+        } else {
+            match self.groups.get_key_value(&path) {
+                Some((p, metadata)) => { nodes.push(Node::Group(p.as_str(), metadata)); },
+                None => { return nodes; },
+            }
+        }
+        self.push_subtree(&path, &mut nodes);
+        nodes
+    }
+
+    fn push_subtree<'a>(&'a self, path: &str, nodes: &mut Vec<Node<'a>>) {
+        for node in self.children(path) {
+            match node {
+                Node::Group(p, _) => {
+                    nodes.push(node);
+                    self.push_subtree(p, nodes);
+                },
+                Node::Series(_) => { nodes.push(node); },
+            }
+        }
+    }
+
+    /// Adds a series, assigning it the next identifier.
+    pub fn add_series(&mut self, mut series: TimeSeries) -> &mut TimeSeries {
+        series.id = self.series.iter().map(|s| s.id + 1).max().unwrap_or(0);
+        self.group_mut(&series.group.clone());
+        self.series.push(series);
+        self.series.last_mut().unwrap()
+    }
+
+    pub fn series_list(&self) -> &[TimeSeries] {
+        &self.series
+    }
+
+    pub fn series_names(&self) -> Vec<&str> {
+        self.series.iter().map(|s| s.name()).collect()
+    }
+
+    /// Looks a series up by its name, or by its path if `name` starts
+    /// with `/`.
+    pub fn series(&self, name: &str) -> Option<&TimeSeries> {
+        self.series.iter().find(|s| s.matches(name))
+    }
+
+    pub fn series_mut(&mut self, name: &str) -> Option<&mut TimeSeries> {
+        self.series.iter_mut().find(|s| s.matches(name))
+    }
+
+    pub fn series_by_id(&self, id: u64) -> Option<&TimeSeries> {
+        self.series.iter().find(|s| s.id == id)
+    }
+}
+
+/// Reads all logs and series from a stream.
+///
+/// Named f64ts blocks with the same name in the same group are joined into
+/// one series.
+/// Unnamed blocks become separate series with an empty name, identified by
+/// their position in the dataset.
+pub fn load<R: io::Read>(stream: R) -> Result<Dataset> {
+    load_from_reader(Reader::new(stream))
+}
+
+/// Reads a dataset like `load` with `reader`, which has not read the header
+/// yet, so that options such as `Reader::accept_segments` apply.
+pub fn load_from_reader<R: io::Read>(mut reader: Reader<R>) -> Result<Dataset> {
+    reader.initialize()?;
+
+    let mut dataset = Dataset::new();
+
+    loop {
+        let block = match reader.next_block() {
+            Ok(block) => block,
+            Err(Error::EndOfFile) => break,
+            Err(e) => { return Err(e); },
+        };
+        match block {
+            Block::Log(log) => {
+                let path = reader.current_group().to_string();
+                dataset.group_mut(&path).get_logs_mut().push(log);
+            },
+            Block::Attr(attr) => {
+                let path = reader.current_group().to_string();
+                dataset.group_mut(&path).get_attrs_mut().push(attr);
+            },
+            Block::Event(event) => {
+                let path = reader.current_group().to_string();
+                dataset.group_mut(&path).get_events_mut().push(event);
+            },
+            Block::Group(g) => { dataset.group_mut(g.path()); },
+            Block::F64TS(fts) => {
+                let path = reader.current_group();
+                let pos = match fts.name() {
+                    Some(name) => dataset.series.iter()
+                        .position(|s| s.name == name && s.group == path),
+                    None => None,
+                };
+                let pos = match pos {
+                    Some(pos) => pos,
+                    None => {
+                        let (id, name) = match fts.name() {
+                            Some(name) => (fts.id(), name.to_string()),
+                            None => (dataset.series.len() as u64, String::new()),
+                        };
+                        let mut series = TimeSeries::new(name, fts.value_len())
+                            .with_group(path);
+                        series.id = id;
+                        series.channels = fts.channel_names().to_vec();
+                        dataset.series.push(series);
+                        dataset.series.len() - 1
+                    },
+                };
+                if dataset.series[pos].value_len != fts.value_len() {
+                    return Err(Error::Shape);
+                }
+                for ent in reader.f64ts_entries(&fts) {
+                    dataset.series[pos].entries.push(ent?);
+                }
+            },
+        }
+    }
+
+    Ok(dataset)
+}
+
+/// Writes the metadata and the series of a dataset to a stream.
+pub fn save<W: io::Write>(stream: W, dataset: &Dataset) -> Result<W> {
+    let mut writer = Writer::new(stream);
+    writer.write_header()?;
+
+    for path in dataset.group_paths() {
+        if path != group::ROOT {
+            writer.write_group(path)?;
+        }
+        let metadata = dataset.group(path).unwrap();
+        for log in metadata.get_logs().iter() {
+            writer.write_log(log)?;
+        }
+        for attr in metadata.get_attrs().iter() {
+            writer.write_attr(attr)?;
+        }
+        for event in metadata.get_events().iter() {
+            writer.write_event(event)?;
+        }
+        for series in dataset.series.iter().filter(|s| s.group == path) {
+            let mut w = writer.write_f64ts(series.block())?;
+            for &(index, ref values) in series.entries.iter() {
+                w.write_entry(index, values)?;
+            }
+            writer = w.finish();
+        }
+    }
+
+    Ok(writer.into_stream())
+}
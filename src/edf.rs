@@ -0,0 +1,364 @@
+//! Import of EDF and EDF+ recordings (European Data Format).
+//!
+//! Every ordinary signal becomes a series with one value, named by its
+//! label and indexed by seconds from the start of the recording. The
+//! header fields and the label, physical dimension, calibration and sample
+//! rate of each signal are kept as attributes (`edf.patient`,
+//! `edf.signal.NAME.dimension`, ... where NAME is the name of the series),
+//! and the annotations of EDF+ files become events.
+
+use std::io;
+use std::io::Read;
+use byteorder::{LittleEndian, ReadBytesExt};
+use header::EventBlock;
+use dataset::{Dataset, TimeSeries};
+use error::{Result, Error};
+use Metadata;
+
+const ANNOTATIONS: &str = "EDF Annotations";
+
+fn field<R: io::Read>(input: &mut R, len: usize) -> Result<String> {
+    let mut buf = vec![0; len];
+    input.read_exact(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).trim().to_string())
+}
+
+fn number<T: ::std::str::FromStr>(s: &str, what: &str) -> Result<T> {
+    s.parse().map_err(|_| Error::Parse(format!("EDF header: invalid {}: {}", what, s)))
+}
+
+/// A signal described in the header of an EDF file.
+#[derive(Debug,Clone,PartialEq)]
+pub struct EdfSignal {
+    label              : String,
+    transducer         : String,
+    dimension          : String,
+    physical_min       : f64,
+    physical_max       : f64,
+    digital_min        : f64,
+    digital_max        : f64,
+    prefiltering       : String,
+    samples_per_record : usize,
+}
+
+impl EdfSignal {
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn transducer(&self) -> &str {
+        &self.transducer
+    }
+
+    /// The physical dimension (unit) of the signal, such as `uV`.
+    pub fn dimension(&self) -> &str {
+        &self.dimension
+    }
+
+    pub fn physical_range(&self) -> (f64, f64) {
+        (self.physical_min, self.physical_max)
+    }
+
+    pub fn digital_range(&self) -> (f64, f64) {
+        (self.digital_min, self.digital_max)
+    }
+
+    pub fn prefiltering(&self) -> &str {
+        &self.prefiltering
+    }
+
+    pub fn samples_per_record(&self) -> usize {
+        self.samples_per_record
+    }
+
+    pub fn is_annotations(&self) -> bool {
+        self.label == ANNOTATIONS
+    }
+
+    /// Converts a digital sample to its physical value.
+    pub fn physical(&self, digital: i16) -> f64 {
+        let gain = (self.physical_max - self.physical_min) / (self.digital_max - self.digital_min);
+        (digital as f64 - self.digital_min) * gain + self.physical_min
+    }
+}
+
+/// The header of an EDF file.
+#[derive(Debug,Clone,PartialEq)]
+pub struct EdfHeader {
+    version         : String,
+    patient         : String,
+    recording       : String,
+    start_date      : String,
+    start_time      : String,
+    reserved        : String,
+    records         : i64,
+    record_duration : f64,
+    signals         : Vec<EdfSignal>,
+}
+
+impl EdfHeader {
+    pub fn read_from<R: io::Read>(input: &mut R) -> Result<Self> {
+        let version = field(input, 8)?;
+        if version != "0" {
+            return Err(Error::Magic);
+        }
+        let patient = field(input, 80)?;
+        let recording = field(input, 80)?;
+        let start_date = field(input, 8)?;
+        let start_time = field(input, 8)?;
+        let _header_bytes = field(input, 8)?;
+        let reserved = field(input, 44)?;
+        let records = number(&field(input, 8)?, "number of data records")?;
+        let record_duration = number(&field(input, 8)?, "duration of a data record")?;
+        let ns: usize = number(&field(input, 4)?, "number of signals")?;
+
+        let mut columns: Vec<Vec<String>> = Vec::new();
+        for &len in [16, 80, 8, 8, 8, 8, 8, 80, 8, 32].iter() {
+            columns.push((0..ns).map(|_| field(input, len)).collect::<Result<_>>()?);
+        }
+        // The fields are stored signal by signal for each kind of field.
+        let mut columns: Vec<_> = columns.into_iter().map(|c| c.into_iter()).collect();
+        let mut next = |c: usize| columns[c].next().unwrap();
+        let mut signals = Vec::new();
+        for _ in 0..ns {
+            signals.push(EdfSignal {
+                label              : next(0),
+                transducer         : next(1),
+                dimension          : next(2),
+                physical_min       : number(&next(3), "physical minimum")?,
+                physical_max       : number(&next(4), "physical maximum")?,
+                digital_min        : number(&next(5), "digital minimum")?,
+                digital_max        : number(&next(6), "digital maximum")?,
+                prefiltering       : next(7),
+                samples_per_record : number(&next(8), "number of samples")?,
+            });
+        }
+        if let Some(s) = signals.iter().find(|s| s.digital_min == s.digital_max) {
+            return Err(Error::Parse(format!("EDF header: empty digital range of {}", s.label)));
+        }
+
+        Ok(EdfHeader {
+            version,
+            patient,
+            recording,
+            start_date,
+            start_time,
+            reserved,
+            records,
+            record_duration,
+            signals,
+        })
+    }
+
+    pub fn patient(&self) -> &str {
+        &self.patient
+    }
+
+    pub fn recording(&self) -> &str {
+        &self.recording
+    }
+
+    pub fn is_edf_plus(&self) -> bool {
+        self.reserved.starts_with("EDF+")
+    }
+
+    /// Tests whether the data records of an EDF+ file are not contiguous.
+    pub fn is_discontinuous(&self) -> bool {
+        self.reserved.starts_with("EDF+D")
+    }
+
+    /// Number of data records, or `None` if it is unknown.
+    pub fn records(&self) -> Option<u64> {
+        if self.records < 0 { None } else { Some(self.records as u64) }
+    }
+
+    /// Duration of a data record in seconds.
+    pub fn record_duration(&self) -> f64 {
+        self.record_duration
+    }
+
+    pub fn signals(&self) -> &[EdfSignal] {
+        &self.signals
+    }
+
+    /// Sample rate of the `n`-th signal in Hz.
+    pub fn sample_rate(&self, n: usize) -> f64 {
+        self.signals[n].samples_per_record as f64 / self.record_duration
+    }
+
+    /// The start of the recording as `YYYY-MM-DDThh:mm:ss` in local time.
+    ///
+    /// The two-digit year of the header is taken to be in 1985-2084 unless
+    /// the `Startdate` of an EDF+ recording field gives it in full.
+    pub fn start(&self) -> Option<String> {
+        let date: Vec<u32> = self.start_date.split('.').filter_map(|s| s.parse().ok()).collect();
+        let time: Vec<u32> = self.start_time.split('.').filter_map(|s| s.parse().ok()).collect();
+        if date.len() != 3 || time.len() != 3 {
+            return None;
+        }
+        let mut year = if date[2] >= 85 { 1900 + date[2] } else { 2000 + date[2] };
+        if self.is_edf_plus() {
+            let full_year = self.recording.split(' ').nth(1)
+                .and_then(|d| d.rsplit('-').next())
+                .and_then(|y| y.parse::<u32>().ok());
+            if let Some(y) = full_year {
+                year = y;
+            }
+        }
+        Some(format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", year, date[1], date[0], time[0], time[1], time[2]))
+    }
+
+    /// Names of the series of the signals, or None for annotation signals.
+    /// Labels are made unique and `/` in them is replaced by `_`.
+    fn series_names(&self) -> Vec<Option<String>> {
+        let mut names: Vec<Option<String>> = Vec::new();
+        for signal in self.signals.iter() {
+            if signal.is_annotations() {
+                names.push(None);
+                continue;
+            }
+            let base = signal.label.replace('/', "_");
+            let mut name = base.clone();
+            let mut k = 1;
+            while names.iter().flatten().any(|s| *s == name) {
+                k += 1;
+                name = format!("{}_{}", base, k);
+            }
+            names.push(Some(name));
+        }
+        names
+    }
+
+    /// Returns the attributes recording the header.
+    pub fn to_metadata(&self) -> Metadata {
+        let mut metadata = Metadata::new();
+        metadata.set_attr("edf.version", if self.is_edf_plus() { self.reserved.as_str() } else { "EDF" });
+        metadata.set_attr("edf.patient", self.patient.as_str());
+        metadata.set_attr("edf.recording", self.recording.as_str());
+        if let Some(start) = self.start() {
+            metadata.set_attr("edf.start", start);
+        }
+        metadata.set_attr("edf.record_duration", self.record_duration.to_string());
+        if self.is_edf_plus() {
+            // Subfields are separated by spaces and use `_` for spaces.
+            let subfields = |s: &str| -> Vec<String> { s.split(' ').map(|f| f.replace('_', " ")).collect() };
+            let patient = subfields(&self.patient);
+            for (key, value) in ["code", "sex", "birthdate", "name"].iter().zip(patient.iter()) {
+                metadata.set_attr(format!("edf.patient.{}", key), value.as_str());
+            }
+            let recording = subfields(&self.recording);
+            for (key, value) in ["admin_code", "technician", "equipment"].iter().zip(recording.iter().skip(2)) {
+                metadata.set_attr(format!("edf.recording.{}", key), value.as_str());
+            }
+        }
+        for (n, name) in self.series_names().into_iter().enumerate() {
+            let name = match name {
+                Some(name) => name,
+                None => continue,
+            };
+            let signal = &self.signals[n];
+            let prefix = format!("edf.signal.{}", name);
+            metadata.set_attr(format!("{}.label", prefix), signal.label.as_str());
+            metadata.set_attr(format!("{}.transducer", prefix), signal.transducer.as_str());
+            metadata.set_attr(format!("{}.dimension", prefix), signal.dimension.as_str());
+            metadata.set_attr(format!("{}.physical_min", prefix), signal.physical_min.to_string());
+            metadata.set_attr(format!("{}.physical_max", prefix), signal.physical_max.to_string());
+            metadata.set_attr(format!("{}.digital_min", prefix), signal.digital_min.to_string());
+            metadata.set_attr(format!("{}.digital_max", prefix), signal.digital_max.to_string());
+            metadata.set_attr(format!("{}.prefiltering", prefix), signal.prefiltering.as_str());
+            metadata.set_attr(format!("{}.sample_rate", prefix), self.sample_rate(n).to_string());
+        }
+        metadata
+    }
+}
+
+// Parses the time-stamped annotation lists (TALs) of an annotation signal.
+// Returns the onset of the data record given by its first, empty TAL.
+fn parse_annotations(bytes: &[u8], events: &mut Vec<EventBlock>) -> Result<Option<f64>> {
+    let mut record_onset = None;
+    for (n, tal) in bytes.split(|&b| b == 0).filter(|t| !t.is_empty()).enumerate() {
+        let tal = String::from_utf8_lossy(tal);
+        let mut fields = tal.split('\x14');
+        let time = fields.next().unwrap_or("");
+        let mut time = time.splitn(2, '\x15');
+        let invalid = || Error::Parse(format!("EDF+ annotation: invalid time: {}", tal.trim()));
+        let onset: f64 = time.next().unwrap_or("").parse().map_err(|_| invalid())?;
+        let duration = match time.next() {
+            Some(d) => Some(d.parse::<f64>().map_err(|_| invalid())?),
+            None => None,
+        };
+        let texts: Vec<&str> = fields.filter(|t| !t.is_empty()).collect();
+        if n == 0 && texts.is_empty() {
+            record_onset = Some(onset);
+        }
+        for text in texts {
+            events.push(EventBlock::new(onset, duration, text));
+        }
+    }
+    Ok(record_onset)
+}
+
+/// Reads an EDF or EDF+ file into a dataset with its series, attributes and
+/// events in the group at `group`.
+///
+/// Labels are made unique and `/` in them is replaced by `_` to form the
+/// names of the series.
+pub fn read_edf<R: io::Read>(mut input: R, group: &str) -> Result<Dataset> {
+    let header = EdfHeader::read_from(&mut input)?;
+    let mut dataset = Dataset::new();
+    *dataset.group_mut(group) = header.to_metadata();
+
+    // The series of each signal, or None for annotation signals.
+    let mut series: Vec<Option<TimeSeries>> = header.series_names().into_iter()
+        .map(|name| name.map(|name| TimeSeries::new(name, 1).with_group(group)))
+        .collect();
+
+    let mut events = Vec::new();
+    let mut record = 0u64;
+    while header.records().map(|n| record < n).unwrap_or(true) {
+        let mut record_onset = None;
+        let mut samples = Vec::new();
+        for (n, signal) in header.signals().iter().enumerate() {
+            let mut bytes = vec![0; 2 * signal.samples_per_record];
+            if n == 0 {
+                // The end of a file with an unknown number of records.
+                let read = input.by_ref().take(bytes.len() as u64).read(&mut bytes)?;
+                if read == 0 && !bytes.is_empty() && header.records().is_none() {
+                    break;
+                }
+                input.read_exact(&mut bytes[read..])?;
+            } else {
+                input.read_exact(&mut bytes)?;
+            }
+            if signal.is_annotations() {
+                let onset = parse_annotations(&bytes, &mut events)?;
+                record_onset = record_onset.or(onset);
+            }
+            samples.push(bytes);
+        }
+        if samples.len() < header.signals().len() {
+            break;
+        }
+        let onset = record_onset.unwrap_or(record as f64 * header.record_duration());
+        for (n, bytes) in samples.iter().enumerate() {
+            let series = match series[n] {
+                Some(ref mut series) => series,
+                None => continue,
+            };
+            let signal = &header.signals()[n];
+            let rate = header.sample_rate(n);
+            let mut bytes = &bytes[..];
+            for k in 0..signal.samples_per_record {
+                let digital = bytes.read_i16::<LittleEndian>()?;
+                series.push(onset + k as f64 / rate, vec![signal.physical(digital)]);
+            }
+        }
+        record += 1;
+    }
+
+    dataset.group_mut(group).get_events_mut().extend(events);
+    for series in series.into_iter().flatten() {
+        dataset.add_series(series);
+    }
+    Ok(dataset)
+}
@@ -9,6 +9,8 @@ pub enum Error {
     EndOfFile,
     Magic,
     UndefinedBlock,
+    Shape,
+    Parse(String),
     Io(io::Error),
     FromUtf8(string::FromUtf8Error),
 }
@@ -27,6 +29,8 @@ impl fmt::Display for Error {
             Error::EndOfFile => write!(f, "End of File"),
             Error::Magic => write!(f, "Magic number error"),
             Error::UndefinedBlock => write!(f, "Undefined Block"),
+            Error::Shape => write!(f, "Inconsistent shape of series"),
+            Error::Parse(ref msg) => write!(f, "Parse error: {}", msg),
             Error::Io(ref err) => write!(f, "IO error: {}", err),
             Error::FromUtf8(ref err) => write!(f, "String error: {}", err),
         }
@@ -34,17 +38,7 @@ impl fmt::Display for Error {
 }
 
 impl error::Error for Error {
-    fn description(&self) -> &str {
-        match *self {
-            Error::EndOfFile => "End of File",
-            Error::Magic => "Magic number",
-            Error::UndefinedBlock => "UndefinedBlock",
-            Error::Io(ref err) => err.description(),
-            Error::FromUtf8(ref err) => err.description(),
-        }
-    }
-
-    fn cause(&self) -> Option<&error::Error> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match *self {
             Error::Io(ref err) => Some(err),
             Error::FromUtf8(ref err) => Some(err),
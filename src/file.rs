@@ -0,0 +1,250 @@
+use std::fs;
+use std::io;
+use std::io::Seek;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use header::{Header, BlockHeader};
+use reader::{Reader, Block};
+use lazy::LazyReader;
+use follow::Follower;
+use writer::Writer;
+use error::{Result, Error};
+
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Opens and creates broto files on the file system.
+#[derive(Debug)]
+pub struct File;
+
+impl File {
+    /// Opens a file for reading and reads its header.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Reader<io::BufReader<fs::File>>> {
+        let file = fs::File::open(path)?;
+        let mut reader = Reader::new(io::BufReader::new(file));
+        reader.initialize()?;
+        Ok(reader)
+    }
+
+    /// Opens a file and reads only its metadata and the shapes of its series.
+    pub fn open_lazy<P: AsRef<Path>>(path: P) -> Result<LazyReader<io::BufReader<fs::File>>> {
+        let file = fs::File::open(path)?;
+        LazyReader::new(io::BufReader::new(file))
+    }
+
+    /// Opens a file for reading while it is being written.
+    pub fn follow<P: AsRef<Path>>(path: P) -> Result<Follower<io::BufReader<fs::File>>> {
+        let file = fs::File::open(path)?;
+        Ok(Follower::new(io::BufReader::new(file)))
+    }
+
+    /// Truncates a file left behind by a crashed writer after the last block
+    /// which reads completely, and returns the number of bytes removed.
+    ///
+    /// The entries written after the last checkpoint of a series (see
+    /// `F64TSWriter::checkpoint`) are removed with the rest. Files joined
+    /// with `cat` are read as one. The file is left as it is, and the error
+    /// returned, if a block cannot be read for another reason, such as an
+    /// undefined block, or if a block or header magic number follows the
+    /// unreadable bytes, which are then not the tail of a crashed write.
+    pub fn recover<P: AsRef<Path>>(path: P) -> Result<u64> {
+        let file = fs::OpenOptions::new().read(true).write(true).open(path)?;
+        let end = file.metadata()?.len();
+        let mut reader = Reader::new(io::BufReader::new(&file)).accept_segments(true);
+        reader.initialize()?;
+        let mut good = reader.get_mut().stream_position()?;
+        loop {
+            let result = match reader.next_block() {
+                Ok(Block::F64TS(fts)) => reader.skip_f64ts(&fts),
+                Ok(_) => Ok(()),
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => {},
+                // A block cut short by the crash.
+                Err(Error::EndOfFile) | Err(Error::Magic) => break,
+                Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+            let pos = reader.get_mut().stream_position()?;
+            if pos > end {
+                break;
+            }
+            good = pos;
+        }
+        drop(reader);
+        if good < end && contains_magic(&file, good + 1)? {
+            return Err(Error::Magic);
+        }
+        file.set_len(good)?;
+        file.sync_all()?;
+        Ok(end - good)
+    }
+
+    /// Creates a file for writing and writes its header.
+    ///
+    /// The data goes to a temporary file in the same directory, which is
+    /// renamed to `path` by `Writer::close`. If the writer is dropped without
+    /// being closed, the temporary file is removed and `path` is untouched.
+    /// Since nothing of it survives a crash, checkpoints need a `fs::File`.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Writer<PendingFile>> {
+        let mut writer = Writer::new(PendingFile::create(path.as_ref())?);
+        writer.write_header()?;
+        Ok(writer)
+    }
+}
+
+// Returns whether a block or header magic number appears in `file` from
+// `from` on.
+fn contains_magic(file: &fs::File, from: u64) -> io::Result<bool> {
+    let mut reader = io::BufReader::new(file);
+    reader.seek(io::SeekFrom::Start(from))?;
+    let magics = [BlockHeader::clone_magic(), Header::clone_magic()];
+    let mut buf = Vec::new();
+    loop {
+        // Keeps the last 7 bytes, where a magic number may start.
+        let keep = buf.len().saturating_sub(7);
+        buf.drain(..keep);
+        let start = buf.len();
+        buf.resize(start + (1 << 16), 0);
+        let n = io::Read::read(&mut reader, &mut buf[start..])?;
+        buf.truncate(start + n);
+        if buf.windows(8).any(|w| magics.iter().any(|m| w == m)) {
+            return Ok(true);
+        }
+        if n == 0 {
+            return Ok(false);
+        }
+    }
+}
+
+/// A buffered temporary file which replaces its destination on commit.
+#[derive(Debug)]
+pub struct PendingFile {
+    stream: Option<io::BufWriter<fs::File>>,
+    temp_path: PathBuf,
+    path: PathBuf,
+}
+
+impl PendingFile {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file_name = path.file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+        let mut temp_name = std::ffi::OsString::from(".");
+        temp_name.push(file_name);
+        temp_name.push(format!(".{}.{}.tmp", process::id(),
+                               TEMP_COUNTER.fetch_add(1, Ordering::SeqCst)));
+        let temp_path = path.with_file_name(temp_name);
+        let file = fs::OpenOptions::new().write(true).create_new(true).open(&temp_path)?;
+        Ok(PendingFile {
+            stream: Some(io::BufWriter::new(file)),
+            temp_path,
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_ref(&self) -> &fs::File {
+        self.stream.as_ref().unwrap().get_ref()
+    }
+
+    /// Flushes the buffer, syncs the data to the disk and renames the
+    /// temporary file to the destination path.
+    pub fn commit(mut self) -> Result<()> {
+        let stream = self.stream.take().unwrap();
+        let file = stream.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&self.temp_path, &self.path)?;
+        sync_parent(&self.path);
+        Ok(())
+    }
+
+    fn stream_mut(&mut self) -> &mut io::BufWriter<fs::File> {
+        self.stream.as_mut().unwrap()
+    }
+}
+
+#[cfg(unix)]
+fn sync_parent(path: &Path) {
+    let parent = match path.parent() {
+        Some(p) if p.as_os_str().is_empty() => Path::new("."),
+        Some(p) => p,
+        None => return,
+    };
+    // The rename has been done already; a failure here only weakens the
+    // durability of the directory entry.
+    if let Ok(dir) = fs::File::open(parent) {
+        let _ = dir.sync_all();
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent(_path: &Path) {}
+
+impl io::Write for PendingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream_mut().flush()
+    }
+}
+
+impl io::Seek for PendingFile {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.stream_mut().seek(pos)
+    }
+}
+
+impl Drop for PendingFile {
+    fn drop(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            drop(stream);
+            let _ = fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+/// A stream whose data can be synced to the disk, for checkpoints.
+pub trait Durable {
+    /// Flushes any buffer and syncs the data to the disk.
+    fn sync_data(&mut self) -> io::Result<()>;
+}
+
+impl Durable for fs::File {
+    fn sync_data(&mut self) -> io::Result<()> {
+        fs::File::sync_data(self)
+    }
+}
+
+impl<W: Durable + io::Write> Durable for io::BufWriter<W> {
+    fn sync_data(&mut self) -> io::Result<()> {
+        io::Write::flush(self)?;
+        self.get_mut().sync_data()
+    }
+}
+
+/// In-memory streams have nothing to sync.
+impl<T> Durable for io::Cursor<T> {
+    fn sync_data(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: Durable> Durable for &mut W {
+    fn sync_data(&mut self) -> io::Result<()> {
+        (**self).sync_data()
+    }
+}
+
+impl Writer<PendingFile> {
+    /// Flushes the data and atomically moves the file into place.
+    pub fn close(self) -> Result<()> {
+        self.into_stream().commit()
+    }
+}
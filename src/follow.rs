@@ -0,0 +1,259 @@
+//! Reading a file while it is being written, like `tail -f`.
+//!
+//! A `Follower` reads the blocks and the entries which have been written
+//! completely and, at the end of the data, waits for more instead of
+//! failing with `Error::EndOfFile`. The length of a f64ts block is only
+//! provisional while the file is open (it is zero until the writer calls
+//! `finalize`), so the entries of a block are read for as long as they
+//! come, and the block ends when the next block starts.
+//!
+//! Past the provisional length, a row which starts with the block magic
+//! number and the name of a block this crate knows is taken for the next
+//! block. An entry would be misread only if its index were the number
+//! whose bytes are the magic number (about 6.0e-154) and its first value
+//! the length of such a name.
+//!
+//! A file created with `File::create` only appears at its path when it is
+//! closed; a file to be followed is written with `Writer::new` on a plain
+//! `fs::File`.
+
+use std::io;
+use std::io::{Read, SeekFrom};
+use std::thread;
+use std::time::Duration;
+use byteorder::{LittleEndian, ByteOrder};
+use header::{self, Header, BlockHeader, F64TSBlock};
+use reader::{self, Block};
+use error::{Result, Error};
+use group;
+use Entry;
+
+const POLL_INTERVAL_MS: u64 = 100;
+
+const BLOCK_NAMES: [&[u8]; 5] = [b"log", b"f64ts", b"group", b"attr", b"event"];
+
+/// A block or an entry of the f64ts block read last.
+#[derive(Debug)]
+pub enum Item {
+    Block(Block),
+    Entry(Entry),
+}
+
+// The entries of a f64ts block being read.
+#[derive(Debug)]
+struct Series {
+    block      : F64TSBlock,
+    length_pos : u64,
+    data_pos   : u64,
+    row        : u64,
+}
+
+impl Series {
+    fn row_pos(&self) -> Result<u64> {
+        self.block.row_size()?.checked_mul(self.row).and_then(|n| n.checked_add(self.data_pos))
+            .ok_or_else(|| Error::Parse(format!("row {} of a f64ts block is out of range", self.row)))
+    }
+}
+
+/// Reads a file which may still be growing.
+#[derive(Debug)]
+pub struct Follower<R> {
+    stream   : R,
+    header   : Option<Header>,
+    pos      : u64,
+    series   : Option<Series>,
+    group    : String,
+    interval : Duration,
+}
+
+impl<R: io::Read + io::Seek> Follower<R> {
+    /// Creates a follower reading `stream` from its start, header included.
+    pub fn new(stream: R) -> Self {
+        Follower {
+            stream,
+            header   : None,
+            pos      : 0,
+            series   : None,
+            group    : group::ROOT.to_string(),
+            interval : Duration::from_millis(POLL_INTERVAL_MS),
+        }
+    }
+
+    /// Sets how long `next_item` sleeps between polls.
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Returns the header once it has been read.
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+
+    /// Path of the group which the last block read belongs to.
+    pub fn current_group(&self) -> &str {
+        &self.group
+    }
+
+    /// Descriptor of the f64ts block whose entries are being read, if any.
+    pub fn current_series(&self) -> Option<&F64TSBlock> {
+        self.series.as_ref().map(|s| &s.block)
+    }
+
+    pub fn into_inner(self) -> R {
+        self.stream
+    }
+
+    /// Reads the next block or entry, waiting until it has been written.
+    pub fn next_item(&mut self) -> Result<Item> {
+        loop {
+            if let Some(item) = self.try_next()? {
+                return Ok(item);
+            }
+            thread::sleep(self.interval);
+        }
+    }
+
+    /// Reads the next block or entry if it has been written completely, or
+    /// returns `None` without waiting.
+    pub fn try_next(&mut self) -> Result<Option<Item>> {
+        if self.header.is_none() {
+            let size = Header::new().size();
+            let buf = self.read_at(0, size as u64)?;
+            if buf.len() < size {
+                return Ok(None);
+            }
+            self.header = Some(Header::read_from(&mut io::Cursor::new(buf))?);
+            self.pos = size as u64;
+        }
+        if self.series.is_some() {
+            match self.next_entry()? {
+                Some(Some(entry)) => { return Ok(Some(Item::Entry(entry))); },
+                Some(None) => { return Ok(None); },
+                None => { self.series = None; },
+            }
+        }
+        self.next_block()
+    }
+
+    // Reads the next entry of the current series: `Some(None)` if it has
+    // not been written yet, and `None` if the next block has started.
+    fn next_entry(&mut self) -> Result<Option<Option<Entry>>> {
+        let (pos, row_size, row, index_len, value_len, length_pos) = {
+            let series = self.series.as_ref().unwrap();
+            (series.row_pos()?, series.block.row_size()?, series.row, series.block.index_len() as usize,
+             series.block.value_len() as usize, series.length_pos)
+        };
+        let buf = self.read_at(pos, row_size)?;
+        if buf.len() >= 8 && BlockHeader::check_magic(&buf) && row >= self.read_length(length_pos)? {
+            match self.block_starts_at(pos)? {
+                Some(true) => {
+                    self.pos = pos;
+                    return Ok(None);
+                },
+                Some(false) => {},
+                None => { return Ok(Some(None)); },
+            }
+        }
+        if (buf.len() as u64) < row_size {
+            return Ok(Some(None));
+        }
+        let entry = header::read_entry_from(&mut &buf[..], index_len, value_len)?;
+        self.series.as_mut().unwrap().row += 1;
+        Ok(Some(Some(entry)))
+    }
+
+    // Whether the bytes at `pos`, which start with the block magic number,
+    // go on with the name of a known block. `None` if too little has been
+    // written to tell.
+    fn block_starts_at(&mut self, pos: u64) -> Result<Option<bool>> {
+        let start = self.read_at(pos, 16)?;
+        if start.len() < 16 {
+            return Ok(None);
+        }
+        let name_len = LittleEndian::read_u64(&start[8..]);
+        if name_len > 8 {
+            return Ok(Some(false));
+        }
+        let name = self.read_at(pos + 16, name_len)?;
+        if (name.len() as u64) < name_len {
+            return Ok(None);
+        }
+        Ok(Some(BLOCK_NAMES.contains(&&name[..])))
+    }
+
+    // Reads the length field of the block, which may have been rewritten.
+    fn read_length(&mut self, pos: u64) -> Result<u64> {
+        let buf = self.read_at(pos, 8)?;
+        if buf.len() < 8 {
+            return Err(Error::EndOfFile);
+        }
+        Ok(LittleEndian::read_u64(&buf))
+    }
+
+    fn next_block(&mut self) -> Result<Option<Item>> {
+        let start = self.read_at(self.pos, 16)?;
+        if start.len() < 16 {
+            if start.len() >= 8 && !BlockHeader::check_magic(&start) {
+                return Err(Error::Magic);
+            }
+            return Ok(None);
+        }
+        if !BlockHeader::check_magic(&start) {
+            return Err(Error::Magic);
+        }
+        let name_len = LittleEndian::read_u64(&start[8..]);
+        let header_size = name_len.checked_add(8 + 8 + 8)
+            .ok_or_else(|| Error::Parse(format!("block name of {} bytes", name_len)))?;
+        let buf = self.read_at(self.pos, header_size)?;
+        if (buf.len() as u64) < header_size {
+            return Ok(None);
+        }
+        let bheader = BlockHeader::read_from(&mut io::Cursor::new(buf))?;
+        let size = header_size.checked_add(bheader.block_size())
+            .ok_or_else(|| Error::Parse(format!("block of {} bytes", bheader.block_size())))?;
+        let buf = self.read_at(self.pos, size)?;
+        if (buf.len() as u64) < size {
+            return Ok(None);
+        }
+        let block = reader::decode_block(&bheader.clone_name(), &mut &buf[header_size as usize..])?;
+        match block {
+            Block::Log(ref log) if log.is_padding() => {
+                self.pos += size;
+                return self.next_block();
+            },
+            Block::F64TS(ref data) => {
+                self.series = Some(Series {
+                    block      : data.clone(),
+                    length_pos : self.pos + header_size + 16,
+                    data_pos   : self.pos + size,
+                    row        : 0,
+                });
+            },
+            Block::Group(ref g) => {
+                self.group = g.path().to_string();
+            },
+            _ => {},
+        }
+        self.pos += size;
+        Ok(Some(Item::Block(block)))
+    }
+
+    // Reads up to `len` bytes at `pos`; fewer are returned at the end of
+    // the data written so far.
+    fn read_at(&mut self, pos: u64, len: u64) -> Result<Vec<u8>> {
+        self.stream.seek(SeekFrom::Start(pos))?;
+        let mut buf = Vec::new();
+        (&mut self.stream).take(len).read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+impl<R: io::Read + io::Seek> Iterator for Follower<R> {
+    type Item = Result<Item>;
+
+    /// Waits for the next block or entry; it never returns `None`.
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_item())
+    }
+}
@@ -0,0 +1,46 @@
+//! Paths of groups such as `/run3/sensor2`.
+//!
+//! A path is a sequence of names separated by `/`. Paths are kept in a
+//! normalized form: they start with `/`, and they have neither empty
+//! components nor a trailing `/`. The root group is `/`.
+
+pub const ROOT: &str = "/";
+
+pub fn normalize(path: &str) -> String {
+    let mut normalized = String::new();
+    for name in path.split('/').filter(|s| !s.is_empty() && *s != ".") {
+        normalized.push('/');
+        normalized.push_str(name);
+    }
+    if normalized.is_empty() {
+        normalized.push('/');
+    }
+    normalized
+}
+
+/// Appends `name` to the group at `path`.
+pub fn join(path: &str, name: &str) -> String {
+    normalize(&format!("{}/{}", path, name))
+}
+
+/// Returns the parent of the group at `path`, or `None` for the root group.
+pub fn parent(path: &str) -> Option<String> {
+    let path = normalize(path);
+    if path == ROOT {
+        return None;
+    }
+    let pos = path.rfind('/').unwrap();
+    Some(normalize(&path[..pos]))
+}
+
+/// Returns the last name in `path`.
+pub fn base_name(path: &str) -> &str {
+    path.rsplit('/').find(|s| !s.is_empty()).unwrap_or("")
+}
+
+/// Tests whether the group at `path` is `ancestor` or one of its descendants.
+pub fn is_within(path: &str, ancestor: &str) -> bool {
+    let path = normalize(path);
+    let ancestor = normalize(ancestor);
+    ancestor == ROOT || path == ancestor || path.starts_with(&format!("{}/", ancestor))
+}
@@ -1,7 +1,13 @@
 use std;
 use std::io;
+use std::io::Read;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use error::{Result, Error};
+use group;
+use Entry;
+
+// The program of the logs written as alignment padding.
+const PADDING_PROGRAM: &str = "broto.padding";
 
 
 fn read_string_from<R: io::Read>(reader: &mut R) -> Result<String> {
@@ -11,10 +17,48 @@ fn read_string_from<R: io::Read>(reader: &mut R) -> Result<String> {
         let c = reader.read_u8()?;
         v.push(c);
     }
-    String::from_utf8(v).map_err(|e| Error::FromUtf8(e))
+    String::from_utf8(v).map_err(Error::FromUtf8)
+}
+
+/// Reads into `magic` until it is full or the stream ends, and returns the
+/// number of bytes read, so that short reads are not taken for the end.
+pub fn read_magic<R: io::Read>(reader: &mut R, magic: &mut [u8; 8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < magic.len() {
+        match reader.read(&mut magic[n..]) {
+            Ok(0) => break,
+            Ok(m) => { n += m; },
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
+            Err(e) => { return Err(e); },
+        }
+    }
+    Ok(n)
+}
+
+/// Length written in place of a string which is absent.
+const NO_STRING: u64 = u64::MAX;
+
+fn read_optional_string_from<R: io::Read>(reader: &mut R) -> Result<Option<String>> {
+    let len = reader.read_u64::<LittleEndian>()?;
+    if len == NO_STRING {
+        return Ok(None);
+    }
+    let mut v = Vec::new();
+    reader.take(len).read_to_end(&mut v)?;
+    if (v.len() as u64) < len {
+        return Err(Error::EndOfFile);
+    }
+    String::from_utf8(v).map(Some).map_err(Error::FromUtf8)
 }
 
-fn write_string_into<W: io::Write>(string: &String, writer: &mut W) -> Result<()> {
+fn write_optional_string_into<W: io::Write>(string: Option<&str>, writer: &mut W) -> Result<()> {
+    match string {
+        Some(s) => write_string_into(s, writer),
+        None => Ok(writer.write_u64::<LittleEndian>(NO_STRING)?),
+    }
+}
+
+fn write_string_into<W: io::Write>(string: &str, writer: &mut W) -> Result<()> {
     writer.write_u64::<LittleEndian>(string.len() as u64)?;
     for c in string.bytes() {
         writer.write_u8(c)?;
@@ -22,7 +66,18 @@ fn write_string_into<W: io::Write>(string: &String, writer: &mut W) -> Result<()
     Ok(())
 }
 
-#[derive(Debug,PartialEq)]
+/// Major version of the format written by `Header::new`.
+pub const MAJOR_VERSION: u32 = 0;
+
+/// Minor version of the format written by `Header::new`. Version 0.2 adds
+/// the identifier, the name and the channels of a f64ts block, which a
+/// reader of 0.1 reads as entries.
+pub const MINOR_VERSION: u32 = 2;
+
+/// With the `serde` feature a header is (de)serialized as its versions,
+/// `{"major_version": 0, "minor_version": 2}`.
+#[derive(Debug,PartialEq,Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(from = "HeaderVersions", into = "HeaderVersions"))]
 pub struct Header {
     magic_number    : [u8; 8],
     header_size     : u64,
@@ -34,13 +89,19 @@ pub struct Header {
     reserved3       : u64,
 }
 
+impl Default for Header {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Header {
     pub fn new() -> Header {
         Header {
             magic_number  : Header::clone_magic(),
             header_size   : std::mem::size_of::<Header>() as u64,
-            major_version : 0,
-            minor_version : 1,
+            major_version : MAJOR_VERSION,
+            minor_version : MINOR_VERSION,
             reserved0     : 0,
             reserved1     : 0,
             reserved2     : 0,
@@ -59,33 +120,52 @@ impl Header {
         magic.iter().zip(input.iter()).all(|(&x, &y)| x == y)
     }
 
+    pub fn header_size(&self) -> u64 {
+        self.header_size
+    }
+
+    pub fn major_version(&self) -> u32 {
+        self.major_version
+    }
+
+    pub fn minor_version(&self) -> u32 {
+        self.minor_version
+    }
+
+    pub fn size(&self) -> usize {
+        8 + 8 + 4 + 4 + 8 * 4
+    }
+
     pub fn read_from<R: io::Read>(reader: &mut R) -> Result<Self> {
         let mut magic: [u8; 8] = [0; 8];
-        let result = reader.read(&mut magic);
+        let result = read_magic(reader, &mut magic);
         match result {
             Ok(n) if n < 8 => { return Err(Error::EndOfFile); },
             Err(e) => { return Err(Error::Io(e)); },
             _ => {},
         }
-        if Header::check_magic(&magic) == false {
+        if !Header::check_magic(&magic) {
             return Err(Error::Magic);
         }
         let header_size = reader.read_u64::<LittleEndian>()?;
         let major_version = reader.read_u32::<LittleEndian>()?;
         let minor_version = reader.read_u32::<LittleEndian>()?;
+        if major_version != MAJOR_VERSION || minor_version > MINOR_VERSION {
+            return Err(Error::Parse(format!("unsupported format version {}.{}", major_version, minor_version)));
+        }
         let reserved0 = reader.read_u64::<LittleEndian>()?;
         let reserved1 = reader.read_u64::<LittleEndian>()?;
         let reserved2 = reader.read_u64::<LittleEndian>()?;
         let reserved3 = reader.read_u64::<LittleEndian>()?;
         let hd = Header {
             magic_number : magic,
-            header_size  : header_size,
-            major_version: major_version,
-            minor_version: minor_version,
-            reserved0    : reserved0,
-            reserved1    : reserved1,
-            reserved2    : reserved2,
-            reserved3    : reserved3,
+            header_size,
+            major_version,
+            minor_version,
+            reserved0,
+            reserved1,
+            reserved2,
+            reserved3,
         };
         Ok(hd)
     }
@@ -108,6 +188,34 @@ impl Header {
     }
 }
 
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct HeaderVersions {
+    major_version : u32,
+    minor_version : u32,
+}
+
+#[cfg(feature = "serde")]
+impl From<HeaderVersions> for Header {
+    fn from(versions: HeaderVersions) -> Self {
+        Header {
+            major_version : versions.major_version,
+            minor_version : versions.minor_version,
+            ..Header::new()
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Header> for HeaderVersions {
+    fn from(header: Header) -> Self {
+        HeaderVersions {
+            major_version : header.major_version,
+            minor_version : header.minor_version,
+        }
+    }
+}
+
 #[derive(Debug,PartialEq)]
 pub struct BlockHeader {
     magic : [u8; 8],
@@ -120,12 +228,22 @@ impl BlockHeader {
         BlockHeader {
             magic : Self::clone_magic(),
             name  : name.into(),
-            size  : size,
+            size,
         }
     }
 
     pub fn clone_name(&self) -> String {
-        String::from(self.name.clone())
+        self.name.clone()
+    }
+
+    /// Number of bytes between the end of the block header and the next block
+    /// (or the payload of a f64ts block), including any padding.
+    pub fn block_size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn size(&self) -> usize {
+        8 + (8 + self.name.len()) + 8
     }
 
     pub fn clone_magic() -> [u8; 8] {
@@ -141,21 +259,21 @@ impl BlockHeader {
 
     pub fn read_from<R: io::Read>(reader: &mut R) -> Result<Self> {
         let mut magic: [u8; 8] = [0; 8];
-        let result = reader.read(&mut magic);
+        let result = read_magic(reader, &mut magic);
         match result {
             Ok(n) if n < 8 => { return Err(Error::EndOfFile); },
             Err(e) => { return Err(e.into()); },
             _ => {},
         }
-        if Self::check_magic(&magic) == false {
+        if !Self::check_magic(&magic) {
             return Err(Error::Magic);
         }
         let name = read_string_from(reader)?;
         let size = reader.read_u64::<LittleEndian>()?;
         let hd = BlockHeader {
-            magic : magic,
-            name  : name,
-            size  : size,
+            magic,
+            name,
+            size,
         };
         Ok(hd)
     }
@@ -173,11 +291,27 @@ impl BlockHeader {
     }
 }
 
+/// Descriptor of a time-series of floating-point numbers.
+///
+/// A named series carries an extension area after the fixed fields:
+/// its length in bytes followed by the identifier, the name and, if any,
+/// the number of channel names and the names. An unnamed series with
+/// channel names stores `u64::MAX` as the length of its name. The area is
+/// counted in the size of the block header, and a zero length (which is what
+/// the alignment padding of older writers reads as) means that there is no
+/// extension.
+///
+/// With the `serde` feature the shape is checked when a block is
+/// deserialized, as `F64TSBlockBuilder::build` does.
 #[derive(Debug,PartialEq,Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(try_from = "F64TSBlockFields"))]
 pub struct F64TSBlock {
     index_len  : u64,
     value_len  : u64,
     length     : Option<u64>,
+    id         : u64,
+    name       : Option<String>,
+    channels   : Vec<String>,
 }
 
 impl F64TSBlock {
@@ -197,34 +331,159 @@ impl F64TSBlock {
         self.length = Some(len);
     }
 
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Names of the values, or an empty slice if they are not named.
+    pub fn channel_names(&self) -> &[String] {
+        &self.channels
+    }
+
     pub fn size(&self) -> usize {
-        8 + 8 + 8
+        8 + 8 + 8 + self.extension_size().map_or(0, |n| 8 + n)
+    }
+
+    fn extension_size(&self) -> Option<usize> {
+        if self.name.is_none() && self.channels.is_empty() {
+            return None;
+        }
+        let mut size = 8 + (8 + self.name.as_ref().map_or(0, |s| s.len()));
+        if !self.channels.is_empty() {
+            size += 8 + self.channels.iter().map(|s| 8 + s.len()).sum::<usize>();
+        }
+        Some(size)
     }
 
+    /// Size of an entry in bytes. Fails with `Error::Parse` if it does not
+    /// fit in a `u64`, which only a corrupt block declares.
+    pub fn row_size(&self) -> Result<u64> {
+        self.index_len.checked_add(self.value_len).and_then(|n| n.checked_mul(8))
+            .ok_or_else(|| Error::Parse(format!("f64ts block with {} + {} values per entry",
+                                                self.index_len, self.value_len)))
+    }
+
+    /// Size of the entries following the block in bytes. Fails with
+    /// `Error::Parse` if it does not fit in a `u64`.
+    pub fn data_size(&self) -> Result<u64> {
+        let length = self.length.unwrap_or(0);
+        self.row_size()?.checked_mul(length)
+            .ok_or_else(|| Error::Parse(format!("f64ts block of {} entries is too large", length)))
+    }
+
+    /// Reads the block from `reader`, which must end where the block ends
+    /// (the size of the block header).
     pub fn read_from<R: io::Read>(reader: &mut R) -> Result<Self> {
         let index_len = reader.read_u64::<LittleEndian>()?;
         let value_len = reader.read_u64::<LittleEndian>()?;
         let length = reader.read_u64::<LittleEndian>()?;
-        Ok(F64TSBlock {
-            index_len: index_len,
-            value_len: value_len,
+        let mut block = F64TSBlock {
+            index_len,
+            value_len,
             length: Some(length),
-        })
+            id: 0,
+            name: None,
+            channels: Vec::new(),
+        };
+        let ext_len = match reader.read_u64::<LittleEndian>() {
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => 0,
+            Err(e) => { return Err(e.into()); },
+        };
+        if ext_len > 0 {
+            let mut ext = Vec::new();
+            reader.take(ext_len).read_to_end(&mut ext)?;
+            if (ext.len() as u64) < ext_len {
+                return Err(Error::EndOfFile);
+            }
+            let mut ext = io::Cursor::new(ext);
+            block.id = ext.read_u64::<LittleEndian>()?;
+            block.name = read_optional_string_from(&mut ext)?;
+            if ext.position() < ext_len {
+                let n = ext.read_u64::<LittleEndian>()?;
+                for _ in 0..n {
+                    block.channels.push(read_string_from(&mut ext)?);
+                }
+            }
+        }
+        Ok(block)
     }
 
     pub fn write_into<W: io::Write>(&self, writer: &mut W) -> Result<()> {
         writer.write_u64::<LittleEndian>(self.index_len)?;
         writer.write_u64::<LittleEndian>(self.value_len)?;
         writer.write_u64::<LittleEndian>(self.length.unwrap_or(0))?;
+        if let Some(ext_len) = self.extension_size() {
+            writer.write_u64::<LittleEndian>(ext_len as u64)?;
+            writer.write_u64::<LittleEndian>(self.id)?;
+            write_optional_string_into(self.name.as_deref(), writer)?;
+            if !self.channels.is_empty() {
+                writer.write_u64::<LittleEndian>(self.channels.len() as u64)?;
+                for channel in self.channels.iter() {
+                    write_string_into(channel, writer)?;
+                }
+            }
+        }
         Ok(())
     }
 }
 
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct F64TSBlockFields {
+    index_len : u64,
+    value_len : u64,
+    #[serde(default)]
+    length    : Option<u64>,
+    #[serde(default)]
+    id        : u64,
+    #[serde(default)]
+    name      : Option<String>,
+    #[serde(default)]
+    channels  : Vec<String>,
+}
+
+#[cfg(feature = "serde")]
+impl std::convert::TryFrom<F64TSBlockFields> for F64TSBlock {
+    type Error = Error;
+
+    fn try_from(fields: F64TSBlockFields) -> Result<Self> {
+        if fields.index_len != 1 {
+            return Err(Error::Parse(format!("index_len must be 1, not {}", fields.index_len)));
+        }
+        if !fields.channels.is_empty() && fields.channels.len() as u64 != fields.value_len {
+            return Err(Error::Parse(format!("{} channel names for value_len {}",
+                                            fields.channels.len(), fields.value_len)));
+        }
+        Ok(F64TSBlock {
+            index_len : fields.index_len,
+            value_len : fields.value_len,
+            length    : fields.length,
+            id        : fields.id,
+            name      : fields.name,
+            channels  : fields.channels,
+        })
+    }
+}
+
 #[derive(Debug,PartialEq)]
 pub struct F64TSBlockBuilder<IdxLenType,ValLenType> {
     index_len : IdxLenType,
     value_len : ValLenType,
     length    : Option<u64>,
+    id        : u64,
+    name      : Option<String>,
+    channels  : Vec<String>,
+}
+
+impl Default for F64TSBlockBuilder<(), ()> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl F64TSBlockBuilder<(), ()> {
@@ -233,6 +492,9 @@ impl F64TSBlockBuilder<(), ()> {
             index_len : (),
             value_len : (),
             length : None,
+            id : 0,
+            name : None,
+            channels : Vec::new(),
         }
     }
 }
@@ -243,6 +505,9 @@ impl<IdxLenType, ValLenType> F64TSBlockBuilder<IdxLenType, ValLenType> {
             index_len : len,
             value_len : self.value_len,
             length    : self.length,
+            id        : self.id,
+            name      : self.name,
+            channels  : self.channels,
         }
     }
 
@@ -251,31 +516,55 @@ impl<IdxLenType, ValLenType> F64TSBlockBuilder<IdxLenType, ValLenType> {
             index_len : self.index_len,
             value_len : len,
             length    : self.length,
+            id        : self.id,
+            name      : self.name,
+            channels  : self.channels,
         }
     }
 
-    pub fn length(self, len: u64) -> Self {
-        F64TSBlockBuilder {
-            index_len : self.index_len,
-            value_len : self.value_len,
-            length : Some(len),
-        }
+    pub fn length(mut self, len: u64) -> Self {
+        self.length = Some(len);
+        self
+    }
+
+    pub fn id(mut self, id: u64) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn channel_names<S: Into<String>>(mut self, names: Vec<S>) -> Self {
+        self.channels = names.into_iter().map(|s| s.into()).collect();
+        self
     }
 }
 
 impl F64TSBlockBuilder<u64, u64> {
     pub fn build(self) -> F64TSBlock {
         assert_eq!(self.index_len, 1);
+        assert!(self.channels.is_empty() || self.channels.len() as u64 == self.value_len,
+                "the number of channel names must equal value_len");
         F64TSBlock {
             index_len : self.index_len,
             value_len : self.value_len,
             length    : self.length,
+            id        : self.id,
+            name      : self.name,
+            channels  : self.channels,
         }
     }
 }
 
-#[derive(Debug,PartialEq)]
+/// With the `serde` feature the time is (de)serialized as an RFC 3339
+/// timestamp in UTC.
+#[derive(Debug,PartialEq,Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct LogBlock {
+    #[cfg_attr(feature = "serde", serde(with = "::timestamp::rfc3339"))]
     time    : std::time::Duration,
     program : String,
     info    : String,
@@ -291,23 +580,32 @@ impl LogBlock {
     }
 
     pub fn time(&self) -> std::time::Duration {
-        self.time.clone()
+        self.time
     }
 
     pub fn size(&self) -> usize {
         8 + 4 + (8 + self.program.len()) + (8 + self.info.len())
     }
 
+    /// Returns whether the log is alignment padding written by
+    /// `Writer::with_alignment`. Readers skip such logs.
+    pub fn is_padding(&self) -> bool {
+        self.time == std::time::Duration::new(0, 0) && self.program == PADDING_PROGRAM
+    }
+
     pub fn read_from<R: io::Read>(reader: &mut R) -> Result<Self> {
         let secs = reader.read_u64::<LittleEndian>()?;
         let nanos = reader.read_u32::<LittleEndian>()?;
+        if nanos >= 1_000_000_000 {
+            return Err(Error::Parse(format!("log time with {} nanoseconds", nanos)));
+        }
         let dur = std::time::Duration::new(secs, nanos);
         let program = read_string_from(reader)?;
         let info = read_string_from(reader)?;
         let log = LogBlock {
             time : dur,
-            program : program,
-            info : info,
+            program,
+            info,
         };
         Ok(log)
     }
@@ -328,6 +626,12 @@ pub struct LogBlockBuilder<ProgType, InfoType> {
     info    : InfoType,
 }
 
+impl Default for LogBlockBuilder<(), ()> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LogBlockBuilder<(), ()> {
     pub fn new() -> Self {
         LogBlockBuilder {
@@ -372,3 +676,196 @@ impl<ProgType, InfoType> LogBlockBuilder<ProgType, InfoType> {
         self
     }
 }
+
+/// Makes the blocks following it belong to the group at `path`.
+#[derive(Debug,PartialEq,Clone)]
+pub struct GroupBlock {
+    path : String,
+}
+
+impl GroupBlock {
+    pub fn new(path: &str) -> Self {
+        GroupBlock {
+            path : group::normalize(path),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn size(&self) -> usize {
+        8 + self.path.len()
+    }
+
+    pub fn read_from<R: io::Read>(reader: &mut R) -> Result<Self> {
+        let path = read_string_from(reader)?;
+        Ok(GroupBlock::new(&path))
+    }
+
+    pub fn write_into<W: io::Write>(&self, writer: &mut W) -> Result<()> {
+        write_string_into(&self.path, writer)
+    }
+}
+
+/// A key-value attribute of the group it belongs to.
+#[derive(Debug,PartialEq,Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AttrBlock {
+    key   : String,
+    value : String,
+}
+
+impl AttrBlock {
+    pub fn new<K: Into<String>, V: Into<String>>(key: K, value: V) -> Self {
+        AttrBlock {
+            key   : key.into(),
+            value : value.into(),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn size(&self) -> usize {
+        (8 + self.key.len()) + (8 + self.value.len())
+    }
+
+    pub fn read_from<R: io::Read>(reader: &mut R) -> Result<Self> {
+        let key = read_string_from(reader)?;
+        let value = read_string_from(reader)?;
+        Ok(AttrBlock { key, value })
+    }
+
+    pub fn write_into<W: io::Write>(&self, writer: &mut W) -> Result<()> {
+        write_string_into(&self.key, writer)?;
+        write_string_into(&self.value, writer)?;
+        Ok(())
+    }
+}
+
+/// An annotation of the group it belongs to: a text marking an instant or
+/// an interval of the index, such as a stimulus or an artifact.
+#[derive(Debug,PartialEq,Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EventBlock {
+    onset    : f64,
+    duration : Option<f64>,
+    text     : String,
+}
+
+impl EventBlock {
+    pub fn new<S: Into<String>>(onset: f64, duration: Option<f64>, text: S) -> Self {
+        EventBlock {
+            onset,
+            duration,
+            text : text.into(),
+        }
+    }
+
+    /// The index at which the event starts.
+    pub fn onset(&self) -> f64 {
+        self.onset
+    }
+
+    pub fn duration(&self) -> Option<f64> {
+        self.duration
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn size(&self) -> usize {
+        8 + 8 + (8 + self.text.len())
+    }
+
+    pub fn read_from<R: io::Read>(reader: &mut R) -> Result<Self> {
+        let onset = reader.read_f64::<LittleEndian>()?;
+        // A missing duration is written as NaN.
+        let duration = reader.read_f64::<LittleEndian>()?;
+        let duration = if duration.is_nan() { None } else { Some(duration) };
+        let text = read_string_from(reader)?;
+        Ok(EventBlock { onset, duration, text })
+    }
+
+    pub fn write_into<W: io::Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_f64::<LittleEndian>(self.onset)?;
+        writer.write_f64::<LittleEndian>(self.duration.unwrap_or(f64::NAN))?;
+        write_string_into(&self.text, writer)?;
+        Ok(())
+    }
+}
+
+/// Encodes a block: the block header and the body written by `write_body`.
+/// `Writer` and `async_io::AsyncWriter` encode their blocks with this.
+pub fn encode_block<F>(name: &str, write_body: F) -> Result<Vec<u8>>
+    where F: FnOnce(&mut Vec<u8>) -> Result<()>
+{
+    let mut body = Vec::new();
+    write_body(&mut body)?;
+    let header = BlockHeader::new(name, body.len() as u64);
+    let mut buf = Vec::with_capacity(header.size() + body.len());
+    header.write_into(&mut buf)?;
+    buf.extend_from_slice(&body);
+    Ok(buf)
+}
+
+/// Returns the size of the padding to write at `position` before `block`
+/// so that its entries start at a multiple of `alignment`: zero, or the
+/// size of a padding log, which is at least `PADDING_MIN`.
+pub fn f64ts_padding(block: &F64TSBlock, position: u64, alignment: u64) -> u64 {
+    let end = position + (BlockHeader::new("f64ts", 0).size() + block.size()) as u64;
+    let mut padding = (alignment - end % alignment) % alignment;
+    while padding > 0 && padding < PADDING_MIN {
+        padding += alignment;
+    }
+    padding
+}
+
+/// Size of the smallest padding log: the block header and a log with an
+/// empty info.
+pub const PADDING_MIN: u64 = (8 + (8 + 3) + 8) + 8 + 4 + (8 + PADDING_PROGRAM.len() as u64) + 8;
+
+/// Encodes a padding log of `size` bytes, at least `PADDING_MIN`.
+///
+/// The padding is a log rather than bytes in front of the payload because
+/// readers before `with_alignment` read the entries right after the fixed
+/// fields of a f64ts block; they read a padding log as an ordinary log.
+pub fn encode_padding(size: u64) -> Result<Vec<u8>> {
+    assert!(size >= PADDING_MIN);
+    let log = LogBlock {
+        time    : std::time::Duration::new(0, 0),
+        program : PADDING_PROGRAM.to_string(),
+        info    : " ".repeat((size - PADDING_MIN) as usize),
+    };
+    encode_block("log", |b| log.write_into(b))
+}
+
+/// Writes an entry of a f64ts block with an index of one value.
+pub fn write_entry_into<W: io::Write>(writer: &mut W, index: f64, values: &[f64]) -> Result<()> {
+    writer.write_f64::<LittleEndian>(index)?;
+    for &x in values.iter() {
+        writer.write_f64::<LittleEndian>(x)?;
+    }
+    Ok(())
+}
+
+/// Reads an entry of a f64ts block. Only the first of the `index_len`
+/// values of the index is kept.
+pub fn read_entry_from<R: io::Read>(reader: &mut R, index_len: usize, value_len: usize) -> Result<Entry> {
+    let index = reader.read_f64::<LittleEndian>()?;
+    for _ in 1..index_len {
+        reader.read_f64::<LittleEndian>()?;
+    }
+    let mut values = Vec::with_capacity(value_len);
+    for _ in 0..value_len {
+        values.push(reader.read_f64::<LittleEndian>()?);
+    }
+    Ok((index, values))
+}
@@ -0,0 +1,464 @@
+//! Conversion between series and InfluxDB line protocol.
+//!
+//! A series is written as the measurement of its name with one field per
+//! channel and one line per entry. The attributes of its group become tags,
+//! and a series outside the root group has the tag `group` with the path
+//! of its group. An imported series keeps its measurement and tags in the
+//! attribute `influx.series.NAME` of its group, which takes precedence. The
+//! index is taken to be seconds since the UNIX epoch and written as an
+//! integer timestamp of the chosen precision.
+//!
+//! Both directions work line by line, so files of any size can be
+//! converted.
+
+use std::io;
+use std::str::FromStr;
+use lazy::LazyReader;
+use writer::Writer;
+use header::{F64TSBlockBuilder, AttrBlock};
+use error::{Result, Error};
+use group;
+use {Metadata, Entry};
+
+/// The unit of timestamps.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Precision {
+    Nanoseconds,
+    Microseconds,
+    Milliseconds,
+    Seconds,
+}
+
+impl Precision {
+    /// Number of units in a second.
+    pub fn per_second(self) -> f64 {
+        match self {
+            Precision::Nanoseconds => 1e9,
+            Precision::Microseconds => 1e6,
+            Precision::Milliseconds => 1e3,
+            Precision::Seconds => 1.0,
+        }
+    }
+
+    pub fn timestamp(self, index: f64) -> i64 {
+        (index * self.per_second()).round() as i64
+    }
+
+    pub fn index(self, timestamp: i64) -> f64 {
+        timestamp as f64 / self.per_second()
+    }
+}
+
+impl FromStr for Precision {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ns" | "n" => Ok(Precision::Nanoseconds),
+            "us" | "u" => Ok(Precision::Microseconds),
+            "ms" => Ok(Precision::Milliseconds),
+            "s" => Ok(Precision::Seconds),
+            _ => Err(Error::Parse(format!("unknown precision: {}", s))),
+        }
+    }
+}
+
+fn escape(s: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c == '\\' || special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Returns the names of the fields of a series: its channel names, or
+/// `value` for a single unnamed value and `value0`, `value1`, ... otherwise.
+pub fn field_names(channels: &[String], value_len: usize) -> Vec<String> {
+    if channels.len() == value_len {
+        channels.to_vec()
+    } else if value_len == 1 {
+        vec!["value".to_string()]
+    } else {
+        (0..value_len).map(|n| format!("value{}", n)).collect()
+    }
+}
+
+/// Prefix of the attributes recording the series key of imported series.
+const SERIES_ATTR: &str = "influx.series.";
+
+/// Returns the tags of a series in the group at `path` with `metadata`.
+/// Attributes with empty keys or values, which cannot be tags, are left out,
+/// as are the `influx.series.` attributes.
+pub fn tags(path: &str, metadata: &Metadata) -> Vec<(String, String)> {
+    let mut tags: Vec<(String, String)> = Vec::new();
+    for attr in metadata.get_attrs() {
+        if attr.key().is_empty() || attr.value().is_empty() || attr.key() == "group"
+            || attr.key().starts_with(SERIES_ATTR) {
+            continue;
+        }
+        match tags.iter().position(|t| t.0 == attr.key()) {
+            Some(pos) => { tags[pos].1 = attr.value().to_string(); },
+            None => { tags.push((attr.key().to_string(), attr.value().to_string())); },
+        }
+    }
+    if path != group::ROOT {
+        tags.push(("group".to_string(), path.to_string()));
+    }
+    tags.sort();
+    tags
+}
+
+/// Returns the measurement and the tags of the series `name` in the group
+/// at `path` with `metadata`: its name and the tags of the group, with those
+/// recorded by `Importer` taking precedence.
+pub fn series_key(path: &str, name: &str, metadata: &Metadata) -> (String, Vec<(String, String)>) {
+    let mut tags = tags(path, metadata);
+    let key = match metadata.get_attr(&format!("{}{}", SERIES_ATTR, name)) {
+        Some(key) => split_unescaped(key, ','),
+        None => { return (name.to_string(), tags); },
+    };
+    for tag in key[1..].iter() {
+        let (k, v) = match key_value(tag) {
+            Ok(kv) => kv,
+            Err(_) => continue,
+        };
+        let v = unescape(v);
+        match tags.iter().position(|t| t.0 == k) {
+            Some(pos) => { tags[pos].1 = v; },
+            None => { tags.push((k, v)); },
+        }
+    }
+    tags.sort();
+    (unescape(key[0]), tags)
+}
+
+// Formats a measurement and tags as the series key of a line.
+fn format_key(measurement: &str, tags: &[(String, String)]) -> String {
+    let mut key = escape(measurement, &[',', ' ']);
+    for (k, v) in tags.iter() {
+        key.push(',');
+        key.push_str(&escape(k, &[',', '=', ' ']));
+        key.push('=');
+        key.push_str(&escape(v, &[',', '=', ' ']));
+    }
+    key
+}
+
+/// Writes entries as lines of the measurement `measurement`. Values which
+/// are NaN or infinite cannot be written and are left out, as are entries
+/// without any other value. Returns the number of lines written.
+pub fn write_lines<W, I>(out: &mut W, measurement: &str, tags: &[(String, String)], fields: &[String],
+                         entries: I, precision: Precision) -> Result<u64>
+    where W: io::Write, I: Iterator<Item = Result<Entry>>
+{
+    let key = format_key(measurement, tags);
+    let fields: Vec<String> = fields.iter().map(|f| escape(f, &[',', '=', ' '])).collect();
+
+    let mut lines = 0;
+    for entry in entries {
+        let (index, values) = entry?;
+        if values.len() != fields.len() {
+            return Err(Error::Shape);
+        }
+        let values: Vec<String> = fields.iter().zip(values.iter())
+            .filter(|&(_, x)| x.is_finite())
+            .map(|(f, x)| format!("{}={}", f, x))
+            .collect();
+        if values.is_empty() {
+            continue;
+        }
+        writeln!(out, "{} {} {}", key, values.join(","), precision.timestamp(index))?;
+        lines += 1;
+    }
+    Ok(lines)
+}
+
+/// Writes every series read by `lazy` as line protocol. Returns the number
+/// of lines written.
+pub fn export<R, W>(lazy: &mut LazyReader<R>, out: &mut W, precision: Precision) -> Result<u64>
+    where R: io::Read + io::Seek, W: io::Write
+{
+    let mut lines = 0;
+    for n in 0..lazy.series().len() {
+        let (measurement, tags, fields) = {
+            let info = &lazy.series()[n];
+            let metadata = lazy.group(info.group()).unwrap();
+            let name = info.name().map(|s| s.to_string()).unwrap_or_else(|| format!("series{}", n));
+            let (measurement, tags) = series_key(info.group(), &name, metadata);
+            let fields = field_names(info.block().channel_names(), info.value_len() as usize);
+            (measurement, tags, fields)
+        };
+        lines += write_lines(out, &measurement, &tags, &fields, lazy.entries(n)?, precision)?;
+    }
+    Ok(lines)
+}
+
+/// A line of line protocol.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Point {
+    pub measurement : String,
+    pub tags        : Vec<(String, String)>,
+    pub fields      : Vec<(String, f64)>,
+    pub timestamp   : i64,
+}
+
+// Splits `s` at `sep` outside of double quotes and not escaped by `\`.
+fn split_unescaped(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            quoted = !quoted;
+        } else if c == sep && !quoted {
+            parts.push(&s[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => { unescaped.push(chars.next().unwrap_or('\\')); },
+            c => { unescaped.push(c); },
+        }
+    }
+    unescaped
+}
+
+fn key_value(s: &str) -> Result<(String, &str)> {
+    let kv = split_unescaped(s, '=');
+    if kv.len() != 2 || kv[0].is_empty() {
+        return Err(Error::Parse(format!("invalid key=value: {}", s)));
+    }
+    Ok((unescape(kv[0]), kv[1]))
+}
+
+fn field_value(key: &str, s: &str) -> Result<f64> {
+    let invalid = || Error::Parse(format!("invalid value of field {}: {}", key, s));
+    match s {
+        "t" | "T" | "true" | "True" | "TRUE" => Ok(1.0),
+        "f" | "F" | "false" | "False" | "FALSE" => Ok(0.0),
+        _ if s.starts_with('"') => Err(Error::Parse(format!("string field {} is not supported", key))),
+        _ if s.ends_with('i') || s.ends_with('u') => {
+            s[..s.len() - 1].parse::<i128>().map(|x| x as f64).map_err(|_| invalid())
+        },
+        _ => s.parse().map_err(|_| invalid()),
+    }
+}
+
+/// Parses a line of line protocol. Returns `None` for blank lines and
+/// comments. String fields are not supported, and the timestamp is required.
+pub fn parse_line(line: &str) -> Result<Option<Point>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let parts: Vec<&str> = split_unescaped(line, ' ').into_iter().filter(|p| !p.is_empty()).collect();
+    if parts.len() != 3 {
+        return Err(Error::Parse(format!("expected measurement, fields and timestamp: {}", line)));
+    }
+
+    let key = split_unescaped(parts[0], ',');
+    let measurement = unescape(key[0]);
+    if measurement.is_empty() {
+        return Err(Error::Parse("empty measurement".to_string()));
+    }
+    let mut tags = Vec::new();
+    for tag in key[1..].iter() {
+        let (k, v) = key_value(tag)?;
+        tags.push((k, unescape(v)));
+    }
+    tags.sort();
+    let mut fields = Vec::new();
+    for field in split_unescaped(parts[1], ',') {
+        let (k, v) = key_value(field)?;
+        let x = field_value(&k, v)?;
+        fields.push((k, x));
+    }
+    let timestamp = parts[2].parse()
+        .map_err(|_| Error::Parse(format!("invalid timestamp: {}", parts[2])))?;
+    Ok(Some(Point { measurement, tags, fields, timestamp }))
+}
+
+// A series being imported and its entries not yet written.
+#[derive(Debug)]
+struct PendingSeries {
+    measurement : String,
+    tags        : Vec<(String, String)>,
+    group       : String,
+    name        : String,
+    fields      : Vec<String>,
+    entries     : Vec<Entry>,
+    written     : bool,
+}
+
+/// Writes points into f64ts blocks.
+///
+/// A series is identified by its measurement and tags. The `group` tag
+/// gives its group, and the measurement and the other tags are recorded in
+/// the attribute `influx.series.NAME` of the group. A series whose name is
+/// taken in its group by a series with other tags is named by its series
+/// key such as `cpu,host=b`. Its fields are those of its first point;
+/// missing fields are NaN.
+///
+/// Entries are kept in memory up to `chunk_rows` per series and then
+/// written as a block, so a series may span several blocks.
+#[derive(Debug)]
+pub struct Importer<W: io::Write> {
+    writer     : Option<Writer<W>>,
+    precision  : Precision,
+    chunk_rows : usize,
+    group      : String,
+    series     : Vec<PendingSeries>,
+}
+
+impl<W: io::Write> Importer<W> {
+    /// Writes the blocks with `writer`, whose header must have been written.
+    pub fn new(writer: Writer<W>, precision: Precision) -> Self {
+        Importer {
+            writer     : Some(writer),
+            precision,
+            chunk_rows : 65_536,
+            group      : group::ROOT.to_string(),
+            series     : Vec::new(),
+        }
+    }
+
+    pub fn chunk_rows(mut self, rows: usize) -> Self {
+        assert!(rows > 0);
+        self.chunk_rows = rows;
+        self
+    }
+
+    pub fn push(&mut self, point: Point) -> Result<()> {
+        let pos = self.series.iter()
+            .position(|s| s.measurement == point.measurement && s.tags == point.tags);
+        let pos = match pos {
+            Some(pos) => pos,
+            None => {
+                let group = point.tags.iter().find(|t| t.0 == "group")
+                    .map(|t| group::normalize(&t.1))
+                    .unwrap_or_else(|| group::ROOT.to_string());
+                let tags: Vec<(String, String)> = point.tags.iter().filter(|t| t.0 != "group").cloned().collect();
+                let mut name = point.measurement.clone();
+                if self.series.iter().any(|s| s.group == group && s.name == name) {
+                    for (k, v) in tags.iter() {
+                        name.push_str(&format!(",{}={}", k, v));
+                    }
+                }
+                self.series.push(PendingSeries {
+                    measurement : point.measurement.clone(),
+                    tags        : point.tags.clone(),
+                    group,
+                    name,
+                    fields      : point.fields.iter().map(|f| f.0.clone()).collect(),
+                    entries     : Vec::new(),
+                    written     : false,
+                });
+                self.series.len() - 1
+            },
+        };
+
+        let mut values = vec![f64::NAN; self.series[pos].fields.len()];
+        for (key, x) in point.fields {
+            match self.series[pos].fields.iter().position(|f| *f == key) {
+                Some(n) => { values[n] = x; },
+                None => {
+                    return Err(Error::Parse(format!("field {} is not in the first line of {}",
+                                                    key, point.measurement)));
+                },
+            }
+        }
+        self.series[pos].entries.push((self.precision.index(point.timestamp), values));
+        if self.series[pos].entries.len() >= self.chunk_rows {
+            self.flush(pos)?;
+        }
+        Ok(())
+    }
+
+    fn take_writer(&mut self) -> Result<Writer<W>> {
+        self.writer.take()
+            .ok_or_else(|| Error::Io(io::Error::other("the importer has stopped on an error")))
+    }
+
+    // Writes the entries of the series at `pos` as a block. The writer is
+    // lost if this fails.
+    fn flush(&mut self, pos: usize) -> Result<()> {
+        let mut writer = self.take_writer()?;
+        let series = &mut self.series[pos];
+        if self.group != series.group {
+            writer.write_group(&series.group)?;
+            self.group = series.group.clone();
+        }
+        let tags: Vec<(String, String)> = series.tags.iter().filter(|t| t.0 != "group").cloned().collect();
+        if !series.written && (!tags.is_empty() || series.name != series.measurement) {
+            let key = format_key(&series.measurement, &tags);
+            writer.write_attr(&AttrBlock::new(format!("{}{}", SERIES_ATTR, series.name), key))?;
+        }
+        series.written = true;
+        let block = F64TSBlockBuilder::new()
+            .index_len(1)
+            .value_len(series.fields.len() as u64)
+            .length(series.entries.len() as u64)
+            .name(series.name.clone())
+            .channel_names(series.fields.clone())
+            .build();
+        let mut w = writer.write_f64ts(block)?;
+        for &(index, ref values) in series.entries.iter() {
+            w.write_entry(index, values)?;
+        }
+        self.writer = Some(w.finish());
+        series.entries.clear();
+        Ok(())
+    }
+
+    /// Pushes the points of every line of `input`. Returns the number of
+    /// points.
+    pub fn read_lines<R: io::BufRead>(&mut self, input: R) -> Result<u64> {
+        let mut points = 0;
+        for (n, line) in input.lines().enumerate() {
+            let point = parse_line(&line?).map_err(|e| match e {
+                Error::Parse(msg) => Error::Parse(format!("line {}: {}", n + 1, msg)),
+                e => e,
+            })?;
+            if let Some(point) = point {
+                self.push(point)?;
+                points += 1;
+            }
+        }
+        Ok(points)
+    }
+
+    /// Writes the remaining entries and returns the writer.
+    pub fn finish(mut self) -> Result<Writer<W>> {
+        for pos in 0..self.series.len() {
+            if !self.series[pos].entries.is_empty() {
+                self.flush(pos)?;
+            }
+        }
+        self.take_writer()
+    }
+}
+
+/// Reads line protocol from `input` and writes it with `writer`.
+pub fn import<R, W>(input: R, writer: Writer<W>, precision: Precision) -> Result<Writer<W>>
+    where R: io::BufRead, W: io::Write
+{
+    let mut importer = Importer::new(writer, precision);
+    importer.read_lines(input)?;
+    importer.finish()
+}
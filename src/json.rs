@@ -0,0 +1,313 @@
+//! Export of the structure of a file as JSON and of series rows as NDJSON.
+//!
+//! `write_json` describes a whole file in one document: the header, the
+//! logs, attributes and events of every group and the descriptors of the
+//! series. The entries themselves are written by `write_ndjson`, one JSON
+//! object per line, so they can be streamed into tools such as `jq`.
+//!
+//! Numbers which are NaN or infinite have no JSON representation and are
+//! written as `null`.
+
+use std::fmt;
+use std::io;
+use lazy::{LazyReader, SeriesInfo};
+use header::{LogBlock, AttrBlock, EventBlock};
+use error::{Result, Error};
+use timestamp;
+use group;
+use Entry;
+
+/// A JSON value. Objects keep the order of their members.
+#[derive(Debug,Clone,PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Returns the member `key` of an object.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match *self {
+            Value::Object(ref members) => members.iter().find(|m| m.0 == key).map(|m| &m.1),
+            _ => None,
+        }
+    }
+
+    /// Writes the value on one line, or indented by two spaces per level
+    /// if `pretty` is true.
+    pub fn write_into<W: io::Write>(&self, out: &mut W, pretty: bool) -> Result<()> {
+        let mut s = String::new();
+        self.format(&mut s, if pretty { Some(0) } else { None });
+        out.write_all(s.as_bytes())?;
+        Ok(())
+    }
+
+    fn format(&self, s: &mut String, indent: Option<usize>) {
+        let newline = |s: &mut String, level: usize| {
+            s.push('\n');
+            for _ in 0..level {
+                s.push_str("  ");
+            }
+        };
+        match *self {
+            Value::Null => s.push_str("null"),
+            Value::Bool(b) => s.push_str(if b { "true" } else { "false" }),
+            Value::Int(n) => s.push_str(&n.to_string()),
+            Value::Number(x) => s.push_str(&format_number(x)),
+            Value::String(ref string) => quote(s, string),
+            Value::Array(ref items) if items.is_empty() => s.push_str("[]"),
+            Value::Object(ref members) if members.is_empty() => s.push_str("{}"),
+            Value::Array(ref items) => {
+                s.push('[');
+                for (n, item) in items.iter().enumerate() {
+                    if n > 0 {
+                        s.push(',');
+                    }
+                    if let Some(level) = indent {
+                        newline(s, level + 1);
+                    }
+                    item.format(s, indent.map(|l| l + 1));
+                }
+                if let Some(level) = indent {
+                    newline(s, level);
+                }
+                s.push(']');
+            },
+            Value::Object(ref members) => {
+                s.push('{');
+                for (n, (key, value)) in members.iter().enumerate() {
+                    if n > 0 {
+                        s.push(',');
+                    }
+                    if let Some(level) = indent {
+                        newline(s, level + 1);
+                    }
+                    quote(s, key);
+                    s.push(':');
+                    if indent.is_some() {
+                        s.push(' ');
+                    }
+                    value.format(s, indent.map(|l| l + 1));
+                }
+                if let Some(level) = indent {
+                    newline(s, level);
+                }
+                s.push('}');
+            },
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = String::new();
+        self.format(&mut s, None);
+        f.write_str(&s)
+    }
+}
+
+impl<'a> From<&'a str> for Value {
+    fn from(s: &'a str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(x: f64) -> Self {
+        Value::Number(x)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(n: u64) -> Self {
+        Value::Int(n as i64)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(v: Option<T>) -> Self {
+        v.map(|v| v.into()).unwrap_or(Value::Null)
+    }
+}
+
+fn format_number(x: f64) -> String {
+    if !x.is_finite() {
+        "null".to_string()
+    } else if x == 0.0 && x.is_sign_negative() {
+        "-0".to_string()
+    } else if x.fract() == 0.0 && x.abs() < 1e15 {
+        (x as i64).to_string()
+    } else {
+        // Debug formatting switches to an exponent for very large or small
+        // values, which JSON accepts.
+        format!("{:?}", x)
+    }
+}
+
+fn quote(s: &mut String, string: &str) {
+    s.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => s.push_str("\\\""),
+            '\\' => s.push_str("\\\\"),
+            '\n' => s.push_str("\\n"),
+            '\r' => s.push_str("\\r"),
+            '\t' => s.push_str("\\t"),
+            c if (c as u32) < 0x20 => s.push_str(&format!("\\u{:04x}", c as u32)),
+            c => s.push(c),
+        }
+    }
+    s.push('"');
+}
+
+fn object(members: Vec<(&str, Value)>) -> Value {
+    Value::Object(members.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+/// Returns a log as `{"time": RFC 3339, "program": ..., "info": ...}`.
+pub fn log_value(log: &LogBlock) -> Value {
+    object(vec![
+        ("time", timestamp::format_rfc3339(log.time()).into()),
+        ("program", log.program().into()),
+        ("info", log.info().into()),
+    ])
+}
+
+/// Returns attributes as an object. A key given more than once keeps its
+/// first position and its last value, as `Metadata::get_attr` does.
+pub fn attrs_value(attrs: &[AttrBlock]) -> Value {
+    let mut members: Vec<(String, Value)> = Vec::new();
+    for attr in attrs {
+        let value = Value::from(attr.value());
+        match members.iter().position(|m| m.0 == attr.key()) {
+            Some(pos) => { members[pos].1 = value; },
+            None => { members.push((attr.key().to_string(), value)); },
+        }
+    }
+    Value::Object(members)
+}
+
+/// Returns an event as `{"onset": ..., "duration": ..., "text": ...}`.
+pub fn event_value(event: &EventBlock) -> Value {
+    object(vec![
+        ("onset", event.onset().into()),
+        ("duration", event.duration().into()),
+        ("text", event.text().into()),
+    ])
+}
+
+/// Returns the descriptor of a series: its shape, names and the offset of
+/// its entries in the file.
+pub fn series_value(info: &SeriesInfo) -> Value {
+    let block = info.block();
+    let path = info.name().map(|name| group::join(info.group(), name));
+    object(vec![
+        ("id", info.id().into()),
+        ("name", info.name().into()),
+        ("group", info.group().into()),
+        ("path", path.into()),
+        ("index_len", block.index_len().into()),
+        ("value_len", block.value_len().into()),
+        ("length", block.length().into()),
+        ("channels", Value::Array(block.channel_names().iter().map(|c| c.as_str().into()).collect())),
+        ("offset", info.offset().into()),
+    ])
+}
+
+/// Describes the file read by `lazy`.
+pub fn describe<R: io::Read + io::Seek>(lazy: &LazyReader<R>) -> Value {
+    let header = lazy.header();
+    let groups = lazy.group_paths().into_iter().map(|path| {
+        let metadata = lazy.group(path).unwrap();
+        object(vec![
+            ("path", path.into()),
+            ("logs", Value::Array(metadata.get_logs().iter().map(log_value).collect())),
+            ("attrs", attrs_value(metadata.get_attrs())),
+            ("events", Value::Array(metadata.get_events().iter().map(event_value).collect())),
+        ])
+    }).collect();
+    object(vec![
+        ("header", object(vec![
+            ("major_version", (header.major_version() as u64).into()),
+            ("minor_version", (header.minor_version() as u64).into()),
+            ("header_size", header.header_size().into()),
+        ])),
+        ("groups", Value::Array(groups)),
+        ("series", Value::Array(lazy.series().iter().map(series_value).collect())),
+    ])
+}
+
+/// Writes the description of the file read by `lazy`, followed by a newline.
+pub fn write_json<R, W>(lazy: &LazyReader<R>, out: &mut W, pretty: bool) -> Result<()>
+    where R: io::Read + io::Seek, W: io::Write
+{
+    describe(lazy).write_into(out, pretty)?;
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+/// Returns an entry of the series `series` as a row object. The values are
+/// an object keyed by channel name if every channel is named, and an array
+/// otherwise.
+pub fn row_value(series: &str, channels: &[String], entry: &Entry) -> Value {
+    let values = if !channels.is_empty() && channels.len() == entry.1.len() {
+        Value::Object(channels.iter().zip(entry.1.iter()).map(|(c, &x)| (c.clone(), x.into())).collect())
+    } else {
+        Value::Array(entry.1.iter().map(|&x| x.into()).collect())
+    };
+    object(vec![("series", series.into()), ("index", entry.0.into()), ("values", values)])
+}
+
+/// Writes entries as NDJSON rows of the series `series`. Returns the
+/// number of rows written.
+pub fn write_ndjson<W, I>(out: &mut W, series: &str, channels: &[String], value_len: usize,
+                          entries: I) -> Result<u64>
+    where W: io::Write, I: Iterator<Item = Result<Entry>>
+{
+    let mut rows = 0;
+    for entry in entries {
+        let entry = entry?;
+        if entry.1.len() != value_len {
+            return Err(Error::Shape);
+        }
+        writeln!(out, "{}", row_value(series, channels, &entry))?;
+        rows += 1;
+    }
+    Ok(rows)
+}
+
+/// Writes the rows of every series read by `lazy` as NDJSON. Series are
+/// identified by their path, or `#N` if they have no name. Returns the
+/// number of rows written.
+pub fn export_ndjson<R, W>(lazy: &mut LazyReader<R>, out: &mut W) -> Result<u64>
+    where R: io::Read + io::Seek, W: io::Write
+{
+    let mut rows = 0;
+    for n in 0..lazy.series().len() {
+        let (series, channels, value_len) = series_key(lazy, n);
+        rows += write_ndjson(out, &series, &channels, value_len, lazy.entries(n)?)?;
+    }
+    Ok(rows)
+}
+
+/// Returns the key, the channel names and the value length of the `n`-th
+/// series, as used by `export_ndjson`.
+pub fn series_key<R: io::Read + io::Seek>(lazy: &LazyReader<R>, n: usize) -> (String, Vec<String>, usize) {
+    let info = &lazy.series()[n];
+    let series = match info.name() {
+        Some(name) => group::join(info.group(), name),
+        None => format!("#{}", n),
+    };
+    (series, info.block().channel_names().to_vec(), info.value_len() as usize)
+}
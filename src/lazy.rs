@@ -0,0 +1,230 @@
+use std::io;
+use std::collections::BTreeMap;
+use std::io::SeekFrom;
+use reader::{Reader, Block, F64TSReader};
+use header::{Header, F64TSBlock};
+use error::{Result, Error};
+use group;
+use {Metadata, Entry};
+
+/// The shape and the location of a f64ts block.
+#[derive(Debug,PartialEq,Clone)]
+pub struct SeriesInfo {
+    block  : F64TSBlock,
+    group  : String,
+    offset : u64,
+}
+
+impl SeriesInfo {
+    pub fn block(&self) -> &F64TSBlock {
+        &self.block
+    }
+
+    /// Position of the first entry in the stream.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn id(&self) -> u64 {
+        self.block.id()
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.block.name()
+    }
+
+    pub fn group(&self) -> &str {
+        &self.group
+    }
+
+    pub fn value_len(&self) -> u64 {
+        self.block.value_len()
+    }
+
+    pub fn length(&self) -> u64 {
+        self.block.length().unwrap_or(0)
+    }
+}
+
+/// A reader which scans the metadata and the shapes of series when it is
+/// created and reads the entries of a series only on demand.
+#[derive(Debug)]
+pub struct LazyReader<R: io::Read + io::Seek> {
+    reader   : Reader<R>,
+    metadata : Metadata,
+    groups   : BTreeMap<String, Metadata>,
+    series   : Vec<SeriesInfo>,
+}
+
+impl<R: io::Read + io::Seek> LazyReader<R> {
+    pub fn new(stream: R) -> Result<Self> {
+        Self::from_reader(Reader::new(stream))
+    }
+
+    /// Scans the file with `reader`, which has not read the header yet, so
+    /// that options such as `Reader::accept_segments` apply.
+    pub fn from_reader(mut reader: Reader<R>) -> Result<Self> {
+        reader.initialize()?;
+
+        let mut metadata = Metadata::new();
+        let mut groups: BTreeMap<String, Metadata> = BTreeMap::new();
+        let mut series = Vec::new();
+
+        loop {
+            let block = match reader.next_block() {
+                Ok(block) => block,
+                Err(Error::EndOfFile) => break,
+                Err(e) => { return Err(e); },
+            };
+            let group = reader.current_group().to_string();
+            let current = if group == group::ROOT {
+                &mut metadata
+            } else {
+                groups.entry(group.clone()).or_default()
+            };
+            match block {
+                Block::Log(log) => { current.get_logs_mut().push(log); },
+                Block::Attr(attr) => { current.get_attrs_mut().push(attr); },
+                Block::Event(event) => { current.get_events_mut().push(event); },
+                Block::Group(_) => {},
+                Block::F64TS(fts) => {
+                    let offset = reader.get_mut().stream_position()?;
+                    reader.skip_f64ts(&fts)?;
+                    series.push(SeriesInfo { block: fts, group, offset });
+                },
+            }
+        }
+
+        Ok(LazyReader { reader, metadata, groups, series })
+    }
+
+    pub fn header(&self) -> &Header {
+        self.reader.header().unwrap()
+    }
+
+    /// Returns the metadata of the root group.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Returns the metadata of the group at `path`.
+    pub fn group(&self, path: &str) -> Option<&Metadata> {
+        let path = group::normalize(path);
+        if path == group::ROOT {
+            Some(&self.metadata)
+        } else {
+            self.groups.get(&path)
+        }
+    }
+
+    /// Returns the paths of all groups including the root group.
+    pub fn group_paths(&self) -> Vec<&str> {
+        let mut paths = vec![group::ROOT];
+        paths.extend(self.groups.keys().map(|p| p.as_str()));
+        paths
+    }
+
+    pub fn series(&self) -> &[SeriesInfo] {
+        &self.series
+    }
+
+    /// Returns the position of the first series named `name`.
+    pub fn find_series(&self, name: &str) -> Option<usize> {
+        self.series.iter().position(|s| s.name() == Some(name))
+    }
+
+    /// Returns an iterator over the entries of the `n`-th series.
+    pub fn entries(&mut self, n: usize) -> Result<F64TSReader<'_, R>> {
+        let info = info(&self.series, n)?;
+        self.reader.get_mut().seek(SeekFrom::Start(info.offset))?;
+        Ok(self.reader.f64ts_entries(&info.block))
+    }
+
+    /// Returns an iterator over the entries of the `n`-th series starting
+    /// at the `row`-th entry.
+    pub fn entries_from(&mut self, n: usize, row: u64) -> Result<F64TSReader<'_, R>> {
+        let info = info(&self.series, n)?;
+        let row = row.min(info.length());
+        // The size of the entries was checked when the block was skipped.
+        let row_size = info.block.row_size()?;
+        let mut block = info.block.clone();
+        block.set_length(info.length() - row);
+        self.reader.get_mut().seek(SeekFrom::Start(info.offset + row * row_size))?;
+        Ok(self.reader.f64ts_entries(&block))
+    }
+
+    /// Returns the positions of the blocks which `load` joins with the
+    /// `n`-th into one series: those with its group and name, in the order
+    /// of the file. An unnamed block is not joined with any other, and there
+    /// are none if `n` is out of range.
+    pub fn chunks(&self, n: usize) -> Vec<usize> {
+        let info = match self.series.get(n) {
+            Some(info) => info,
+            None => { return Vec::new(); },
+        };
+        if info.name().is_none() {
+            return vec![n];
+        }
+        (0..self.series.len())
+            .filter(|&m| self.series[m].name() == info.name() && self.series[m].group == info.group)
+            .collect()
+    }
+
+    /// Returns an iterator over the entries of all the blocks joined with
+    /// the `n`-th (see `chunks`), such as the chunks written by a
+    /// `MuxWriter`. Fails with `Error::Shape` if their shapes differ.
+    pub fn joined_entries(&mut self, n: usize) -> Result<JoinedEntries<'_, R>> {
+        let first = info(&self.series, n)?;
+        let blocks = self.chunks(n);
+        let shape = |info: &SeriesInfo| (info.block.index_len(), info.value_len());
+        if blocks.iter().any(|&m| shape(&self.series[m]) != shape(first)) {
+            return Err(Error::Shape);
+        }
+        Ok(JoinedEntries { lazy: self, blocks, next: 0, remaining: 0 })
+    }
+
+    /// Reads all entries of the `n`-th series.
+    pub fn load_series(&mut self, n: usize) -> Result<Vec<Entry>> {
+        self.entries(n)?.collect()
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+}
+
+// Returns the `n`-th series, or an error if there are not so many.
+fn info(series: &[SeriesInfo], n: usize) -> Result<&SeriesInfo> {
+    series.get(n).ok_or_else(|| {
+        Error::Io(io::Error::new(io::ErrorKind::InvalidInput, format!("series #{} out of range ({} series)", n, series.len())))
+    })
+}
+
+/// An iterator over the entries of several blocks joined into one series.
+#[derive(Debug)]
+pub struct JoinedEntries<'a, R: 'a + io::Read + io::Seek> {
+    lazy      : &'a mut LazyReader<R>,
+    blocks    : Vec<usize>,
+    next      : usize,
+    remaining : u64,
+}
+
+impl<'a, R: io::Read + io::Seek> Iterator for JoinedEntries<'a, R> {
+    type Item = Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.remaining == 0 {
+            let n = *self.blocks.get(self.next)?;
+            let info = &self.lazy.series[n];
+            if let Err(e) = self.lazy.reader.get_mut().seek(SeekFrom::Start(info.offset)) {
+                return Some(Err(e.into()));
+            }
+            self.remaining = info.length();
+            self.next += 1;
+        }
+        self.remaining -= 1;
+        let lazy = &mut *self.lazy;
+        let block = &lazy.series[self.blocks[self.next - 1]].block;
+        lazy.reader.f64ts_entries(block).next()
+    }
+}
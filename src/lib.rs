@@ -1,26 +1,79 @@
 extern crate byteorder;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(feature = "derive")]
+extern crate broto_derive;
+#[cfg(feature = "background")]
+extern crate crossbeam_queue;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "tokio")]
+extern crate futures_core;
 
 pub mod header;
 pub mod writer;
 pub mod reader;
 pub mod error;
+pub mod file;
+pub mod lazy;
+pub mod dataset;
+pub mod group;
+pub mod timestamp;
+pub mod csv;
+pub mod npy;
+pub mod wav;
+pub mod edf;
+pub mod mat;
+pub mod influx;
+pub mod json;
+pub mod record;
+pub mod check;
+pub mod merge;
+pub mod slice;
+pub mod rolling;
+pub mod follow;
+#[cfg(feature = "background")]
+pub mod background;
+pub mod mux;
+#[cfg(feature = "tokio")]
+pub mod async_io;
 
 pub use self::writer::*;
 pub use self::reader::*;
 pub use self::header::*;
 pub use self::error::*;
+pub use self::file::*;
+pub use self::lazy::*;
+pub use self::dataset::*;
+#[cfg(feature = "derive")]
+pub use broto_derive::BrotoRecord;
 
 use std::io;
 
-#[derive(Debug,PartialEq)]
+/// An entry of a f64ts block: the index and the values.
+pub type Entry = (f64, Vec<f64>);
+
+#[derive(Debug,PartialEq,Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
 pub struct Metadata {
     logs: Vec<header::LogBlock>,
+    attrs: Vec<header::AttrBlock>,
+    events: Vec<header::EventBlock>,
+}
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Metadata {
     pub fn new() -> Self {
         Metadata {
             logs: Vec::new(),
+            attrs: Vec::new(),
+            events: Vec::new(),
         }
     }
 
@@ -31,14 +84,44 @@ impl Metadata {
     pub fn get_logs_mut(&mut self) -> &mut Vec<header::LogBlock> {
         &mut self.logs
     }
+
+    pub fn get_attrs(&self) -> &Vec<header::AttrBlock> {
+        &self.attrs
+    }
+
+    pub fn get_attrs_mut(&mut self) -> &mut Vec<header::AttrBlock> {
+        &mut self.attrs
+    }
+
+    pub fn get_events(&self) -> &Vec<header::EventBlock> {
+        &self.events
+    }
+
+    pub fn get_events_mut(&mut self) -> &mut Vec<header::EventBlock> {
+        &mut self.events
+    }
+
+    /// Returns the value of the last attribute with the key.
+    pub fn get_attr(&self, key: &str) -> Option<&str> {
+        self.attrs.iter().rev().find(|a| a.key() == key).map(|a| a.value())
+    }
+
+    /// Replaces the value of the attribute with the key, or adds one.
+    pub fn set_attr<K: Into<String>, V: Into<String>>(&mut self, key: K, value: V) {
+        let attr = header::AttrBlock::new(key, value);
+        match self.attrs.iter().position(|a| a.key() == attr.key()) {
+            Some(pos) => { self.attrs[pos] = attr; },
+            None => { self.attrs.push(attr); },
+        }
+    }
 }
 
-pub fn load_f64ts<R: io::Read>(stream: R) -> Result<(Vec<(f64,Vec<f64>)>, Metadata)> {
+pub fn load_f64ts<R: io::Read>(stream: R) -> Result<(Vec<Entry>, Metadata)> {
     let mut reader = Reader::new(stream);
-    let _ = reader.initialize().unwrap();
+    reader.initialize().unwrap();
 
     let mut read_data = Vec::new();
-    let mut metadata = Metadata { logs: Vec::new(), };
+    let mut metadata = Metadata::new();
 
     loop {
         let result = reader.next_block();
@@ -52,6 +135,9 @@ pub fn load_f64ts<R: io::Read>(stream: R) -> Result<(Vec<(f64,Vec<f64>)>, Metada
         let block = result.unwrap();
         match block {
             Block::Log(log) => { metadata.logs.push(log); },
+            Block::Attr(attr) => { metadata.attrs.push(attr); },
+            Block::Event(event) => { metadata.events.push(event); },
+            Block::Group(_) => {},
             Block::F64TS(fts) => {
                 for ent in reader.f64ts_entries(&fts) {
                     let ent = ent.unwrap();
@@ -64,7 +150,7 @@ pub fn load_f64ts<R: io::Read>(stream: R) -> Result<(Vec<(f64,Vec<f64>)>, Metada
     Ok((read_data, metadata))
 }
 
-pub fn save_f64ts<W: io::Write>(stream: W, entries: &[(f64,Vec<f64>)], metadata: &Metadata) -> Result<W> {
+pub fn save_f64ts<W: io::Write>(stream: W, entries: &[Entry], metadata: &Metadata) -> Result<W> {
     let mut writer = Writer::new(stream);
     writer.write_header()?;
 
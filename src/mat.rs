@@ -0,0 +1,490 @@
+//! MATLAB Level 5 MAT-files (uncompressed).
+//!
+//! Only double matrices, character arrays and cell arrays are written and
+//! read. `write_mat` stores each series as a double matrix with the index in
+//! the first column, and describes the file in cell arrays of strings:
+//!
+//! * `series`: `{variable, path, {channel names}}` for each series,
+//! * `attrs`: `{group, key, value}` for each attribute,
+//! * `logs`: `{group, time, program, info}` for each log, and
+//! * `events`: `{group, onset, duration, text}` for each event.
+//!
+//! `read_mat` reads these back into a dataset.
+
+use std::io::{self, Read};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use header::{LogBlockBuilder, AttrBlock, EventBlock};
+use dataset::{Dataset, TimeSeries};
+use error::{Result, Error};
+use group;
+use timestamp;
+
+const MI_INT8: u32 = 1;
+const MI_UINT8: u32 = 2;
+const MI_INT16: u32 = 3;
+const MI_UINT16: u32 = 4;
+const MI_INT32: u32 = 5;
+const MI_UINT32: u32 = 6;
+const MI_SINGLE: u32 = 7;
+const MI_DOUBLE: u32 = 9;
+const MI_INT64: u32 = 12;
+const MI_UINT64: u32 = 13;
+const MI_MATRIX: u32 = 14;
+const MI_COMPRESSED: u32 = 15;
+const MI_UTF8: u32 = 16;
+
+const MX_CELL_CLASS: u8 = 1;
+const MX_CHAR_CLASS: u8 = 4;
+const MX_DOUBLE_CLASS: u8 = 6;
+
+/// A MATLAB array of the supported classes. Elements are in column-major
+/// order.
+#[derive(Debug,Clone,PartialEq)]
+pub enum MatValue {
+    Double { rows: usize, cols: usize, data: Vec<f64> },
+    Char(String),
+    Cell { rows: usize, cols: usize, items: Vec<MatValue> },
+}
+
+impl MatValue {
+    pub fn scalar(x: f64) -> Self {
+        MatValue::Double { rows: 1, cols: 1, data: vec![x] }
+    }
+
+    /// A cell array with the given rows.
+    pub fn cell_rows(rows: Vec<Vec<MatValue>>) -> Self {
+        let cols = rows.first().map(|r| r.len()).unwrap_or(0);
+        assert!(rows.iter().all(|r| r.len() == cols));
+        let mut items = Vec::with_capacity(rows.len() * cols);
+        for c in 0..cols {
+            items.extend(rows.iter().map(|r| r[c].clone()));
+        }
+        MatValue::Cell { rows: rows.len(), cols, items }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            MatValue::Char(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_scalar(&self) -> Option<f64> {
+        match *self {
+            MatValue::Double { ref data, .. } if data.len() == 1 => Some(data[0]),
+            _ => None,
+        }
+    }
+
+    /// Returns the rows of a cell array.
+    pub fn cell_to_rows(&self) -> Option<Vec<Vec<&MatValue>>> {
+        match *self {
+            MatValue::Cell { rows, cols, ref items } => Some((0..rows).map(|r| {
+                (0..cols).map(|c| &items[c * rows + r]).collect()
+            }).collect()),
+            _ => None,
+        }
+    }
+
+    // Size of the miMATRIX element of this array without its tag.
+    fn size(&self, name: &str) -> u64 {
+        let body = match *self {
+            MatValue::Double { ref data, .. } => 8 + 8 * data.len() as u64,
+            MatValue::Char(ref s) => 8 + padded(2 * s.encode_utf16().count() as u64),
+            MatValue::Cell { ref items, .. } => items.iter().map(|item| 8 + item.size("")).sum(),
+        };
+        16 + 16 + 8 + padded(name.len() as u64) + body
+    }
+
+    fn write_into<W: io::Write>(&self, out: &mut W, name: &str) -> Result<()> {
+        let (class, rows, cols) = match *self {
+            MatValue::Double { rows, cols, .. } => (MX_DOUBLE_CLASS, rows, cols),
+            MatValue::Char(ref s) => (MX_CHAR_CLASS, 1, s.encode_utf16().count()),
+            MatValue::Cell { rows, cols, .. } => (MX_CELL_CLASS, rows, cols),
+        };
+        write_tag(out, MI_MATRIX, self.size(name))?;
+        write_tag(out, MI_UINT32, 8)?;
+        out.write_u32::<LittleEndian>(class as u32)?;
+        out.write_u32::<LittleEndian>(0)?;
+        write_tag(out, MI_INT32, 8)?;
+        out.write_i32::<LittleEndian>(rows as i32)?;
+        out.write_i32::<LittleEndian>(cols as i32)?;
+        write_tag(out, MI_INT8, name.len() as u64)?;
+        out.write_all(name.as_bytes())?;
+        write_padding(out, name.len() as u64)?;
+        match *self {
+            MatValue::Double { ref data, .. } => {
+                write_tag(out, MI_DOUBLE, 8 * data.len() as u64)?;
+                for &x in data.iter() {
+                    out.write_f64::<LittleEndian>(x)?;
+                }
+            },
+            MatValue::Char(ref s) => {
+                write_tag(out, MI_UINT16, 2 * cols as u64)?;
+                for unit in s.encode_utf16() {
+                    out.write_u16::<LittleEndian>(unit)?;
+                }
+                write_padding(out, 2 * cols as u64)?;
+            },
+            MatValue::Cell { ref items, .. } => {
+                for item in items.iter() {
+                    item.write_into(out, "")?;
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+fn padded(len: u64) -> u64 {
+    len.div_ceil(8) * 8
+}
+
+fn write_tag<W: io::Write>(out: &mut W, data_type: u32, len: u64) -> Result<()> {
+    if len > u32::MAX as u64 {
+        return Err(Error::Parse("MAT-file elements over 4 GiB are not supported".to_string()));
+    }
+    out.write_u32::<LittleEndian>(data_type)?;
+    out.write_u32::<LittleEndian>(len as u32)?;
+    Ok(())
+}
+
+fn write_padding<W: io::Write>(out: &mut W, len: u64) -> Result<()> {
+    out.write_all(&[0; 8][..(padded(len) - len) as usize])?;
+    Ok(())
+}
+
+/// Writes the variables of a MAT-file.
+#[derive(Debug)]
+pub struct MatWriter<W: io::Write> {
+    out : W,
+}
+
+impl<W: io::Write> MatWriter<W> {
+    /// Writes the 128-byte file header.
+    pub fn new(mut out: W) -> Result<Self> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let text = format!("MATLAB 5.0 MAT-file, Platform: broto, Created on: {}",
+                           timestamp::format_rfc3339(Duration::from_secs(now.as_secs())));
+        let mut header = text.into_bytes();
+        header.resize(116, b' ');
+        out.write_all(&header)?;
+        out.write_all(&[0; 8])?;
+        out.write_u16::<LittleEndian>(0x0100)?;
+        out.write_all(b"IM")?;
+        Ok(MatWriter { out })
+    }
+
+    pub fn write_var(&mut self, name: &str, value: &MatValue) -> Result<()> {
+        value.write_into(&mut self.out, name)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+// Reads the tag of a data element, which may be in the small format with
+// the data in the last 4 bytes. Returns the type, the length and the
+// number of bytes occupied by the element after the tag.
+fn read_tag<R: io::Read>(input: &mut R) -> Result<(u32, u64, u64)> {
+    let first = input.read_u32::<LittleEndian>()?;
+    if first >> 16 != 0 {
+        return Ok((first & 0xffff, (first >> 16) as u64, 4));
+    }
+    let len = input.read_u32::<LittleEndian>()? as u64;
+    Ok((first, len, padded(len)))
+}
+
+// Reads the `occupied` bytes of an element and keeps the first `len`. Only
+// the bytes which exist are allocated, whatever the tag says.
+fn read_data<R: io::Read>(input: &mut R, len: u64, occupied: u64) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    input.by_ref().take(occupied).read_to_end(&mut data)?;
+    if (data.len() as u64) < occupied {
+        return Err(Error::EndOfFile);
+    }
+    data.truncate(len as usize);
+    Ok(data)
+}
+
+fn read_element<R: io::Read>(input: &mut R) -> Result<(u32, Vec<u8>)> {
+    let (data_type, len, occupied) = read_tag(input)?;
+    Ok((data_type, read_data(input, len, occupied)?))
+}
+
+fn to_numbers(data_type: u32, data: &[u8]) -> Result<Vec<f64>> {
+    let mut data = data;
+    let width = match data_type {
+        MI_INT8 | MI_UINT8 | MI_UTF8 => 1,
+        MI_INT16 | MI_UINT16 => 2,
+        MI_INT32 | MI_UINT32 | MI_SINGLE => 4,
+        MI_DOUBLE | MI_INT64 | MI_UINT64 => 8,
+        _ => { return Err(Error::Parse(format!("unsupported MAT data type {}", data_type))); },
+    };
+    let mut numbers = Vec::with_capacity(data.len() / width);
+    for _ in 0..data.len() / width {
+        numbers.push(match data_type {
+            MI_INT8 => data.read_i8()? as f64,
+            MI_UINT8 | MI_UTF8 => data.read_u8()? as f64,
+            MI_INT16 => data.read_i16::<LittleEndian>()? as f64,
+            MI_UINT16 => data.read_u16::<LittleEndian>()? as f64,
+            MI_INT32 => data.read_i32::<LittleEndian>()? as f64,
+            MI_UINT32 => data.read_u32::<LittleEndian>()? as f64,
+            MI_SINGLE => data.read_f32::<LittleEndian>()? as f64,
+            MI_INT64 => data.read_i64::<LittleEndian>()? as f64,
+            MI_UINT64 => data.read_u64::<LittleEndian>()? as f64,
+            _ => data.read_f64::<LittleEndian>()?,
+        });
+    }
+    Ok(numbers)
+}
+
+// Reads the contents of an miMATRIX element. Returns the name and the
+// array, or `None` for arrays of unsupported classes.
+fn read_matrix(mut body: &[u8]) -> Result<(String, Option<MatValue>)> {
+    let (_, flags) = read_element(&mut body)?;
+    if flags.len() < 8 {
+        return Err(Error::Parse("invalid MAT array flags".to_string()));
+    }
+    let class = flags[0];
+    let complex = flags[1] & 0x08 != 0;
+    let (_, dims) = read_element(&mut body)?;
+    let dims = to_numbers(MI_INT32, &dims)?;
+    let (_, name) = read_element(&mut body)?;
+    let name = String::from_utf8(name).map_err(Error::FromUtf8)?;
+    if dims.len() != 2 || complex {
+        return Ok((name, None));
+    }
+    let (rows, cols) = (dims[0] as usize, dims[1] as usize);
+    let count = rows.checked_mul(cols)
+        .ok_or_else(|| Error::Parse(format!("MAT array of {} by {} elements is too large", rows, cols)))?;
+    let value = match class {
+        MX_DOUBLE_CLASS => {
+            let (data_type, data) = read_element(&mut body)?;
+            let data = to_numbers(data_type, &data)?;
+            if data.len() != count {
+                return Err(Error::Shape);
+            }
+            Some(MatValue::Double { rows, cols, data })
+        },
+        MX_CHAR_CLASS => {
+            let (data_type, data) = read_element(&mut body)?;
+            let units: Vec<u16> = to_numbers(data_type, &data)?.into_iter().map(|x| x as u16).collect();
+            let text = if data_type == MI_UTF8 {
+                String::from_utf8(data).map_err(Error::FromUtf8)?
+            } else if units.len() != count {
+                return Err(Error::Shape);
+            } else {
+                // Rows of a character matrix are joined by newlines.
+                let rows_text: Vec<String> = (0..rows).map(|r| {
+                    let row: Vec<u16> = (0..cols).filter_map(|c| units.get(c * rows + r).cloned()).collect();
+                    String::from_utf16_lossy(&row)
+                }).collect();
+                rows_text.join("\n")
+            };
+            Some(MatValue::Char(text))
+        },
+        MX_CELL_CLASS => {
+            // Each item takes at least the 8 bytes of its tag.
+            if count > body.len() / 8 {
+                return Err(Error::Shape);
+            }
+            let mut items = Vec::new();
+            for _ in 0..count {
+                let (data_type, data) = read_element(&mut body)?;
+                if data_type != MI_MATRIX {
+                    return Err(Error::Parse("invalid MAT cell element".to_string()));
+                }
+                match read_matrix(&data)?.1 {
+                    Some(item) => items.push(item),
+                    None => { return Ok((name, None)); },
+                }
+            }
+            Some(MatValue::Cell { rows, cols, items })
+        },
+        _ => None,
+    };
+    Ok((name, value))
+}
+
+/// Reads the variables of a MAT-file. Variables of unsupported classes are
+/// skipped, and compressed variables are an error.
+pub fn read_vars<R: io::Read>(input: &mut R) -> Result<Vec<(String, MatValue)>> {
+    let mut header = [0; 128];
+    input.read_exact(&mut header)?;
+    if &header[126..] != b"IM" {
+        if &header[126..] == b"MI" {
+            return Err(Error::Parse("big-endian MAT-files are not supported".to_string()));
+        }
+        return Err(Error::Magic);
+    }
+
+    let mut vars = Vec::new();
+    loop {
+        let (data_type, len, occupied) = match read_tag(input) {
+            Ok(tag) => tag,
+            Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => { return Err(e); },
+        };
+        if data_type == MI_COMPRESSED {
+            return Err(Error::Parse("compressed MAT-files are not supported".to_string()));
+        }
+        let data = read_data(input, len, occupied)?;
+        if data_type == MI_MATRIX {
+            if let (name, Some(value)) = read_matrix(&data)? {
+                vars.push((name, value));
+            }
+        }
+    }
+    Ok(vars)
+}
+
+// Makes a valid and unused MATLAB variable name from the path of a series.
+fn variable_name(path: &str, used: &[String]) -> String {
+    let mut base: String = path.trim_start_matches('/').chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !base.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        base.insert_str(0, "s_");
+    }
+    base.truncate(56);
+    let mut name = base.clone();
+    let mut k = 1;
+    while used.contains(&name) {
+        k += 1;
+        name = format!("{}_{}", base, k);
+    }
+    name
+}
+
+fn string(s: &str) -> MatValue {
+    MatValue::Char(s.to_string())
+}
+
+/// Writes a dataset as a MAT-file.
+pub fn write_mat<W: io::Write>(out: W, dataset: &Dataset) -> Result<W> {
+    let mut writer = MatWriter::new(out)?;
+    let mut used: Vec<String> = ["series", "attrs", "logs", "events"].iter().map(|s| s.to_string()).collect();
+    let mut series_rows = Vec::new();
+    for series in dataset.series_list() {
+        let name = variable_name(&series.path(), &used);
+        let rows = series.len();
+        let cols = 1 + series.value_len() as usize;
+        let mut data = vec![0.0; rows * cols];
+        for (r, &(index, ref values)) in series.entries().iter().enumerate() {
+            data[r] = index;
+            for (c, &x) in values.iter().enumerate() {
+                data[(c + 1) * rows + r] = x;
+            }
+        }
+        writer.write_var(&name, &MatValue::Double { rows, cols, data })?;
+        let channels = series.channel_names().iter().map(|s| string(s)).collect();
+        series_rows.push(vec![string(&name), string(&series.path()),
+                              MatValue::cell_rows(vec![channels])]);
+        used.push(name);
+    }
+
+    let (mut attrs, mut logs, mut events) = (Vec::new(), Vec::new(), Vec::new());
+    for path in dataset.group_paths() {
+        let metadata = dataset.group(path).unwrap();
+        for attr in metadata.get_attrs() {
+            attrs.push(vec![string(path), string(attr.key()), string(attr.value())]);
+        }
+        for log in metadata.get_logs() {
+            logs.push(vec![string(path), MatValue::Char(timestamp::format_rfc3339(log.time())),
+                           MatValue::Char(log.program()), MatValue::Char(log.info())]);
+        }
+        for event in metadata.get_events() {
+            events.push(vec![string(path), MatValue::scalar(event.onset()),
+                             MatValue::scalar(event.duration().unwrap_or(f64::NAN)),
+                             string(event.text())]);
+        }
+    }
+    for (name, rows) in [("series", series_rows), ("attrs", attrs), ("logs", logs), ("events", events)] {
+        if !rows.is_empty() {
+            writer.write_var(name, &MatValue::cell_rows(rows))?;
+        }
+    }
+    Ok(writer.into_inner())
+}
+
+fn invalid(var: &str) -> Error {
+    Error::Parse(format!("invalid MAT variable {}", var))
+}
+
+/// Reads a MAT-file written by `write_mat`. In other files, each double
+/// matrix with at least one column becomes a series named after its
+/// variable.
+pub fn read_mat<R: io::Read>(input: &mut R) -> Result<Dataset> {
+    let vars = read_vars(input)?;
+    let table = |name: &str| vars.iter().find(|v| v.0 == name).and_then(|v| v.1.cell_to_rows());
+    let text = |value: &MatValue, var: &str| value.as_str().map(|s| s.to_string()).ok_or_else(|| invalid(var));
+
+    let mut dataset = Dataset::new();
+    let described = table("series");
+    if described.iter().flatten().any(|row| row.len() != 3) {
+        return Err(invalid("series"));
+    }
+    for (var, value) in vars.iter() {
+        let (rows, cols, data) = match *value {
+            MatValue::Double { rows, cols, ref data } if cols >= 1 => (rows, cols, data),
+            _ => continue,
+        };
+        let row = described.as_ref().and_then(|t| t.iter().find(|r| r[0].as_str() == Some(var)).cloned());
+        let (path, channels) = match row {
+            Some(ref row) => {
+                let channels = match row[2].cell_to_rows() {
+                    Some(ref rows) if !rows.is_empty() => {
+                        rows[0].iter().map(|c| text(c, "series")).collect::<Result<Vec<_>>>()?
+                    },
+                    _ => Vec::new(),
+                };
+                (group::normalize(&text(row[1], "series")?), channels)
+            },
+            None if described.is_some() => continue,
+            None => (group::join(group::ROOT, var), Vec::new()),
+        };
+        let mut series = TimeSeries::new(group::base_name(&path), cols as u64 - 1)
+            .with_group(&group::parent(&path).unwrap_or_else(|| group::ROOT.to_string()));
+        if channels.len() == cols - 1 && !channels.is_empty() {
+            series = series.with_channel_names(channels);
+        }
+        for r in 0..rows {
+            series.push(data[r], (1..cols).map(|c| data[c * rows + r]).collect());
+        }
+        dataset.add_series(series);
+    }
+
+    for row in table("attrs").unwrap_or_default() {
+        if row.len() != 3 {
+            return Err(invalid("attrs"));
+        }
+        let attr = AttrBlock::new(text(row[1], "attrs")?, text(row[2], "attrs")?);
+        dataset.group_mut(&text(row[0], "attrs")?).get_attrs_mut().push(attr);
+    }
+    for row in table("logs").unwrap_or_default() {
+        if row.len() != 4 {
+            return Err(invalid("logs"));
+        }
+        let time = timestamp::parse_rfc3339(&text(row[1], "logs")?).ok_or_else(|| invalid("logs"))?;
+        let log = LogBlockBuilder::new()
+            .time(time)
+            .program(text(row[2], "logs")?)
+            .info(text(row[3], "logs")?)
+            .build();
+        dataset.group_mut(&text(row[0], "logs")?).get_logs_mut().push(log);
+    }
+    for row in table("events").unwrap_or_default() {
+        if row.len() != 4 {
+            return Err(invalid("events"));
+        }
+        let onset = row[1].as_scalar().ok_or_else(|| invalid("events"))?;
+        let duration = row[2].as_scalar().ok_or_else(|| invalid("events"))?;
+        let duration = if duration.is_nan() { None } else { Some(duration) };
+        let event = EventBlock::new(onset, duration, text(row[3], "events")?);
+        dataset.group_mut(&text(row[0], "events")?).get_events_mut().push(event);
+    }
+    Ok(dataset)
+}
@@ -0,0 +1,288 @@
+//! Merging and concatenation of files.
+//!
+//! Series of the inputs with the same group, name and channel layout are
+//! combined into one series ordered by index. The entries are streamed by
+//! a k-way merge which holds only a chunk of rows of each source in memory;
+//! the entries of every source must be in nondecreasing order of index.
+//! `concat` instead appends the entries of each input in turn. Series must
+//! have a single index column; others fail with `Error::Shape`.
+//!
+//! The logs and events of each group are carried over in input order
+//! without exact duplicates, and the attributes are combined with later
+//! inputs taking precedence.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap, VecDeque};
+use std::io;
+use std::str::FromStr;
+use lazy::LazyReader;
+use writer::{Writer, F64TSWriter};
+use header::{F64TSBlockBuilder, LogBlock};
+use error::{Result, Error};
+use group;
+use {Metadata, Entry};
+
+const CHUNK_ROWS: usize = 4096;
+
+/// What to do with entries of a series which have the same index.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Duplicates {
+    /// Keep the entry of the earliest input.
+    First,
+    /// Keep the entry of the latest input.
+    Last,
+    /// Fail with `Error::Parse`.
+    Error,
+}
+
+impl FromStr for Duplicates {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "first" => Ok(Duplicates::First),
+            "last" => Ok(Duplicates::Last),
+            "error" => Ok(Duplicates::Error),
+            _ => Err(Error::Parse(format!("unknown duplicate policy: {}", s))),
+        }
+    }
+}
+
+// The key of a merged series: the group, the name and the channel layout.
+#[derive(Debug,Clone,PartialEq)]
+struct Key {
+    group     : String,
+    name      : Option<String>,
+    value_len : u64,
+    channels  : Vec<String>,
+}
+
+impl Key {
+    fn describe(&self) -> String {
+        match self.name {
+            Some(ref name) => group::join(&self.group, name),
+            None => format!("unnamed series in {}", self.group),
+        }
+    }
+}
+
+// A series of an input, read a chunk at a time.
+struct Source {
+    input   : usize,
+    series  : usize,
+    row     : u64,
+    length  : u64,
+    buffer  : VecDeque<Entry>,
+    last    : Option<f64>,
+}
+
+impl Source {
+    fn next<R>(&mut self, inputs: &mut [LazyReader<R>]) -> Result<Option<Entry>>
+        where R: io::Read + io::Seek
+    {
+        if self.buffer.is_empty() && self.row < self.length {
+            let entries = inputs[self.input].entries_from(self.series, self.row)?;
+            for entry in entries.take(CHUNK_ROWS) {
+                self.buffer.push_back(entry?);
+            }
+            self.row += self.buffer.len() as u64;
+        }
+        Ok(self.buffer.pop_front())
+    }
+
+    // Like `next`, but fails unless the indices are in order.
+    fn next_in_order<R>(&mut self, inputs: &mut [LazyReader<R>], key: &Key) -> Result<Option<Entry>>
+        where R: io::Read + io::Seek
+    {
+        let entry = match self.next(inputs)? {
+            Some(entry) => entry,
+            None => { return Ok(None); },
+        };
+        if entry.0.is_nan() || self.last.is_some_and(|last| entry.0 < last) {
+            return Err(Error::Parse(format!("the index of {} in input {} is not in order",
+                                            key.describe(), self.input + 1)));
+        }
+        self.last = Some(entry.0);
+        Ok(Some(entry))
+    }
+}
+
+// The next entry of a source, ordered by index and then by source so that
+// `BinaryHeap` pops the smallest first.
+struct Head {
+    entry  : Entry,
+    source : usize,
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Indices are never NaN here.
+        other.entry.0.partial_cmp(&self.entry.0).unwrap()
+            .then(other.source.cmp(&self.source))
+    }
+}
+
+fn add_unique<T: PartialEq + Clone>(to: &mut Vec<T>, items: &[T]) {
+    for item in items {
+        if !to.contains(item) {
+            to.push(item.clone());
+        }
+    }
+}
+
+/// Merges the files read by `inputs` into `writer`, whose header must
+/// have been written. `log`, if any, is added to the logs of the root
+/// group to record the merge.
+pub fn merge<R, W>(inputs: &mut [LazyReader<R>], writer: Writer<W>, duplicates: Duplicates,
+                   log: Option<&LogBlock>) -> Result<Writer<W>>
+    where R: io::Read + io::Seek, W: io::Write + io::Seek
+{
+    combine(inputs, writer, log, |inputs, w, key, sources| merge_entries(inputs, w, key, sources, duplicates))
+}
+
+/// Concatenates the files read by `inputs` into `writer` like `merge`, but
+/// appends the entries of each series in the order of the inputs instead
+/// of interleaving them.
+pub fn concat<R, W>(inputs: &mut [LazyReader<R>], writer: Writer<W>, log: Option<&LogBlock>) -> Result<Writer<W>>
+    where R: io::Read + io::Seek, W: io::Write + io::Seek
+{
+    combine(inputs, writer, log, |inputs, w, _, sources| {
+        for source in sources.iter_mut() {
+            while let Some((index, values)) = source.next(inputs)? {
+                w.write_entry(index, &values)?;
+            }
+        }
+        Ok(())
+    })
+}
+
+// Writes the combined metadata and, for each merged series, the entries
+// written by `write_entries`.
+fn combine<R, W, F>(inputs: &mut [LazyReader<R>], mut writer: Writer<W>, log: Option<&LogBlock>,
+                    mut write_entries: F) -> Result<Writer<W>>
+    where R: io::Read + io::Seek, W: io::Write + io::Seek,
+          F: FnMut(&mut [LazyReader<R>], &mut F64TSWriter<W>, &Key, &mut [Source]) -> Result<()>
+{
+    let mut groups: BTreeMap<String, Metadata> = BTreeMap::new();
+    groups.insert(group::ROOT.to_string(), Metadata::new());
+    let mut keys: Vec<(Key, u64, Vec<Source>)> = Vec::new();
+    for (n, input) in inputs.iter().enumerate() {
+        for path in input.group_paths() {
+            let from = input.group(path).unwrap();
+            let to = groups.entry(path.to_string()).or_default();
+            add_unique(to.get_logs_mut(), from.get_logs());
+            add_unique(to.get_events_mut(), from.get_events());
+            for attr in from.get_attrs() {
+                to.set_attr(attr.key(), attr.value());
+            }
+        }
+        for (s, info) in input.series().iter().enumerate() {
+            // Entries carry only the first index column.
+            if info.block().index_len() != 1 {
+                return Err(Error::Shape);
+            }
+            let key = Key {
+                group     : info.group().to_string(),
+                name      : info.name().map(|s| s.to_string()),
+                value_len : info.value_len(),
+                channels  : info.block().channel_names().to_vec(),
+            };
+            let source = Source {
+                input  : n,
+                series : s,
+                row    : 0,
+                length : info.length(),
+                buffer : VecDeque::new(),
+                last   : None,
+            };
+            match keys.iter().position(|k| k.0 == key) {
+                Some(pos) => { keys[pos].2.push(source); },
+                None => { keys.push((key, info.id(), vec![source])); },
+            }
+        }
+    }
+    if let Some(log) = log {
+        groups.get_mut(group::ROOT).unwrap().get_logs_mut().push(log.clone());
+    }
+
+    for (path, metadata) in groups.iter_mut() {
+        if path != group::ROOT {
+            writer.write_group(path)?;
+        }
+        for log in metadata.get_logs() {
+            writer.write_log(log)?;
+        }
+        for attr in metadata.get_attrs() {
+            writer.write_attr(attr)?;
+        }
+        metadata.get_events_mut().sort_by(|a, b| a.onset().partial_cmp(&b.onset()).unwrap_or(Ordering::Equal));
+        for event in metadata.get_events() {
+            writer.write_event(event)?;
+        }
+        for (key, id, sources) in keys.iter_mut().filter(|k| &k.0.group == path) {
+            let mut builder = F64TSBlockBuilder::new()
+                .index_len(1)
+                .value_len(key.value_len)
+                .id(*id)
+                .channel_names(key.channels.clone());
+            if let Some(ref name) = key.name {
+                builder = builder.name(name.as_str());
+            }
+            let mut w = writer.write_f64ts_with_seek(builder.build())?;
+            write_entries(inputs, &mut w, key, sources)?;
+            writer = w.finalize()?.finish();
+        }
+    }
+    Ok(writer)
+}
+
+fn merge_entries<R, W>(inputs: &mut [LazyReader<R>], w: &mut F64TSWriter<W>, key: &Key,
+                       sources: &mut [Source], duplicates: Duplicates) -> Result<()>
+    where R: io::Read + io::Seek, W: io::Write + io::Seek
+{
+    let mut heap = BinaryHeap::with_capacity(sources.len());
+    for (n, source) in sources.iter_mut().enumerate() {
+        if let Some(entry) = source.next_in_order(inputs, key)? {
+            heap.push(Head { entry, source: n });
+        }
+    }
+    while let Some(head) = heap.pop() {
+        let index = head.entry.0;
+        let mut kept = head.entry;
+        let mut source = head.source;
+        loop {
+            if let Some(entry) = sources[source].next_in_order(inputs, key)? {
+                heap.push(Head { entry, source });
+            }
+            match heap.peek() {
+                Some(next) if next.entry.0 == index => {},
+                _ => break,
+            }
+            let next = heap.pop().unwrap();
+            match duplicates {
+                Duplicates::First => {},
+                Duplicates::Last => { kept = next.entry; },
+                Duplicates::Error => {
+                    return Err(Error::Parse(format!("duplicate index {} in {}", index, key.describe())));
+                },
+            }
+            source = next.source;
+        }
+        w.write_entry(kept.0, &kept.1)?;
+    }
+    Ok(())
+}
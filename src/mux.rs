@@ -0,0 +1,208 @@
+//! Writing several series into one file at the same time.
+//!
+//! A `MuxWriter` hands out a `SeriesHandle` per series. Each handle buffers
+//! its entries and writes them as a chunk: a f64ts block with the name, the
+//! identifier and the channels of the series and the length of the chunk,
+//! preceded by a group block when the group changes. Chunks of different
+//! series are interleaved in the order they are written.
+//!
+//! Since the chunks are ordinary named blocks, `load` joins them into one
+//! series, and `LazyReader::joined_entries` iterates over the entries of
+//! all the chunks of a series.
+
+use std::io;
+use std::sync::{Arc, Mutex};
+use writer::Writer;
+use header::F64TSBlock;
+use error::{Result, Error};
+use group;
+use Entry;
+
+const CHUNK_ROWS: usize = 1024;
+
+#[derive(Debug)]
+struct Shared<W: io::Write> {
+    // `None` after a chunk failed halfway.
+    writer : Option<Writer<W>>,
+    group  : String,
+    series : Vec<(String, String)>,
+    // The first error of a chunk written when a handle was dropped.
+    error  : Option<Error>,
+}
+
+impl<W: io::Write> Shared<W> {
+    fn write_chunk(&mut self, group: &str, block: &F64TSBlock, entries: &[Entry]) -> Result<()> {
+        let mut writer = match self.writer.take() {
+            Some(writer) => writer,
+            None => { return Err(Error::Io(io::Error::other("an earlier chunk could not be written"))); },
+        };
+        if self.group != group {
+            writer.write_group(group)?;
+            self.group = group.to_string();
+        }
+        let mut block = block.clone();
+        block.set_length(entries.len() as u64);
+        let mut w = writer.write_f64ts(block)?;
+        for &(index, ref values) in entries {
+            w.write_entry(index, values)?;
+        }
+        self.writer = Some(w.finish());
+        Ok(())
+    }
+}
+
+/// Writes series which are open at the same time into one stream.
+///
+/// Clones share the stream, so a `MuxWriter` can be passed to the threads
+/// producing the series.
+#[derive(Debug)]
+pub struct MuxWriter<W: io::Write> {
+    shared     : Arc<Mutex<Shared<W>>>,
+    chunk_rows : usize,
+}
+
+impl<W: io::Write> Clone for MuxWriter<W> {
+    fn clone(&self) -> Self {
+        MuxWriter { shared: self.shared.clone(), chunk_rows: self.chunk_rows }
+    }
+}
+
+impl<W: io::Write> MuxWriter<W> {
+    /// Creates a multiplexer writing into `writer`, whose header must have
+    /// been written. The chunks belong to the root group unless their
+    /// series is in another.
+    pub fn new(writer: Writer<W>) -> Self {
+        MuxWriter {
+            shared: Arc::new(Mutex::new(Shared {
+                writer : Some(writer),
+                group  : group::ROOT.to_string(),
+                series : Vec::new(),
+                error  : None,
+            })),
+            chunk_rows : CHUNK_ROWS,
+        }
+    }
+
+    /// Sets the number of entries a handle buffers before writing a chunk.
+    pub fn chunk_rows(mut self, rows: usize) -> Self {
+        assert!(rows > 0);
+        self.chunk_rows = rows;
+        self
+    }
+
+    /// Opens the series described by `block` in the group at `path`. The
+    /// series must be named, and no other open series in the group may have
+    /// the same name.
+    pub fn add_series(&self, path: &str, block: F64TSBlock) -> Result<SeriesHandle<W>> {
+        let path = group::normalize(path);
+        let name = match block.name() {
+            Some(name) => name.to_string(),
+            None => {
+                return Err(Error::Io(io::Error::new(io::ErrorKind::InvalidInput,
+                                                    "a multiplexed series must be named")));
+            },
+        };
+        {
+            let mut shared = self.shared.lock().unwrap();
+            if shared.series.contains(&(path.clone(), name.clone())) {
+                return Err(Error::Io(io::Error::new(io::ErrorKind::AlreadyExists,
+                    format!("series {} is already open", group::join(&path, &name)))));
+            }
+            shared.series.push((path.clone(), name));
+        }
+        Ok(SeriesHandle {
+            shared     : self.shared.clone(),
+            group      : path,
+            value_len  : block.value_len() as usize,
+            block,
+            entries    : Vec::new(),
+            chunk_rows : self.chunk_rows,
+            chunks     : 0,
+        })
+    }
+
+    /// Returns the writer once every clone and every handle is dropped, or
+    /// the first error of a chunk written by a dropped handle. Fails if a
+    /// clone or a handle is still alive.
+    pub fn finish(self) -> Result<Writer<W>> {
+        let shared = match Arc::try_unwrap(self.shared) {
+            Ok(shared) => shared.into_inner().unwrap(),
+            Err(_) => {
+                return Err(Error::Io(io::Error::other("a handle or a clone of the MuxWriter is still alive")));
+            },
+        };
+        if let Some(err) = shared.error {
+            return Err(err);
+        }
+        shared.writer.ok_or_else(|| Error::Io(io::Error::other("a chunk could not be written")))
+    }
+}
+
+/// Writes the entries of one series of a `MuxWriter`.
+///
+/// The buffered entries are written when the handle is dropped; use
+/// `close` to see the error, if any.
+#[derive(Debug)]
+pub struct SeriesHandle<W: io::Write> {
+    shared     : Arc<Mutex<Shared<W>>>,
+    group      : String,
+    block      : F64TSBlock,
+    value_len  : usize,
+    entries    : Vec<Entry>,
+    chunk_rows : usize,
+    chunks     : u64,
+}
+
+impl<W: io::Write> SeriesHandle<W> {
+    pub fn write_entry(&mut self, index: f64, values: &[f64]) -> Result<()> {
+        if values.len() != self.value_len {
+            return Err(Error::Shape);
+        }
+        self.entries.push((index, values.to_vec()));
+        if self.entries.len() >= self.chunk_rows {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the buffered entries as a chunk.
+    pub fn flush(&mut self) -> Result<()> {
+        if self.entries.is_empty() {
+            return Ok(());
+        }
+        self.write_chunk()
+    }
+
+    /// Writes the buffered entries and closes the series. A series without
+    /// entries is written as an empty chunk.
+    pub fn close(mut self) -> Result<()> {
+        self.finish()
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.entries.is_empty() && self.chunks > 0 {
+            return Ok(());
+        }
+        self.write_chunk()
+    }
+
+    fn write_chunk(&mut self) -> Result<()> {
+        let mut shared = self.shared.lock().unwrap();
+        let result = shared.write_chunk(&self.group, &self.block, &self.entries);
+        self.entries.clear();
+        self.chunks += 1;
+        result
+    }
+}
+
+impl<W: io::Write> Drop for SeriesHandle<W> {
+    fn drop(&mut self) {
+        let result = self.finish();
+        let mut shared = self.shared.lock().unwrap();
+        if let (Err(err), true) = (result, shared.error.is_none()) {
+            shared.error = Some(err);
+        }
+        let name = self.block.name().unwrap();
+        shared.series.retain(|s| s.0 != self.group || s.1 != name);
+    }
+}
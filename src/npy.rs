@@ -0,0 +1,519 @@
+//! NumPy `.npy` files of `f8` arrays and uncompressed `.npz` archives.
+//!
+//! A series is stored as an `index` array of shape `(n,)` and a `values`
+//! array of shape `(n, value_len)`. In a `.npz` archive the arrays of a
+//! series are named `PATH/index` and `PATH/values`, where `PATH` is the
+//! path of the series without the leading `/`. A single `.npy` file holds a
+//! series as one `(n, 1 + value_len)` array whose first column is the index.
+
+use std::io;
+use std::io::{Read, SeekFrom};
+use byteorder::{LittleEndian, BigEndian, ReadBytesExt, WriteBytesExt, ByteOrder};
+use dataset::{Dataset, TimeSeries};
+use lazy::LazyReader;
+use error::{Result, Error};
+use group;
+use Entry;
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+/// A row-major array of `f64`.
+#[derive(Debug,Clone,PartialEq)]
+pub struct Array {
+    shape : Vec<usize>,
+    data  : Vec<f64>,
+}
+
+impl Array {
+    pub fn new(shape: Vec<usize>, data: Vec<f64>) -> Self {
+        assert_eq!(shape.iter().product::<usize>(), data.len());
+        Array { shape, data }
+    }
+
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    pub fn data(&self) -> &[f64] {
+        &self.data
+    }
+
+    pub fn rows(&self) -> usize {
+        self.shape.first().cloned().unwrap_or(1)
+    }
+
+    /// Number of elements in a row; 1 for a one-dimensional array.
+    pub fn cols(&self) -> usize {
+        self.shape.iter().skip(1).product()
+    }
+
+    pub fn row(&self, n: usize) -> &[f64] {
+        let cols = self.cols();
+        &self.data[n * cols..(n + 1) * cols]
+    }
+}
+
+/// Which arrays of a series are written.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Part {
+    /// The index as a `(n,)` array.
+    Index,
+    /// The values as a `(n, value_len)` array.
+    Values,
+    /// The index and the values as a `(n, 1 + value_len)` array.
+    Both,
+}
+
+fn header_string(shape: &[usize]) -> String {
+    let dims: Vec<String> = shape.iter().map(|n| n.to_string()).collect();
+    let shape = match dims.len() {
+        1 => format!("({},)", dims[0]),
+        _ => format!("({})", dims.join(", ")),
+    };
+    let mut header = format!("{{'descr': '<f8', 'fortran_order': False, 'shape': {}, }}", shape);
+    // The data starts at a multiple of 64 bytes.
+    let unpadded = MAGIC.len() + 2 + 2 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    header.push('\n');
+    header
+}
+
+/// Returns the number of bytes of a `.npy` file of an array of `shape`.
+pub fn npy_size(shape: &[usize]) -> u64 {
+    let header = header_string(shape);
+    (MAGIC.len() + 2 + 2 + header.len()) as u64 + 8 * shape.iter().product::<usize>() as u64
+}
+
+/// Writes the header of a `.npy` file; the elements are to follow as
+/// little-endian `f64` in row-major order.
+pub fn write_npy_header<W: io::Write>(out: &mut W, shape: &[usize]) -> Result<()> {
+    let header = header_string(shape);
+    out.write_all(MAGIC)?;
+    out.write_all(&[1, 0])?;
+    out.write_u16::<LittleEndian>(header.len() as u16)?;
+    out.write_all(header.as_bytes())?;
+    Ok(())
+}
+
+pub fn write_npy<W: io::Write>(out: &mut W, array: &Array) -> Result<()> {
+    write_npy_header(out, &array.shape)?;
+    for &x in array.data.iter() {
+        out.write_f64::<LittleEndian>(x)?;
+    }
+    Ok(())
+}
+
+/// Writes `rows` entries with `value_len` values each as a `.npy` file.
+pub fn write_npy_entries<W, I>(out: &mut W, rows: usize, value_len: usize, entries: I, part: Part) -> Result<()>
+    where W: io::Write, I: Iterator<Item = Result<Entry>>
+{
+    let shape = match part {
+        Part::Index => vec![rows],
+        Part::Values => vec![rows, value_len],
+        Part::Both => vec![rows, 1 + value_len],
+    };
+    write_npy_header(out, &shape)?;
+    let mut count = 0;
+    for entry in entries {
+        let (index, values) = entry?;
+        if values.len() != value_len || count == rows {
+            return Err(Error::Shape);
+        }
+        if part != Part::Values {
+            out.write_f64::<LittleEndian>(index)?;
+        }
+        if part != Part::Index {
+            for &x in values.iter() {
+                out.write_f64::<LittleEndian>(x)?;
+            }
+        }
+        count += 1;
+    }
+    if count != rows {
+        return Err(Error::Shape);
+    }
+    Ok(())
+}
+
+// Returns the value of `key` in the header dictionary.
+fn header_value<'a>(header: &'a str, key: &str) -> Result<&'a str> {
+    let missing = || Error::Parse(format!("npy header has no {}", key));
+    let pos = header.find(&format!("'{}'", key))
+        .or_else(|| header.find(&format!("\"{}\"", key)))
+        .ok_or_else(missing)?;
+    let rest = &header[pos + key.len() + 2..];
+    let rest = rest.trim_start().strip_prefix(':').ok_or_else(missing)?.trim_start();
+    let end = if rest.starts_with('(') {
+        rest.find(')').map(|n| n + 1)
+    } else {
+        rest.find([',', '}'])
+    };
+    end.map(|n| rest[..n].trim()).ok_or_else(missing)
+}
+
+pub fn read_npy<R: io::Read>(input: &mut R) -> Result<Array> {
+    let mut magic = [0; 6];
+    input.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(Error::Magic);
+    }
+    let major = input.read_u8()?;
+    let _minor = input.read_u8()?;
+    let header_len = match major {
+        1 => input.read_u16::<LittleEndian>()? as u64,
+        2 | 3 => input.read_u32::<LittleEndian>()? as u64,
+        _ => { return Err(Error::Parse(format!("unsupported npy version {}", major))); },
+    };
+    let mut header = String::new();
+    input.by_ref().take(header_len).read_to_string(&mut header)?;
+
+    let descr = header_value(&header, "descr")?.trim_matches(|c| c == '\'' || c == '"');
+    let big_endian = match descr {
+        "<f8" => false,
+        ">f8" => true,
+        _ => { return Err(Error::Parse(format!("unsupported npy dtype {}", descr))); },
+    };
+    let fortran_order = match header_value(&header, "fortran_order")? {
+        "False" => false,
+        "True" => true,
+        v => { return Err(Error::Parse(format!("invalid fortran_order {}", v))); },
+    };
+    let shape = header_value(&header, "shape")?;
+    let shape = shape.trim_start_matches('(').trim_end_matches(')')
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<usize>().map_err(|_| Error::Parse(format!("invalid npy shape {}", s))))
+        .collect::<Result<Vec<usize>>>()?;
+    if shape.len() > 2 {
+        return Err(Error::Parse(format!("{}-dimensional npy arrays are not supported", shape.len())));
+    }
+
+    // The shape is untrusted: only what is actually read gets allocated.
+    let size = shape.iter().try_fold(8u64, |n, &d| n.checked_mul(d as u64))
+        .ok_or_else(|| Error::Parse(format!("npy shape {:?} is too large", shape)))?;
+    let mut bytes = Vec::new();
+    input.by_ref().take(size).read_to_end(&mut bytes)?;
+    if (bytes.len() as u64) < size {
+        return Err(Error::EndOfFile);
+    }
+    let len = bytes.len() / 8;
+    let mut data: Vec<f64> = bytes.chunks(8)
+        .map(|b| if big_endian { BigEndian::read_f64(b) } else { LittleEndian::read_f64(b) })
+        .collect();
+    if fortran_order && shape.len() == 2 {
+        let (rows, cols) = (shape[0], shape[1]);
+        let mut transposed = vec![0.0; len];
+        for r in 0..rows {
+            for c in 0..cols {
+                transposed[r * cols + c] = data[c * rows + r];
+            }
+        }
+        data = transposed;
+    }
+    Ok(Array { shape, data })
+}
+
+/// Makes a series from an array whose first column is the index.
+pub fn series_from_array(name: &str, array: &Array) -> Result<TimeSeries> {
+    if array.shape().len() != 2 || array.cols() < 1 {
+        return Err(Error::Shape);
+    }
+    let mut series = TimeSeries::new(name, array.cols() as u64 - 1);
+    for n in 0..array.rows() {
+        let row = array.row(n);
+        series.push(row[0], row[1..].to_vec());
+    }
+    Ok(series)
+}
+
+/// Makes a series from an `index` array and a `values` array.
+pub fn series_from_arrays(name: &str, index: &Array, values: &Array) -> Result<TimeSeries> {
+    if index.shape().len() != 1 || values.shape().len() > 2 || index.rows() != values.rows() {
+        return Err(Error::Shape);
+    }
+    let mut series = TimeSeries::new(name, values.cols() as u64);
+    for n in 0..index.rows() {
+        series.push(index.data()[n], values.row(n).to_vec());
+    }
+    Ok(series)
+}
+
+const CRC_POLY: u32 = 0xedb8_8320;
+
+// CRC-32 as used by the ZIP format.
+#[derive(Debug)]
+struct Crc32 {
+    table : [u32; 256],
+    value : u32,
+}
+
+impl Crc32 {
+    fn new() -> Self {
+        let mut table = [0; 256];
+        for (n, entry) in table.iter_mut().enumerate() {
+            let mut c = n as u32;
+            for _ in 0..8 {
+                c = if c & 1 != 0 { CRC_POLY ^ (c >> 1) } else { c >> 1 };
+            }
+            *entry = c;
+        }
+        Crc32 { table, value: 0xffff_ffff }
+    }
+
+    fn update(&mut self, buf: &[u8]) {
+        for &b in buf {
+            self.value = self.table[((self.value ^ b as u32) & 0xff) as usize] ^ (self.value >> 8);
+        }
+    }
+
+    fn finish(&self) -> u32 {
+        self.value ^ 0xffff_ffff
+    }
+}
+
+// Counts the bytes written through it and computes their CRC-32.
+struct CrcWriter<'a, W: 'a> {
+    out   : &'a mut W,
+    crc   : Crc32,
+    count : u64,
+}
+
+impl<'a, W: io::Write> io::Write for CrcWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.out.write(buf)?;
+        self.crc.update(&buf[..n]);
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[derive(Debug)]
+struct ZipEntry {
+    name   : String,
+    crc    : u32,
+    size   : u32,
+    offset : u32,
+}
+
+/// Writes an uncompressed `.npz` archive.
+#[derive(Debug)]
+pub struct NpzWriter<W: io::Write> {
+    out     : W,
+    offset  : u64,
+    entries : Vec<ZipEntry>,
+}
+
+impl<W: io::Write> NpzWriter<W> {
+    pub fn new(out: W) -> Self {
+        NpzWriter {
+            out,
+            offset: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    // Writes a stored ZIP entry whose content is produced by `fill`. The
+    // CRC and the size follow the data in a data descriptor.
+    fn write_entry<F>(&mut self, name: &str, fill: F) -> Result<()>
+        where F: FnOnce(&mut CrcWriter<W>) -> Result<()>
+    {
+        let file_name = format!("{}.npy", name);
+        if self.offset > u32::MAX as u64 {
+            return Err(Error::Parse("npz archives over 4 GiB are not supported".to_string()));
+        }
+        let offset = self.offset as u32;
+        self.out.write_u32::<LittleEndian>(0x0403_4b50)?;
+        self.out.write_u16::<LittleEndian>(20)?;   // version needed
+        self.out.write_u16::<LittleEndian>(0x08)?; // data descriptor follows
+        self.out.write_u16::<LittleEndian>(0)?;    // stored
+        self.out.write_u16::<LittleEndian>(0)?;    // time
+        self.out.write_u16::<LittleEndian>(0x21)?; // date: 1980-01-01
+        self.out.write_u32::<LittleEndian>(0)?;
+        self.out.write_u32::<LittleEndian>(0)?;
+        self.out.write_u32::<LittleEndian>(0)?;
+        self.out.write_u16::<LittleEndian>(file_name.len() as u16)?;
+        self.out.write_u16::<LittleEndian>(0)?;
+        self.out.write_all(file_name.as_bytes())?;
+        let (crc, size) = {
+            let mut w = CrcWriter { out: &mut self.out, crc: Crc32::new(), count: 0 };
+            fill(&mut w)?;
+            (w.crc.finish(), w.count)
+        };
+        if size > u32::MAX as u64 {
+            return Err(Error::Parse("npy arrays over 4 GiB are not supported in npz".to_string()));
+        }
+        self.out.write_u32::<LittleEndian>(0x0807_4b50)?;
+        self.out.write_u32::<LittleEndian>(crc)?;
+        self.out.write_u32::<LittleEndian>(size as u32)?;
+        self.out.write_u32::<LittleEndian>(size as u32)?;
+        self.offset += 30 + file_name.len() as u64 + size + 16;
+        self.entries.push(ZipEntry { name: file_name, crc, size: size as u32, offset });
+        Ok(())
+    }
+
+    pub fn write_array(&mut self, name: &str, array: &Array) -> Result<()> {
+        self.write_entry(name, |w| write_npy(w, array))
+    }
+
+    /// Writes a part of the entries of a series as the array `name`.
+    pub fn write_entries<I>(&mut self, name: &str, rows: usize, value_len: usize, entries: I, part: Part) -> Result<()>
+        where I: Iterator<Item = Result<Entry>>
+    {
+        self.write_entry(name, |w| write_npy_entries(w, rows, value_len, entries, part))
+    }
+
+    /// Writes the central directory and returns the stream.
+    pub fn finish(mut self) -> Result<W> {
+        let start = self.offset;
+        let mut size = 0;
+        for entry in self.entries.iter() {
+            self.out.write_u32::<LittleEndian>(0x0201_4b50)?;
+            self.out.write_u16::<LittleEndian>(20)?;   // version made by
+            self.out.write_u16::<LittleEndian>(20)?;   // version needed
+            self.out.write_u16::<LittleEndian>(0x08)?;
+            self.out.write_u16::<LittleEndian>(0)?;
+            self.out.write_u16::<LittleEndian>(0)?;
+            self.out.write_u16::<LittleEndian>(0x21)?;
+            self.out.write_u32::<LittleEndian>(entry.crc)?;
+            self.out.write_u32::<LittleEndian>(entry.size)?;
+            self.out.write_u32::<LittleEndian>(entry.size)?;
+            self.out.write_u16::<LittleEndian>(entry.name.len() as u16)?;
+            self.out.write_u16::<LittleEndian>(0)?;    // extra field
+            self.out.write_u16::<LittleEndian>(0)?;    // comment
+            self.out.write_u16::<LittleEndian>(0)?;    // disk
+            self.out.write_u16::<LittleEndian>(0)?;    // internal attributes
+            self.out.write_u32::<LittleEndian>(0)?;    // external attributes
+            self.out.write_u32::<LittleEndian>(entry.offset)?;
+            self.out.write_all(entry.name.as_bytes())?;
+            size += 46 + entry.name.len() as u64;
+        }
+        if start + size > u32::MAX as u64 || self.entries.len() > u16::MAX as usize {
+            return Err(Error::Parse("npz archive is too large".to_string()));
+        }
+        self.out.write_u32::<LittleEndian>(0x0605_4b50)?;
+        self.out.write_u16::<LittleEndian>(0)?;
+        self.out.write_u16::<LittleEndian>(0)?;
+        self.out.write_u16::<LittleEndian>(self.entries.len() as u16)?;
+        self.out.write_u16::<LittleEndian>(self.entries.len() as u16)?;
+        self.out.write_u32::<LittleEndian>(size as u32)?;
+        self.out.write_u32::<LittleEndian>(start as u32)?;
+        self.out.write_u16::<LittleEndian>(0)?;
+        Ok(self.out)
+    }
+}
+
+/// Reads all arrays of an uncompressed `.npz` archive.
+pub fn read_npz<R: io::Read + io::Seek>(input: &mut R) -> Result<Vec<(String, Array)>> {
+    // The end of central directory record is within the last 64 KiB.
+    let len = input.seek(SeekFrom::End(0))?;
+    let tail_len = len.min(65_557);
+    input.seek(SeekFrom::Start(len - tail_len))?;
+    let mut tail = Vec::new();
+    input.by_ref().take(tail_len).read_to_end(&mut tail)?;
+    let eocd = (0..tail.len().saturating_sub(21)).rev()
+        .find(|&n| LittleEndian::read_u32(&tail[n..]) == 0x0605_4b50)
+        .ok_or(Error::Magic)?;
+    let count = LittleEndian::read_u16(&tail[eocd + 10..]) as usize;
+    let start = LittleEndian::read_u32(&tail[eocd + 16..]) as u64;
+
+    let mut directory = Vec::new();
+    input.seek(SeekFrom::Start(start))?;
+    for _ in 0..count {
+        if input.read_u32::<LittleEndian>()? != 0x0201_4b50 {
+            return Err(Error::Magic);
+        }
+        let mut fixed = [0; 42];
+        input.read_exact(&mut fixed)?;
+        let method = LittleEndian::read_u16(&fixed[6..]);
+        let size = LittleEndian::read_u32(&fixed[16..]);
+        let name_len = LittleEndian::read_u16(&fixed[24..]) as usize;
+        let extra_len = LittleEndian::read_u16(&fixed[26..]) as i64;
+        let comment_len = LittleEndian::read_u16(&fixed[28..]) as i64;
+        let offset = LittleEndian::read_u32(&fixed[38..]);
+        let mut name = vec![0; name_len];
+        input.read_exact(&mut name)?;
+        input.seek(SeekFrom::Current(extra_len + comment_len))?;
+        let name = String::from_utf8(name).map_err(Error::FromUtf8)?;
+        if method != 0 {
+            return Err(Error::Parse(format!("{} is compressed; only stored npz archives are supported", name)));
+        }
+        directory.push((name, size, offset));
+    }
+
+    let mut arrays = Vec::new();
+    for (name, _size, offset) in directory {
+        input.seek(SeekFrom::Start(offset as u64))?;
+        if input.read_u32::<LittleEndian>()? != 0x0403_4b50 {
+            return Err(Error::Magic);
+        }
+        let mut fixed = [0; 26];
+        input.read_exact(&mut fixed)?;
+        let name_len = LittleEndian::read_u16(&fixed[22..]) as i64;
+        let extra_len = LittleEndian::read_u16(&fixed[24..]) as i64;
+        input.seek(SeekFrom::Current(name_len + extra_len))?;
+        let array = read_npy(input)?;
+        let name = name.trim_end_matches(".npy").to_string();
+        arrays.push((name, array));
+    }
+    Ok(arrays)
+}
+
+// Returns the name of the arrays of the `n`-th series in a `.npz` archive.
+fn array_prefix(group: &str, name: Option<&str>, n: usize) -> String {
+    let name = match name {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => format!("series{}", n),
+    };
+    group::join(group, &name)[1..].to_string()
+}
+
+/// Writes every series read by `lazy` into a `.npz` archive.
+pub fn write_npz<W, R>(out: W, lazy: &mut LazyReader<R>) -> Result<W>
+    where W: io::Write, R: io::Read + io::Seek
+{
+    let mut npz = NpzWriter::new(out);
+    for n in 0..lazy.series().len() {
+        let (prefix, rows, value_len) = {
+            let info = &lazy.series()[n];
+            (array_prefix(info.group(), info.name(), n), info.length() as usize, info.value_len() as usize)
+        };
+        npz.write_entries(&format!("{}/index", prefix), rows, value_len, lazy.entries(n)?, Part::Index)?;
+        npz.write_entries(&format!("{}/values", prefix), rows, value_len, lazy.entries(n)?, Part::Values)?;
+    }
+    npz.finish()
+}
+
+/// Reads the series of a `.npz` archive.
+///
+/// Each pair of arrays `PATH/index` and `PATH/values` becomes a series at
+/// `PATH`; a pair named just `index` and `values` becomes a series named
+/// `default_name` in the root group. Other arrays are ignored.
+pub fn read_npz_dataset<R: io::Read + io::Seek>(input: &mut R, default_name: &str) -> Result<Dataset> {
+    let arrays = read_npz(input)?;
+    let mut dataset = Dataset::new();
+    for (name, index) in arrays.iter() {
+        let prefix = match name.strip_suffix("index") {
+            Some(prefix) if prefix.is_empty() || prefix.ends_with('/') => prefix,
+            _ => continue,
+        };
+        let values_name = format!("{}values", prefix);
+        let values = match arrays.iter().find(|(n, _)| *n == values_name) {
+            Some((_, values)) => values,
+            None => continue,
+        };
+        let path = group::normalize(prefix);
+        let series = if path == group::ROOT {
+            series_from_arrays(default_name, index, values)?
+        } else {
+            let parent = group::parent(&path).unwrap();
+            series_from_arrays(group::base_name(&path), index, values)?.with_group(&parent)
+        };
+        dataset.add_series(series);
+    }
+    Ok(dataset)
+}
@@ -1,45 +1,130 @@
 use std::io;
+use std::io::Read;
 use std::marker::PhantomData;
-use byteorder::{LittleEndian, ReadBytesExt};
-use header::{Header, BlockHeader, LogBlock, F64TSBlock};
+use header::{self, Header, BlockHeader, LogBlock, F64TSBlock, GroupBlock, AttrBlock, EventBlock};
+use group;
 use error::{Result, Error};
+use record::{Record, Records};
 
 #[derive(Debug)]
 pub enum Block {
     Log(LogBlock),
     F64TS(F64TSBlock),
+    Group(GroupBlock),
+    Attr(AttrBlock),
+    Event(EventBlock),
+}
+
+/// Decodes the body of a block named `name`, leaving any padding unread.
+/// `Reader` and `async_io::AsyncReader` decode their blocks with this.
+pub fn decode_block<R: io::Read>(name: &str, body: &mut R) -> Result<Block> {
+    Ok(match name {
+        "log" => Block::Log(LogBlock::read_from(body)?),
+        "f64ts" => Block::F64TS(F64TSBlock::read_from(body)?),
+        "group" => Block::Group(GroupBlock::read_from(body)?),
+        "attr" => Block::Attr(AttrBlock::read_from(body)?),
+        "event" => Block::Event(EventBlock::read_from(body)?),
+        _ => { return Err(Error::UndefinedBlock); },
+    })
 }
 
 #[derive(Debug)]
 pub struct Reader<R: io::Read> {
     stream: R,
     header: Option<Header>,
+    group: String,
+    segments: bool,
+    segment: usize,
 }
 
 impl<R: io::Read> Reader<R> {
     pub fn new(stream: R) -> Reader<R> {
         Reader {
-            stream: stream,
+            stream,
             header: None,
+            group: group::ROOT.to_string(),
+            segments: false,
+            segment: 0,
         }
     }
 
+    /// Makes the reader accept the header of another file where a block is
+    /// expected, as in files joined with `cat`. Each header starts a new
+    /// segment, whose blocks belong to the root group until a group block.
+    /// Otherwise such a header fails with `Error::Magic`.
+    pub fn accept_segments(mut self, accept: bool) -> Self {
+        self.segments = accept;
+        self
+    }
+
+    /// Number of embedded headers read so far.
+    pub fn segment(&self) -> usize {
+        self.segment
+    }
+
     pub fn initialize(&mut self) -> Result<()> {
         let header = Header::read_from(&mut self.stream)?;
         self.header = Some(header);
         Ok(())
     }
 
+    /// Returns the header read by `initialize`.
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+
     pub fn next_block(&mut self) -> Result<Block> {
-        let bheader = BlockHeader::read_from(&mut self.stream)?;
-        match bheader.clone_name().as_str() {
-            "log" => LogBlock::read_from(&mut self.stream).map(|v| Block::Log(v)),
-            "f64ts" => F64TSBlock::read_from(&mut self.stream).map(|v| Block::F64TS(v)),
-            _ => Err(Error::UndefinedBlock),
+        let bheader = if self.segments {
+            let mut magic = [0; 8];
+            match header::read_magic(&mut self.stream, &mut magic)? {
+                0 => { return Err(Error::EndOfFile); },
+                8 => {},
+                _ => { return Err(Error::Io(io::ErrorKind::UnexpectedEof.into())); },
+            }
+            let mut stream = io::Cursor::new(magic).chain(&mut self.stream);
+            if Header::check_magic(&magic) {
+                Header::read_from(&mut stream)?;
+                self.segment += 1;
+                self.group = group::ROOT.to_string();
+                return self.next_block();
+            }
+            BlockHeader::read_from(&mut stream)?
+        } else {
+            BlockHeader::read_from(&mut self.stream)?
+        };
+        let mut body = (&mut self.stream).take(bheader.block_size());
+        let block = decode_block(&bheader.clone_name(), &mut body)?;
+        // Skips the padding at the end of the block.
+        let rest = body.limit();
+        if io::copy(&mut body, &mut io::sink())? < rest {
+            return Err(Error::EndOfFile);
+        }
+        match block {
+            Block::Log(ref log) if log.is_padding() => { return self.next_block(); },
+            Block::Group(ref g) => { self.group = g.path().to_string(); },
+            _ => {},
         }
+        Ok(block)
+    }
+
+    /// Path of the group which the last block read belongs to.
+    pub fn current_group(&self) -> &str {
+        &self.group
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.stream
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.stream
     }
 
-    pub fn f64ts_entries(&mut self, data: &F64TSBlock) -> F64TSReader<R> {
+    pub fn into_inner(self) -> R {
+        self.stream
+    }
+
+    pub fn f64ts_entries(&mut self, data: &F64TSBlock) -> F64TSReader<'_, R> {
         F64TSReader {
             index_len: data.index_len() as usize,
             value_len: data.value_len() as usize,
@@ -50,6 +135,18 @@ impl<R: io::Read> Reader<R> {
     }
 }
 
+impl<R: io::Read + io::Seek> Reader<R> {
+    /// Seeks over the entries of a f64ts block without reading them.
+    pub fn skip_f64ts(&mut self, data: &F64TSBlock) -> Result<()> {
+        let size = data.data_size()?;
+        if size > i64::MAX as u64 {
+            return Err(Error::Parse(format!("f64ts block of {} bytes is too large", size)));
+        }
+        self.stream.seek(io::SeekFrom::Current(size as i64))?;
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct F64TSReader<'a, R: 'a> {
     index_len : usize,
@@ -59,6 +156,13 @@ pub struct F64TSReader<'a, R: 'a> {
     phantom: PhantomData<&'a R>,
 }
 
+impl<'a, R> F64TSReader<'a, R> where R: 'a + io::Read {
+    /// Decodes the entries into records of `T`.
+    pub fn records<T: Record>(self) -> Records<Self, T> {
+        Records::new(self)
+    }
+}
+
 impl<'a, R> Iterator for F64TSReader<'a, R> where R: 'a + io::Read {
     type Item = Result<(f64,Vec<f64>)>;
 
@@ -66,26 +170,10 @@ impl<'a, R> Iterator for F64TSReader<'a, R> where R: 'a + io::Read {
         if self.remaining == 0 {
             return None;
         }
-
-        let index: f64;
-        let mut value: Vec<f64> = Vec::new();
-
-        match self.stream.read_f64::<LittleEndian>() {
-            Ok(f) => { index = f; },
-            Err(e) => { return Some(Err(e.into())); },
-        }
-        for _ in 1..self.index_len {
-            if let Err(e) = self.stream.read_f64::<LittleEndian>() {
-                return Some(Err(e.into()));
-            }
-        }
-        for _ in 0..self.value_len {
-            match self.stream.read_f64::<LittleEndian>() {
-                Ok(f) => { value.push(f); },
-                Err(e) => { return Some(Err(e.into())); },
-            }
+        let entry = header::read_entry_from(self.stream, self.index_len, self.value_len);
+        if entry.is_ok() {
+            self.remaining -= 1;
         }
-        self.remaining = self.remaining - 1;
-        Some(Ok((index,value)))
+        Some(entry)
     }
 }
@@ -0,0 +1,152 @@
+//! Typed rows of a f64ts block.
+//!
+//! A `Record` is a struct with one field as the index and the others as
+//! channels. It is usually implemented with `#[derive(BrotoRecord)]` from
+//! the `broto-derive` crate (re-exported with the `derive` feature):
+//!
+//! ```ignore
+//! #[derive(BrotoRecord)]
+//! struct Sample {
+//!     #[broto(index)]
+//!     time: f64,
+//!     temp: f64,
+//!     #[broto(rename = "rh")]
+//!     humidity: f32,
+//!     count: u32,
+//! }
+//! ```
+//!
+//! Fields are converted to and from `f64` by `Field`; reading a value that
+//! the type of its field cannot hold fails with `Error::Parse`. A field
+//! marked `#[broto(skip)]` is not stored and is `Default::default()` when
+//! read.
+
+use std::marker::PhantomData;
+use header::F64TSBlockBuilder;
+use error::{Result, Error};
+use Entry;
+
+/// A value that can be stored in a channel.
+pub trait Field: Sized {
+    fn to_f64(&self) -> f64;
+
+    /// Converts a stored value back, failing with `Error::Parse` if it is
+    /// out of range or, for integers, not integral.
+    fn from_f64(x: f64) -> Result<Self>;
+}
+
+fn out_of_range(x: f64, ty: &str) -> Error {
+    Error::Parse(format!("{} does not fit in {}", x, ty))
+}
+
+macro_rules! impl_int_field {
+    ($($t:ident)*) => {
+        $(
+            impl Field for $t {
+                fn to_f64(&self) -> f64 {
+                    *self as f64
+                }
+
+                fn from_f64(x: f64) -> Result<Self> {
+                    // `MAX as f64 + 1.0` is exact or rounds to the same
+                    // power of two as `MAX as f64`.
+                    if x.fract() != 0.0 || !(x >= $t::MIN as f64 && x < $t::MAX as f64 + 1.0) {
+                        return Err(out_of_range(x, stringify!($t)));
+                    }
+                    Ok(x as $t)
+                }
+            }
+        )*
+    }
+}
+
+impl_int_field!(i8 i16 i32 i64 isize u8 u16 u32 u64 usize);
+
+impl Field for f64 {
+    fn to_f64(&self) -> f64 {
+        *self
+    }
+
+    fn from_f64(x: f64) -> Result<Self> {
+        Ok(x)
+    }
+}
+
+impl Field for f32 {
+    fn to_f64(&self) -> f64 {
+        *self as f64
+    }
+
+    fn from_f64(x: f64) -> Result<Self> {
+        let y = x as f32;
+        if x.is_finite() && y.is_infinite() {
+            return Err(out_of_range(x, "f32"));
+        }
+        Ok(y)
+    }
+}
+
+impl Field for bool {
+    fn to_f64(&self) -> f64 {
+        if *self { 1.0 } else { 0.0 }
+    }
+
+    fn from_f64(x: f64) -> Result<Self> {
+        Ok(x != 0.0)
+    }
+}
+
+/// A struct stored as an entry of a f64ts block.
+pub trait Record: Sized {
+    /// Names of the channels in the order of the values.
+    const CHANNELS: &'static [&'static str];
+
+    fn index(&self) -> f64;
+
+    /// Appends the values of the channels to `values`.
+    fn write_values(&self, values: &mut Vec<f64>);
+
+    /// Builds a record from an entry, failing with `Error::Shape` if the
+    /// number of values is not the number of channels.
+    fn from_entry(index: f64, values: &[f64]) -> Result<Self>;
+
+    fn to_entry(&self) -> Entry {
+        let mut values = Vec::with_capacity(Self::CHANNELS.len());
+        self.write_values(&mut values);
+        (self.index(), values)
+    }
+
+    fn decode(entry: &Entry) -> Result<Self> {
+        Self::from_entry(entry.0, &entry.1)
+    }
+}
+
+/// Returns a builder of the descriptor of a series of `T`, with the shape
+/// and the channel names set.
+pub fn block_builder<T: Record>() -> F64TSBlockBuilder<u64, u64> {
+    F64TSBlockBuilder::new()
+        .index_len(1)
+        .value_len(T::CHANNELS.len() as u64)
+        .channel_names(T::CHANNELS.to_vec())
+}
+
+/// An iterator decoding entries into records.
+#[derive(Debug)]
+pub struct Records<I, T> {
+    entries : I,
+    phantom : PhantomData<T>,
+}
+
+impl<I, T> Records<I, T> where I: Iterator<Item = Result<Entry>>, T: Record {
+    pub fn new(entries: I) -> Self {
+        Records { entries, phantom: PhantomData }
+    }
+}
+
+impl<I, T> Iterator for Records<I, T> where I: Iterator<Item = Result<Entry>>, T: Record {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(|entry| entry.and_then(|entry| T::decode(&entry)))
+    }
+}
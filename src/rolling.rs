@@ -0,0 +1,242 @@
+//! Writing a series into a sequence of files.
+//!
+//! A `RollingWriter` writes the entries of one series into a file until a
+//! limit on its size, its number of rows or the span of its indices is
+//! reached, and then closes it and continues in a new file. Every file has
+//! the metadata and the descriptor of the series, so each can be read on
+//! its own; `merge::concat` joins them back.
+//!
+//! The file names come from a template in which
+//!
+//! * `%Y`, `%m`, `%d`, `%H`, `%M` and `%S` are replaced by the UTC date and
+//!   time of the first index of the file, taken as seconds since the UNIX
+//!   epoch,
+//! * `%n` is replaced by the number of the file, starting at 1, and
+//! * `%%` is replaced by `%`.
+//!
+//! Existing files are never replaced: if the name is taken, `-2`, `-3`, ...
+//! is added before the extension.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use file::{File, PendingFile};
+use writer::F64TSWriter;
+use header::F64TSBlock;
+use error::{Result, Error};
+use timestamp;
+use group;
+use Metadata;
+
+/// Latest time which `file_name` formats, 9999-12-31T23:59:59Z.
+const MAX_SECS: f64 = 253_402_300_799.0;
+
+/// Expands the directives of `template` for the file number `n` whose first
+/// index is `index`. Indices before the epoch or after the year 9999 are
+/// clamped.
+pub fn file_name(template: &str, index: f64, n: usize) -> String {
+    let secs = if index.is_finite() && index > 0.0 { index.min(MAX_SECS) } else { 0.0 };
+    let (year, month, day, hour, min, sec) = timestamp::to_utc(Duration::from_secs_f64(secs));
+    let mut name = String::with_capacity(template.len());
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            name.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => name.push_str(&format!("{:04}", year)),
+            Some('m') => name.push_str(&format!("{:02}", month)),
+            Some('d') => name.push_str(&format!("{:02}", day)),
+            Some('H') => name.push_str(&format!("{:02}", hour)),
+            Some('M') => name.push_str(&format!("{:02}", min)),
+            Some('S') => name.push_str(&format!("{:02}", sec)),
+            Some('n') => name.push_str(&n.to_string()),
+            Some('%') => name.push('%'),
+            Some(other) => { name.push('%'); name.push(other); },
+            None => name.push('%'),
+        }
+    }
+    name
+}
+
+// `path` with `-n` added before the extension.
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, n, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, n),
+    };
+    path.with_file_name(name)
+}
+
+// The file being written.
+struct Part {
+    writer : F64TSWriter<PendingFile>,
+    path   : PathBuf,
+    start  : f64,
+    end    : f64,
+}
+
+/// Writes a series into files which are rotated when a limit is reached.
+///
+/// The current file is written to a temporary file like with
+/// `File::create`; dropping the writer without calling `close` discards it.
+pub struct RollingWriter {
+    template  : String,
+    block     : F64TSBlock,
+    group     : String,
+    metadata  : Metadata,
+    max_bytes : Option<u64>,
+    max_rows  : Option<u64>,
+    max_span  : Option<f64>,
+    current   : Option<Part>,
+    files     : Vec<PathBuf>,
+}
+
+impl RollingWriter {
+    /// Creates a writer of the series described by `block` into files named
+    /// by `template`. No file is created until the first entry is written.
+    /// Fails with `Error::Shape` unless the series has one index column.
+    pub fn new<S: Into<String>>(template: S, block: F64TSBlock) -> Result<Self> {
+        if block.index_len() != 1 {
+            return Err(Error::Shape);
+        }
+        Ok(RollingWriter {
+            template  : template.into(),
+            block,
+            group     : group::ROOT.to_string(),
+            metadata  : Metadata::new(),
+            max_bytes : None,
+            max_rows  : None,
+            max_span  : None,
+            current   : None,
+            files     : Vec::new(),
+        })
+    }
+
+    /// Puts the series in the group at `path`.
+    pub fn group(mut self, path: &str) -> Self {
+        self.group = group::normalize(path);
+        self
+    }
+
+    /// Sets the logs, attributes and events written to the root group of
+    /// every file.
+    pub fn metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Limits the size of a file. A file always has at least one entry, so
+    /// it exceeds the limit if the metadata and one entry do not fit.
+    pub fn max_bytes(mut self, bytes: u64) -> Self {
+        assert!(bytes > 0);
+        self.max_bytes = Some(bytes);
+        self
+    }
+
+    /// Limits the number of entries of a file.
+    pub fn max_rows(mut self, rows: u64) -> Self {
+        assert!(rows > 0);
+        self.max_rows = Some(rows);
+        self
+    }
+
+    /// Limits the indices of a file to an interval of length `span` that
+    /// starts at a multiple of `span`, so that, for example, files of an
+    /// hour start on the hour. An entry outside the interval of the current
+    /// file starts a new one.
+    pub fn max_span(mut self, span: f64) -> Self {
+        assert!(span > 0.0 && span.is_finite());
+        self.max_span = Some(span);
+        self
+    }
+
+    /// Paths of the files which have been closed.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Path of the file being written, if any.
+    pub fn current_path(&self) -> Option<&Path> {
+        self.current.as_ref().map(|part| part.path.as_path())
+    }
+
+    /// Writes an entry, failing with `Error::Shape` if the number of values
+    /// is not `value_len`.
+    pub fn write_entry(&mut self, index: f64, values: &[f64]) -> Result<()> {
+        if values.len() as u64 != self.block.value_len() {
+            return Err(Error::Shape);
+        }
+        if self.is_full(index) {
+            self.roll()?;
+        }
+        if self.current.is_none() {
+            self.open(index)?;
+        }
+        self.current.as_mut().unwrap().writer.write_entry(index, values)
+    }
+
+    /// Closes the current file, if any; the next entry starts a new one.
+    pub fn roll(&mut self) -> Result<()> {
+        if let Some(part) = self.current.take() {
+            part.writer.finalize()?.finish().close()?;
+            self.files.push(part.path);
+        }
+        Ok(())
+    }
+
+    /// Closes the current file and returns the paths of all the files
+    /// written.
+    pub fn close(mut self) -> Result<Vec<PathBuf>> {
+        self.roll()?;
+        Ok(self.files)
+    }
+
+    fn is_full(&self, index: f64) -> bool {
+        let part = match self.current {
+            Some(ref part) => part,
+            None => { return false; },
+        };
+        let count = part.writer.count();
+        let row_size = 8 * (1 + self.block.value_len());
+        self.max_rows.is_some_and(|rows| count >= rows)
+            || self.max_bytes.is_some_and(|bytes| count > 0 && part.writer.position() + row_size > bytes)
+            || index < part.start || index >= part.end
+    }
+
+    fn open(&mut self, index: f64) -> Result<()> {
+        let n = self.files.len() + 1;
+        let name = PathBuf::from(file_name(&self.template, index, n));
+        let mut path = name.clone();
+        let mut k = 1;
+        while path.exists() || self.files.contains(&path) {
+            k += 1;
+            path = numbered(&name, k);
+        }
+
+        let mut writer = File::create(&path)?;
+        for log in self.metadata.get_logs() {
+            writer.write_log(log)?;
+        }
+        for attr in self.metadata.get_attrs() {
+            writer.write_attr(attr)?;
+        }
+        for event in self.metadata.get_events() {
+            writer.write_event(event)?;
+        }
+        if self.group != group::ROOT {
+            writer.write_group(&self.group)?;
+        }
+        let writer = writer.write_f64ts_with_seek(self.block.clone())?;
+        let (start, end) = match self.max_span {
+            Some(span) if index.is_finite() => {
+                let start = (index / span).floor() * span;
+                (start, start + span)
+            },
+            _ => (f64::NEG_INFINITY, f64::INFINITY),
+        };
+        self.current = Some(Part { writer, path, start, end });
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+//! Extraction of a part of every series of a file.
+//!
+//! `extract` writes a new file with the logs, attributes and events of the
+//! input and, of each series, the entries in an index range or a row range.
+//! A series written in several blocks (see `LazyReader::chunks`) is taken
+//! as a whole and written as one block.
+//! `rows_per_part` computes the row ranges for splitting a file into parts
+//! of a number of rows or bytes.
+
+use std::io;
+use lazy::LazyReader;
+use writer::Writer;
+use header::LogBlock;
+use error::{Result, Error};
+use group;
+
+/// The entries of each series to keep.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Range {
+    /// Entries whose index is at least `from` and less than `to`.
+    Index { from: Option<f64>, to: Option<f64> },
+    /// Rows `start` up to but excluding `end`.
+    Rows { start: u64, end: u64 },
+}
+
+impl Range {
+    fn contains(&self, index: f64) -> bool {
+        match *self {
+            Range::Index { from, to } => from.is_none_or(|f| index >= f) && to.is_none_or(|t| index < t),
+            Range::Rows { .. } => true,
+        }
+    }
+}
+
+/// How to split a file into parts.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Split {
+    /// At most this many rows of each series in a part.
+    Rows(u64),
+    /// Parts of at most this many bytes.
+    Bytes(u64),
+}
+
+/// Writes the metadata of the file read by `input` and the entries of each
+/// series in `range` into `writer`, whose header must have been written.
+/// `log`, if any, is added to the logs of the root group.
+pub fn extract<R, W>(input: &mut LazyReader<R>, mut writer: Writer<W>, range: Range,
+                     log: Option<&LogBlock>) -> Result<Writer<W>>
+    where R: io::Read + io::Seek, W: io::Write + io::Seek
+{
+    let paths: Vec<String> = input.group_paths().into_iter().map(|p| p.to_string()).collect();
+    for path in paths.iter() {
+        if path != group::ROOT {
+            writer.write_group(path)?;
+        }
+        {
+            let metadata = input.group(path).unwrap();
+            for log in metadata.get_logs() {
+                writer.write_log(log)?;
+            }
+            if let (Some(log), true) = (log, path == group::ROOT) {
+                writer.write_log(log)?;
+            }
+            for attr in metadata.get_attrs() {
+                writer.write_attr(attr)?;
+            }
+            for event in metadata.get_events() {
+                writer.write_event(event)?;
+            }
+        }
+        for n in 0..input.series().len() {
+            let chunks = input.chunks(n);
+            // The later blocks of a series are written with the first.
+            if input.series()[n].group() != path || chunks[0] != n {
+                continue;
+            }
+            let block = input.series()[n].block().clone();
+            // Only the first column of the index is read and written.
+            if chunks.iter().any(|&m| input.series()[m].value_len() != block.value_len()
+                                 || input.series()[m].block().index_len() != 1) {
+                return Err(Error::Shape);
+            }
+            let mut w = writer.write_f64ts_with_seek(block)?;
+            // The row of the series where the chunk starts.
+            let mut row = 0;
+            for m in chunks {
+                let length = input.series()[m].length();
+                let entries = match range {
+                    Range::Rows { start, end } => {
+                        let from = start.saturating_sub(row).min(length);
+                        let to = end.saturating_sub(row).min(length);
+                        input.entries_from(m, from)?.take(to.saturating_sub(from) as usize)
+                    },
+                    Range::Index { .. } => input.entries(m)?.take(usize::MAX),
+                };
+                for entry in entries {
+                    let (index, values) = entry?;
+                    if range.contains(index) {
+                        w.write_entry(index, &values)?;
+                    }
+                }
+                row += length;
+            }
+            writer = w.finalize()?.finish();
+        }
+    }
+    Ok(writer)
+}
+
+/// Returns the number of rows of each series in a part, and the number of
+/// parts, for splitting the file read by `input`.
+///
+/// It fails with `Error::Parse` for `Split::Rows(0)`, and with
+/// `Error::Shape` if a series has an index of several values, which
+/// `extract` cannot write. For `Split::Bytes`
+/// the size of the metadata, measured with `log` as the log added to each
+/// part, is taken into account; it fails with `Error::Parse` if a row of
+/// each series does not fit.
+pub fn rows_per_part<R>(input: &mut LazyReader<R>, split: Split, log: &LogBlock) -> Result<(u64, u64)>
+    where R: io::Read + io::Seek
+{
+    if input.series().iter().any(|s| s.block().index_len() != 1) {
+        return Err(Error::Shape);
+    }
+    // The first block and the total length of each series.
+    let lengths: Vec<(usize, u64)> = (0..input.series().len())
+        .map(|n| (input.chunks(n), n))
+        .filter(|&(ref chunks, n)| chunks[0] == n)
+        .map(|(chunks, n)| (n, chunks.iter().map(|&m| input.series()[m].length()).sum()))
+        .collect();
+    let max_len = lengths.iter().map(|s| s.1).max().unwrap_or(0);
+    let rows = match split {
+        Split::Rows(0) => {
+            return Err(Error::Parse("parts must have at least one row".to_string()));
+        },
+        Split::Rows(rows) => rows,
+        Split::Bytes(bytes) => {
+            let mut writer = Writer::new(io::Cursor::new(Vec::new()));
+            writer.write_header()?;
+            let overhead = extract(input, writer, Range::Rows { start: 0, end: 0 }, Some(log))?.position();
+            let mut row_size: u64 = 0;
+            for s in lengths.iter().filter(|s| s.1 > 0) {
+                row_size = input.series()[s.0].block().row_size()?.checked_add(row_size)
+                    .ok_or_else(|| Error::Parse("rows are too large".to_string()))?;
+            }
+            let rows = bytes.saturating_sub(overhead).checked_div(row_size).unwrap_or(max_len);
+            if rows == 0 && max_len > 0 {
+                return Err(Error::Parse(format!("parts of {} bytes cannot hold the metadata ({} bytes) and a row",
+                                                bytes, overhead)));
+            }
+            rows.max(1)
+        },
+    };
+    Ok((rows, max_len.div_ceil(rows).max(1)))
+}
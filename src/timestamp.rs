@@ -0,0 +1,122 @@
+//! Conversion between durations since the UNIX epoch and RFC 3339 strings.
+
+use std::time::Duration;
+
+// Days since 1970-01-01 to a proleptic Gregorian date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// A proleptic Gregorian date to days since 1970-01-01.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Splits a duration since the UNIX epoch into the UTC date and time:
+/// `(year, month, day, hour, minute, second)`.
+pub fn to_utc(time: Duration) -> (i64, u32, u32, u32, u32, u32) {
+    let secs = time.as_secs() as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let rem = secs.rem_euclid(86_400) as u32;
+    (year, month, day, rem / 3600, rem / 60 % 60, rem % 60)
+}
+
+/// Formats a duration since the UNIX epoch as an RFC 3339 timestamp in UTC,
+/// e.g. `2018-06-01T12:34:56.789Z`. The fraction is omitted if it is zero.
+pub fn format_rfc3339(time: Duration) -> String {
+    let (year, month, day, hour, min, sec) = to_utc(time);
+    let mut s = format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", year, month, day, hour, min, sec);
+    let nanos = time.subsec_nanos();
+    if nanos > 0 {
+        let frac = format!("{:09}", nanos);
+        s.push('.');
+        s.push_str(frac.trim_end_matches('0'));
+    }
+    s.push('Z');
+    s
+}
+
+/// Parses an RFC 3339 timestamp such as `2018-06-01T12:34:56.789+09:00`
+/// into a duration since the UNIX epoch. Returns `None` if the string is
+/// malformed or the time is before the epoch.
+pub fn parse_rfc3339(s: &str) -> Option<Duration> {
+    let b = s.as_bytes();
+    if b.len() < 20 || b[4] != b'-' || b[7] != b'-' || b[13] != b':' || b[16] != b':'
+        || !(b[10] == b'T' || b[10] == b't' || b[10] == b' ') {
+        return None;
+    }
+    let num = |from: usize, to: usize| -> Option<u32> {
+        let field = s.get(from..to)?;
+        if field.bytes().all(|c| c.is_ascii_digit()) { field.parse().ok() } else { None }
+    };
+    let (year, month, day) = (num(0, 4)?, num(5, 7)?, num(8, 10)?);
+    let (hour, min, sec) = (num(11, 13)?, num(14, 16)?, num(17, 19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || min > 59 || sec > 60 {
+        return None;
+    }
+
+    let mut rest = &s[19..];
+    let mut nanos = 0;
+    if rest.starts_with('.') {
+        let digits = rest[1..].bytes().take_while(|c| c.is_ascii_digit()).count();
+        if digits == 0 {
+            return None;
+        }
+        let frac = &rest[1..1 + digits.min(9)];
+        nanos = frac.parse::<u32>().ok()? * 10u32.pow(9 - frac.len() as u32);
+        rest = &rest[1 + digits..];
+    }
+    let offset = match rest {
+        "Z" | "z" => 0,
+        _ if rest.len() == 6 && rest.as_bytes()[3] == b':' => {
+            let h = num(s.len() - 5, s.len() - 3)? as i64;
+            let m = num(s.len() - 2, s.len())? as i64;
+            match rest.as_bytes()[0] {
+                b'+' => h * 3600 + m * 60,
+                b'-' => -(h * 3600 + m * 60),
+                _ => { return None; },
+            }
+        },
+        _ => { return None; },
+    };
+
+    let days = days_from_civil(year as i64, month, day);
+    let secs = days * 86_400 + (hour * 3600 + min * 60 + sec) as i64 - offset;
+    if secs < 0 {
+        return None;
+    }
+    Some(Duration::new(secs as u64, nanos))
+}
+
+/// (De)serialization of a duration since the UNIX epoch as an RFC 3339
+/// timestamp, for use with `#[serde(with = "...")]`.
+#[cfg(feature = "serde")]
+pub mod rfc3339 {
+    use std::time::Duration;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(time: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::format_rfc3339(*time))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        super::parse_rfc3339(&s)
+            .ok_or_else(|| de::Error::custom(format!("invalid RFC 3339 timestamp: {}", s)))
+    }
+}
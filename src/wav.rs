@@ -0,0 +1,361 @@
+//! Conversion between RIFF/WAVE audio and series.
+//!
+//! Each channel of a WAV file is a value of the series, and the index of
+//! the `n`-th frame is `n / sample_rate` seconds. Integer samples are scaled
+//! to `[-1, 1)` exactly, so PCM audio reads back without loss. The header
+//! fields are kept as attributes (`wav.format`, `wav.sample_rate`, ...) of
+//! the group of the series.
+
+use std::io;
+use std::io::Read;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use header::AttrBlock;
+use dataset::TimeSeries;
+use error::{Result, Error};
+use {Metadata, Entry};
+
+const FORMAT_PCM: u16 = 1;
+const FORMAT_FLOAT: u16 = 3;
+const FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+// The GUID of the subformats of WAVE_FORMAT_EXTENSIBLE after the format tag.
+const SUBFORMAT_GUID: [u8; 14] = [
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xaa, 0x00, 0x38, 0x9b, 0x71,
+];
+
+/// Encoding of the samples of a WAV file.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum SampleFormat {
+    Pcm16,
+    Pcm24,
+    Float32,
+}
+
+impl SampleFormat {
+    pub fn bits_per_sample(self) -> u16 {
+        match self {
+            SampleFormat::Pcm16 => 16,
+            SampleFormat::Pcm24 => 24,
+            SampleFormat::Float32 => 32,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SampleFormat::Pcm16 => "pcm16",
+            SampleFormat::Pcm24 => "pcm24",
+            SampleFormat::Float32 => "float32",
+        }
+    }
+
+    fn bytes(self) -> usize {
+        self.bits_per_sample() as usize / 8
+    }
+}
+
+impl ::std::str::FromStr for SampleFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pcm16" => Ok(SampleFormat::Pcm16),
+            "pcm24" => Ok(SampleFormat::Pcm24),
+            "float32" => Ok(SampleFormat::Float32),
+            _ => Err(Error::Parse(format!("unknown sample format: {}", s))),
+        }
+    }
+}
+
+/// The header fields of a WAV file.
+#[derive(Debug,Clone,PartialEq)]
+pub struct WavSpec {
+    channels     : u16,
+    sample_rate  : u32,
+    format       : SampleFormat,
+    channel_mask : Option<u32>,
+}
+
+impl WavSpec {
+    pub fn new(channels: u16, sample_rate: u32, format: SampleFormat) -> Self {
+        assert!(channels > 0 && sample_rate > 0);
+        WavSpec { channels, sample_rate, format, channel_mask: None }
+    }
+
+    /// Writes the header as WAVE_FORMAT_EXTENSIBLE with the speaker
+    /// positions given by `mask`.
+    pub fn channel_mask(mut self, mask: u32) -> Self {
+        self.channel_mask = Some(mask);
+        self
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn format(&self) -> SampleFormat {
+        self.format
+    }
+
+    pub fn get_channel_mask(&self) -> Option<u32> {
+        self.channel_mask
+    }
+
+    /// Rejects the header fields which `new` asserts on.
+    fn validated(self) -> Result<Self> {
+        if self.channels == 0 || self.sample_rate == 0 {
+            return Err(unsupported(format!("{} channels at {} Hz", self.channels, self.sample_rate)));
+        }
+        Ok(self)
+    }
+
+    fn block_align(&self) -> usize {
+        self.channels as usize * self.format.bytes()
+    }
+
+    /// Returns the attributes recording this header.
+    pub fn to_attrs(&self) -> Vec<AttrBlock> {
+        let mut attrs = vec![
+            AttrBlock::new("wav.format", self.format.name()),
+            AttrBlock::new("wav.channels", self.channels.to_string()),
+            AttrBlock::new("wav.sample_rate", self.sample_rate.to_string()),
+            AttrBlock::new("wav.bits_per_sample", self.format.bits_per_sample().to_string()),
+        ];
+        if let Some(mask) = self.channel_mask {
+            attrs.push(AttrBlock::new("wav.channel_mask", mask.to_string()));
+        }
+        attrs
+    }
+
+    /// Reads the header recorded by `to_attrs`, if any.
+    pub fn from_metadata(metadata: &Metadata) -> Result<Option<Self>> {
+        fn parse<T: ::std::str::FromStr>(metadata: &Metadata, key: &str) -> Result<Option<T>> {
+            match metadata.get_attr(key) {
+                Some(v) => v.parse().map(Some)
+                    .map_err(|_| Error::Parse(format!("invalid {}: {}", key, v))),
+                None => Ok(None),
+            }
+        }
+        let (format, channels, sample_rate) = match (parse(metadata, "wav.format")?,
+                                                     parse(metadata, "wav.channels")?,
+                                                     parse(metadata, "wav.sample_rate")?) {
+            (Some(f), Some(c), Some(r)) => (f, c, r),
+            _ => { return Ok(None); },
+        };
+        WavSpec {
+            channels,
+            sample_rate,
+            format,
+            channel_mask: parse(metadata, "wav.channel_mask")?,
+        }.validated().map(Some)
+    }
+}
+
+fn unsupported(what: String) -> Error {
+    Error::Parse(format!("unsupported WAV file: {}", what))
+}
+
+/// Reads the frames of a WAV file as entries.
+#[derive(Debug)]
+pub struct WavReader<R: io::Read> {
+    input  : io::Take<R>,
+    spec   : WavSpec,
+    frames : u64,
+    frame  : u64,
+}
+
+impl<R: io::Read> WavReader<R> {
+    /// Reads the header up to the start of the samples.
+    pub fn new(mut input: R) -> Result<Self> {
+        let mut id = [0; 4];
+        input.read_exact(&mut id)?;
+        let _riff_size = input.read_u32::<LittleEndian>()?;
+        let mut wave = [0; 4];
+        input.read_exact(&mut wave)?;
+        if &id != b"RIFF" || &wave != b"WAVE" {
+            return Err(Error::Magic);
+        }
+
+        let mut spec = None;
+        loop {
+            input.read_exact(&mut id)?;
+            let size = input.read_u32::<LittleEndian>()? as u64;
+            match &id {
+                b"fmt " => {
+                    let mut chunk = Vec::new();
+                    input.by_ref().take(size + size % 2).read_to_end(&mut chunk)?;
+                    spec = Some(Self::parse_fmt(&chunk)?);
+                },
+                b"data" => {
+                    let spec = spec.ok_or_else(|| unsupported("no fmt chunk before data".to_string()))?;
+                    let frames = size / spec.block_align() as u64;
+                    return Ok(WavReader { input: input.take(size), spec, frames, frame: 0 });
+                },
+                _ => {
+                    io::copy(&mut input.by_ref().take(size + size % 2), &mut io::sink())?;
+                },
+            }
+        }
+    }
+
+    fn parse_fmt(mut chunk: &[u8]) -> Result<WavSpec> {
+        let mut tag = chunk.read_u16::<LittleEndian>()?;
+        let channels = chunk.read_u16::<LittleEndian>()?;
+        let sample_rate = chunk.read_u32::<LittleEndian>()?;
+        let _byte_rate = chunk.read_u32::<LittleEndian>()?;
+        let _block_align = chunk.read_u16::<LittleEndian>()?;
+        let bits = chunk.read_u16::<LittleEndian>()?;
+        let mut channel_mask = None;
+        if tag == FORMAT_EXTENSIBLE {
+            let _cb_size = chunk.read_u16::<LittleEndian>()?;
+            let _valid_bits = chunk.read_u16::<LittleEndian>()?;
+            channel_mask = Some(chunk.read_u32::<LittleEndian>()?);
+            tag = chunk.read_u16::<LittleEndian>()?;
+        }
+        let format = match (tag, bits) {
+            (FORMAT_PCM, 16) => SampleFormat::Pcm16,
+            (FORMAT_PCM, 24) => SampleFormat::Pcm24,
+            (FORMAT_FLOAT, 32) => SampleFormat::Float32,
+            _ => { return Err(unsupported(format!("format {} with {} bits per sample", tag, bits))); },
+        };
+        WavSpec { channels, sample_rate, format, channel_mask }.validated()
+    }
+
+    pub fn spec(&self) -> &WavSpec {
+        &self.spec
+    }
+
+    /// Number of frames in the file.
+    pub fn len(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames == 0
+    }
+
+    fn read_frame(&mut self) -> Result<Entry> {
+        let mut values = Vec::with_capacity(self.spec.channels as usize);
+        for _ in 0..self.spec.channels {
+            let x = match self.spec.format {
+                SampleFormat::Pcm16 => self.input.read_i16::<LittleEndian>()? as f64 / 32768.0,
+                SampleFormat::Pcm24 => self.input.read_i24::<LittleEndian>()? as f64 / 8_388_608.0,
+                SampleFormat::Float32 => self.input.read_f32::<LittleEndian>()? as f64,
+            };
+            values.push(x);
+        }
+        let index = self.frame as f64 / self.spec.sample_rate as f64;
+        self.frame += 1;
+        Ok((index, values))
+    }
+}
+
+impl<R: io::Read> Iterator for WavReader<R> {
+    type Item = Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.frame == self.frames {
+            return None;
+        }
+        let entry = self.read_frame();
+        if entry.is_err() {
+            self.frame = self.frames;
+        }
+        Some(entry)
+    }
+}
+
+/// Reads a WAV file into a series named `name`, returning the header too.
+pub fn read_wav<R: io::Read>(input: R, name: &str) -> Result<(TimeSeries, WavSpec)> {
+    let reader = WavReader::new(input)?;
+    let spec = reader.spec().clone();
+    let mut series = TimeSeries::new(name, spec.channels as u64);
+    for entry in reader {
+        let (index, values) = entry?;
+        series.push(index, values);
+    }
+    Ok((series, spec))
+}
+
+/// Writes `frames` entries as a WAV file; the indices are ignored.
+///
+/// Integer samples are clipped to `[-1, 1)`.
+pub fn write_wav<W, I>(out: &mut W, spec: &WavSpec, frames: u64, entries: I) -> Result<()>
+    where W: io::Write, I: Iterator<Item = Result<Entry>>
+{
+    let block_align = spec.block_align() as u64;
+    let byte_rate = spec.sample_rate as u64 * block_align;
+    if block_align > u16::MAX as u64 || byte_rate > u32::MAX as u64 {
+        return Err(unsupported(format!("{} channels at {} Hz", spec.channels, spec.sample_rate)));
+    }
+    let data_size = frames.checked_mul(block_align)
+        .ok_or_else(|| unsupported("the data exceed 4 GiB".to_string()))?;
+    let fmt_size: u32 = match (spec.channel_mask, spec.format) {
+        (Some(_), _) => 40,
+        (None, SampleFormat::Float32) => 18,
+        (None, _) => 16,
+    };
+    let riff_size = (4 + 8 + fmt_size as u64 + 8 + data_size % 2).saturating_add(data_size);
+    if riff_size > u32::MAX as u64 {
+        return Err(unsupported("the data exceed 4 GiB".to_string()));
+    }
+    let tag = match spec.format {
+        SampleFormat::Float32 => FORMAT_FLOAT,
+        _ => FORMAT_PCM,
+    };
+    let bits = spec.format.bits_per_sample();
+
+    out.write_all(b"RIFF")?;
+    out.write_u32::<LittleEndian>(riff_size as u32)?;
+    out.write_all(b"WAVE")?;
+    out.write_all(b"fmt ")?;
+    out.write_u32::<LittleEndian>(fmt_size)?;
+    out.write_u16::<LittleEndian>(if spec.channel_mask.is_some() { FORMAT_EXTENSIBLE } else { tag })?;
+    out.write_u16::<LittleEndian>(spec.channels)?;
+    out.write_u32::<LittleEndian>(spec.sample_rate)?;
+    out.write_u32::<LittleEndian>(byte_rate as u32)?;
+    out.write_u16::<LittleEndian>(block_align as u16)?;
+    out.write_u16::<LittleEndian>(bits)?;
+    if let Some(mask) = spec.channel_mask {
+        out.write_u16::<LittleEndian>(22)?;
+        out.write_u16::<LittleEndian>(bits)?;
+        out.write_u32::<LittleEndian>(mask)?;
+        out.write_u16::<LittleEndian>(tag)?;
+        out.write_all(&SUBFORMAT_GUID)?;
+    } else if fmt_size == 18 {
+        out.write_u16::<LittleEndian>(0)?;
+    }
+    out.write_all(b"data")?;
+    out.write_u32::<LittleEndian>(data_size as u32)?;
+
+    let mut count = 0;
+    for entry in entries {
+        let (_, values) = entry?;
+        if values.len() != spec.channels as usize || count == frames {
+            return Err(Error::Shape);
+        }
+        for &x in values.iter() {
+            match spec.format {
+                SampleFormat::Pcm16 => {
+                    out.write_i16::<LittleEndian>((x * 32768.0).round().clamp(-32768.0, 32767.0) as i16)?;
+                },
+                SampleFormat::Pcm24 => {
+                    let s = (x * 8_388_608.0).round().clamp(-8_388_608.0, 8_388_607.0);
+                    out.write_i24::<LittleEndian>(s as i32)?;
+                },
+                SampleFormat::Float32 => { out.write_f32::<LittleEndian>(x as f32)?; },
+            }
+        }
+        count += 1;
+    }
+    if count != frames {
+        return Err(Error::Shape);
+    }
+    if data_size % 2 == 1 {
+        out.write_u8(0)?;
+    }
+    Ok(())
+}
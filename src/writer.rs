@@ -1,32 +1,71 @@
 use std::io;
 use std::io::SeekFrom;
-use byteorder::{WriteBytesExt,LittleEndian};
-use header::{Header, BlockHeader, LogBlock, F64TSBlock};
-use error::Result;
+use std::time::{Duration, Instant};
+use header::{self, Header, BlockHeader, LogBlock, F64TSBlock, GroupBlock, AttrBlock, EventBlock};
+use error::{Result, Error};
+use record::Record;
+use file::Durable;
 
 
 #[derive(Debug)]
 pub struct Writer<W: io::Write> {
     stream: W,
+    position: u64,
+    alignment: u64,
 }
 
 impl<W: io::Write> Writer<W> {
     pub fn new(stream: W) -> Writer<W> {
         Writer {
-            stream: stream,
+            stream,
+            position: 0,
+            alignment: 1,
         }
     }
 
+    /// Pads f64ts block headers so that every payload starts at a multiple of
+    /// `alignment` bytes from the beginning of the stream.
+    ///
+    /// The padding is a log block in front of the f64ts block, which
+    /// `Reader` skips and older readers read as an ordinary log.
+    pub fn with_alignment(mut self, alignment: u64) -> Self {
+        assert!(alignment.is_power_of_two(), "alignment must be a power of two");
+        self.alignment = alignment;
+        self
+    }
+
+    pub fn alignment(&self) -> u64 {
+        self.alignment
+    }
+
+    /// Number of bytes written through this writer.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
     pub fn write_header(&mut self) -> Result<()> {
         let header = Header::new();
-        header.write_into(&mut self.stream)
+        header.write_into(&mut self.stream)?;
+        self.position += header.size() as u64;
+        Ok(())
     }
 
     pub fn write_log(&mut self, log: &LogBlock) -> Result<()> {
-        let header = BlockHeader::new("log", log.size() as u64);
-        header.write_into(&mut self.stream)?;
-        log.write_into(&mut self.stream)?;
-        Ok(())
+        self.write_block("log", |b| log.write_into(b))
+    }
+
+    /// Makes the blocks written after this belong to the group at `path`.
+    pub fn write_group(&mut self, path: &str) -> Result<()> {
+        let group = GroupBlock::new(path);
+        self.write_block("group", |b| group.write_into(b))
+    }
+
+    pub fn write_attr(&mut self, attr: &AttrBlock) -> Result<()> {
+        self.write_block("attr", |b| attr.write_into(b))
+    }
+
+    pub fn write_event(&mut self, event: &EventBlock) -> Result<()> {
+        self.write_block("event", |b| event.write_into(b))
     }
 
     pub fn stream_mut(&mut self) -> &mut W {
@@ -37,11 +76,36 @@ impl<W: io::Write> Writer<W> {
         self.stream
     }
 
+    fn write_block<F>(&mut self, name: &str, write_body: F) -> Result<()>
+        where F: FnOnce(&mut Vec<u8>) -> Result<()>
+    {
+        let buf = header::encode_block(name, write_body)?;
+        self.stream.write_all(&buf)?;
+        self.position += buf.len() as u64;
+        Ok(())
+    }
+
+    // Writes the padding, the block header and the f64ts block at `offset`
+    // from the beginning of the stream. Returns the number of bytes in front
+    // of the f64ts block.
+    fn write_f64ts_block(&mut self, block: &F64TSBlock, offset: u64) -> Result<u64> {
+        let padding = header::f64ts_padding(block, offset, self.alignment);
+        if padding > 0 {
+            let buf = header::encode_padding(padding)?;
+            self.stream.write_all(&buf)?;
+            self.position += buf.len() as u64;
+        }
+        self.write_block("f64ts", |b| block.write_into(b))?;
+        Ok(padding + BlockHeader::new("f64ts", 0).size() as u64)
+    }
+
+    /// Writes the f64ts block `block`, whose length must be set, and returns
+    /// a writer of its entries. The padding assumes that the stream started
+    /// at offset 0 with this writer; `write_f64ts_with_seek` asks the stream.
     pub fn write_f64ts(mut self, block: F64TSBlock) -> Result<F64TSWriter<W>> {
         assert!(block.length().is_some());
-        let header = BlockHeader::new("f64ts", block.size() as u64);
-        header.write_into(&mut self.stream)?;
-        block.write_into(&mut self.stream)?;
+        let offset = self.position;
+        self.write_f64ts_block(&block, offset)?;
         Ok(F64TSWriter {
             value_len: block.value_len() as usize,
             writer : self,
@@ -49,16 +113,17 @@ impl<W: io::Write> Writer<W> {
             block_pos: None,
             count: 0,
             finalized: true,
+            checkpoints: CheckpointPolicy::new(),
+            checkpoint_fn: None,
+            checkpointed: (0, Instant::now()),
         })
     }
 }
 
 impl<W: io::Write + io::Seek> Writer<W> {
     pub fn write_f64ts_with_seek(mut self, block: F64TSBlock) -> Result<F64TSWriter<W>> {
-        let header = BlockHeader::new("f64ts", block.size() as u64);
-        header.write_into(&mut self.stream)?;
-        let block_pos = self.stream.seek(SeekFrom::Current(0))?;
-        block.write_into(&mut self.stream)?;
+        let start = self.stream.stream_position()?;
+        let block_pos = start + self.write_f64ts_block(&block, start)?;
         Ok(F64TSWriter {
             value_len: block.value_len() as usize,
             writer: self,
@@ -66,10 +131,54 @@ impl<W: io::Write + io::Seek> Writer<W> {
             block_pos: Some(block_pos),
             count: 0,
             finalized: false,
+            checkpoints: CheckpointPolicy::new(),
+            checkpoint_fn: None,
+            checkpointed: (0, Instant::now()),
         })
     }
 }
 
+/// When a `F64TSWriter` checkpoints: after a number of entries, after some
+/// time, or both, and whether the data is synced to the disk.
+#[derive(Debug,Clone,Copy,PartialEq,Default)]
+pub struct CheckpointPolicy {
+    rows     : Option<u64>,
+    interval : Option<Duration>,
+    sync     : bool,
+}
+
+impl CheckpointPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checkpoints after every `rows` entries.
+    pub fn every_rows(mut self, rows: u64) -> Self {
+        assert!(rows > 0);
+        self.rows = Some(rows);
+        self
+    }
+
+    /// Checkpoints on the first entry written `interval` or more after the
+    /// last checkpoint.
+    pub fn every(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Syncs the data to the disk on each checkpoint.
+    pub fn sync(mut self, sync: bool) -> Self {
+        self.sync = sync;
+        self
+    }
+
+    fn is_due(&self, rows: u64, since: Instant) -> bool {
+        self.rows.is_some_and(|n| rows >= n) || self.interval.is_some_and(|t| since.elapsed() >= t)
+    }
+}
+
+type CheckpointFn<W> = fn(&mut F64TSWriter<W>) -> Result<()>;
+
 #[derive(Debug)]
 pub struct F64TSWriter<W> where W: io::Write {
     value_len : usize,
@@ -78,6 +187,11 @@ pub struct F64TSWriter<W> where W: io::Write {
     block_pos : Option<u64>,
     count : u64,
     finalized : bool,
+    checkpoints : CheckpointPolicy,
+    // Set by `with_checkpoints`, where the stream is known to be seekable.
+    checkpoint_fn : Option<CheckpointFn<W>>,
+    // The count and the time of the last checkpoint.
+    checkpointed : (u64, Instant),
 }
 
 impl<W> F64TSWriter<W> where W: io::Write {
@@ -85,15 +199,43 @@ impl<W> F64TSWriter<W> where W: io::Write {
         self.writer.stream_mut()
     }
 
+    pub fn value_len(&self) -> usize {
+        self.value_len
+    }
+
+    /// Number of entries written.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Number of bytes written through the underlying writer.
+    pub fn position(&self) -> u64 {
+        self.writer.position()
+    }
+
+    /// Writes an entry. Fails with `Error::Shape` if `values` is not
+    /// `value_len` long.
     pub fn write_entry(&mut self, index: f64, values: &[f64]) -> Result<()> {
-        let _ = self.stream_mut().write_f64::<LittleEndian>(index)?;
-        for &x in values.iter() {
-            let _ = self.stream_mut().write_f64::<LittleEndian>(x)?;
+        if values.len() != self.value_len {
+            return Err(Error::Shape);
+        }
+        header::write_entry_into(self.stream_mut(), index, values)?;
+        self.count += 1;
+        self.writer.position += 8 * (1 + values.len() as u64);
+        if let Some(checkpoint) = self.checkpoint_fn {
+            if self.checkpoints.is_due(self.count - self.checkpointed.0, self.checkpointed.1) {
+                checkpoint(self)?;
+            }
         }
-        self.count = self.count + 1;
         Ok(())
     }
 
+    /// Writes a record as an entry.
+    pub fn write_record<T: Record>(&mut self, record: &T) -> Result<()> {
+        let (index, values) = record.to_entry();
+        self.write_entry(index, &values)
+    }
+
     pub fn finish(self) -> Writer<W> {
         assert!(self.finalized, "fn finalize() must be called");
         self.writer
@@ -102,19 +244,54 @@ impl<W> F64TSWriter<W> where W: io::Write {
 
 impl<W> F64TSWriter<W> where W: io::Write + io::Seek {
     pub fn finalize(mut self) -> Result<Self> {
+        self.write_length()?;
+        self.finalized = true;
+        Ok(self)
+    }
+
+    // Rewrites the length of the block with the number of entries written.
+    fn write_length(&mut self) -> Result<()> {
         let block_pos = self.block_pos.unwrap();
-        let mut block_header = self.block_header.clone();
         let count = self.count;
-        {
-            let stream = self.stream_mut();
-            let current = stream.seek(SeekFrom::Current(0))?;
-            stream.seek(SeekFrom::Start(block_pos))?;
-            block_header.set_length(count);
-            block_header.write_into(stream)?;
-            stream.seek(SeekFrom::Start(current))?;
+        self.block_header.set_length(count);
+        let stream = self.writer.stream_mut();
+        let current = stream.stream_position()?;
+        stream.seek(SeekFrom::Start(block_pos))?;
+        self.block_header.write_into(stream)?;
+        stream.seek(SeekFrom::Start(current))?;
+        Ok(())
+    }
+}
+
+impl<W> F64TSWriter<W> where W: io::Write + io::Seek + Durable {
+    /// Makes `write_entry` checkpoint according to `policy`.
+    pub fn with_checkpoints(mut self, policy: CheckpointPolicy) -> Self {
+        self.checkpoints = policy;
+        self.checkpoint_fn = Some(Self::checkpoint);
+        self
+    }
+
+    /// Flushes the entries written so far and records their number in the
+    /// block, so that they are read back even if the writer never finishes.
+    /// With `CheckpointPolicy::sync`, the entries reach the disk before the
+    /// length does.
+    ///
+    /// After a crash, the entries written since the last checkpoint follow
+    /// the block as garbage, which `File::recover` removes.
+    pub fn checkpoint(&mut self) -> Result<()> {
+        let sync = self.checkpoints.sync;
+        self.stream_mut().flush()?;
+        if sync {
+            self.stream_mut().sync_data()?;
         }
-        self.block_header = block_header;
-        self.finalized = true;
-        Ok(self)
+        if self.block_pos.is_some() {
+            self.write_length()?;
+            self.stream_mut().flush()?;
+            if sync {
+                self.stream_mut().sync_data()?;
+            }
+        }
+        self.checkpointed = (self.count, Instant::now());
+        Ok(())
     }
 }
@@ -0,0 +1,155 @@
+#![cfg(feature = "tokio")]
+extern crate broto;
+extern crate tokio;
+
+use std::io::Cursor;
+use std::time::Duration;
+
+use broto::{Block, F64TSBlockBuilder, LogBlockBuilder, Writer};
+use broto::async_io::{AsyncReader, AsyncWriter};
+
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread().build().unwrap()
+}
+
+fn log() -> broto::LogBlock {
+    LogBlockBuilder::new().program("daq").info("start").time(Duration::from_secs(1)).build()
+}
+
+fn block(length: Option<u64>) -> broto::F64TSBlock {
+    let builder = F64TSBlockBuilder::new().index_len(1).value_len(2).name("accel");
+    match length {
+        Some(length) => builder.length(length).build(),
+        None => builder.build(),
+    }
+}
+
+// The same file written by the blocking writer.
+fn expected(alignment: u64) -> Vec<u8> {
+    let mut writer = Writer::new(Cursor::new(Vec::new())).with_alignment(alignment);
+    writer.write_header().unwrap();
+    writer.write_log(&log()).unwrap();
+    writer.write_group("/imu").unwrap();
+    let mut w = writer.write_f64ts_with_seek(block(None)).unwrap();
+    for i in 0..1000 {
+        w.write_entry(i as f64, &[i as f64, -(i as f64)]).unwrap();
+    }
+    w.finalize().unwrap().finish().into_stream().into_inner()
+}
+
+#[test]
+fn test_async_writer() {
+    let rt = runtime();
+    let mut writer = AsyncWriter::new(Cursor::new(Vec::new())).with_alignment(64);
+    rt.block_on(writer.write_header()).unwrap();
+    rt.block_on(writer.write_log(&log())).unwrap();
+    rt.block_on(writer.write_group("/imu")).unwrap();
+    let mut w = rt.block_on(writer.write_f64ts_with_seek(block(None))).unwrap();
+    for i in 0..1000 {
+        rt.block_on(w.write_entry(i as f64, &[i as f64, -(i as f64)])).unwrap();
+    }
+    assert_eq!(w.count(), 1000);
+    match rt.block_on(w.write_entry(0.0, &[1.0])) {
+        Err(broto::Error::Shape) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+    assert_eq!(w.count(), 1000);
+    let mut writer = rt.block_on(w.finalize()).unwrap().finish();
+    rt.block_on(writer.flush()).unwrap();
+    let buf = writer.into_stream().into_inner();
+    assert_eq!(buf, expected(64));
+
+    let dataset = broto::load(Cursor::new(buf)).unwrap();
+    assert_eq!(dataset.series("/imu/accel").unwrap().entries().len(), 1000);
+}
+
+#[test]
+fn test_async_writer_appended() {
+    use std::io::{Seek, SeekFrom};
+
+    let mut cur = Cursor::new(vec![0; 13]);
+    cur.seek(SeekFrom::End(0)).unwrap();
+    let rt = runtime();
+    let mut writer = AsyncWriter::new(cur).with_alignment(64);
+    rt.block_on(writer.write_header()).unwrap();
+    let mut w = rt.block_on(writer.write_f64ts_with_seek(block(None))).unwrap();
+    rt.block_on(w.write_entry(1.0, &[2.0, 3.0])).unwrap();
+    let mut writer = rt.block_on(w.finalize()).unwrap().finish();
+    rt.block_on(writer.flush()).unwrap();
+    let buf = writer.into_stream().into_inner();
+    assert_eq!(buf.len() % 64, 24);
+
+    let dataset = broto::load(Cursor::new(&buf[13..])).unwrap();
+    assert_eq!(dataset.series("accel").unwrap().entries(), vec![(1.0, vec![2.0, 3.0])]);
+}
+
+#[test]
+fn test_async_reader() {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    let mut w = writer.write_f64ts(block(Some(3))).unwrap();
+    for i in 0..3 {
+        w.write_entry(i as f64, &[1.0, 2.0]).unwrap();
+    }
+    let mut buf = w.finish().into_stream().into_inner();
+    buf.extend_from_slice(&expected(1)[56..]);
+
+    let rt = runtime();
+    let mut reader = AsyncReader::new(Cursor::new(buf));
+    rt.block_on(reader.initialize()).unwrap();
+    assert!(reader.header().is_some());
+
+    let data = match rt.block_on(reader.next_block()).unwrap() {
+        Block::F64TS(data) => data,
+        block => panic!("unexpected block: {:?}", block),
+    };
+    let mut entries = reader.f64ts_entries(&data);
+    let mut n = 0;
+    while let Some(entry) = rt.block_on(entries.next_entry()) {
+        assert_eq!(entry.unwrap(), (n as f64, vec![1.0, 2.0]));
+        n += 1;
+    }
+    assert_eq!(n, 3);
+
+    match rt.block_on(reader.next_block()).unwrap() {
+        Block::Log(l) => assert_eq!(l, log()),
+        block => panic!("unexpected block: {:?}", block),
+    }
+    match rt.block_on(reader.next_block()).unwrap() {
+        Block::Group(_) => assert_eq!(reader.current_group(), "/imu"),
+        block => panic!("unexpected block: {:?}", block),
+    }
+    let data = match rt.block_on(reader.next_block()).unwrap() {
+        Block::F64TS(data) => data,
+        block => panic!("unexpected block: {:?}", block),
+    };
+    assert_eq!(data.length(), Some(1000));
+    let mut entries = reader.f64ts_entries(&data);
+    let mut last = None;
+    while let Some(entry) = rt.block_on(entries.next_entry()) {
+        last = Some(entry.unwrap());
+    }
+    assert_eq!(last, Some((999.0, vec![999.0, -999.0])));
+    match rt.block_on(reader.next_block()) {
+        Err(broto::Error::EndOfFile) => {},
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn test_async_reader_block_size() {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    writer.write_log(&log()).unwrap();
+    let mut buf = writer.into_stream().into_inner();
+    // The size of the log block.
+    buf[56 + 19..56 + 27].copy_from_slice(&u64::MAX.to_le_bytes());
+
+    let rt = runtime();
+    let mut reader = AsyncReader::new(Cursor::new(buf));
+    rt.block_on(reader.initialize()).unwrap();
+    match rt.block_on(reader.next_block()) {
+        Err(broto::Error::Parse(_)) => {},
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
@@ -0,0 +1,120 @@
+#![cfg(feature = "background")]
+extern crate broto;
+
+use std::io::{self, Cursor, Write};
+use std::sync::{Arc, Condvar, Mutex};
+
+use broto::{F64TSBlockBuilder, F64TSWriter, Error, Writer};
+use broto::background::{BackgroundWriter, Backpressure};
+
+// A stream whose writes wait while the gate is closed, like a stalled disk.
+struct Gate {
+    buf   : Vec<u8>,
+    state : Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl Write for Gate {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let (ref open, ref cond) = *self.state;
+        let mut open = open.lock().unwrap();
+        while !*open {
+            open = cond.wait(open).unwrap();
+        }
+        self.buf.write(data)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn set_gate(state: &Arc<(Mutex<bool>, Condvar)>, value: bool) {
+    *state.0.lock().unwrap() = value;
+    state.1.notify_all();
+}
+
+fn gated() -> (F64TSWriter<Gate>, Arc<(Mutex<bool>, Condvar)>) {
+    let state = Arc::new((Mutex::new(true), Condvar::new()));
+    let mut writer = Writer::new(Gate { buf: Vec::new(), state: state.clone() });
+    writer.write_header().unwrap();
+    let block = F64TSBlockBuilder::new().index_len(1).value_len(1).length(0).build();
+    let w = writer.write_f64ts(block).unwrap();
+    set_gate(&state, false);
+    (w, state)
+}
+
+#[test]
+fn test_background_block() {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    let block = F64TSBlockBuilder::new().index_len(1).value_len(2).name("accel").build();
+    let w = BackgroundWriter::new(writer.write_f64ts_with_seek(block).unwrap(), 16, Backpressure::Block).unwrap();
+    for i in 0..10000 {
+        let x = i as f64;
+        w.write_entry(x, &[x, -x]).unwrap();
+    }
+    assert!(w.high_water() <= 16);
+    assert_eq!(w.dropped(), 0);
+    let buf = w.finish().unwrap().finalize().unwrap().finish().into_stream().into_inner();
+
+    let dataset = broto::load(Cursor::new(buf)).unwrap();
+    let entries = dataset.series("accel").unwrap().entries();
+    assert_eq!(entries.len(), 10000);
+    assert!(entries.iter().enumerate().all(|(i, e)| e.0 == i as f64 && e.1 == vec![i as f64, -(i as f64)]));
+}
+
+#[test]
+fn test_background_drop_oldest() {
+    let (w, gate) = gated();
+    let w = BackgroundWriter::new(w, 4, Backpressure::DropOldest).unwrap();
+    for i in 0..20 {
+        w.write_entry(i as f64, &[0.0]).unwrap();
+    }
+    assert_eq!(w.high_water(), 4);
+    assert!(w.dropped() >= 15);
+    set_gate(&gate, true);
+    let dropped = w.dropped();
+    let w = w.finish().unwrap();
+    assert_eq!(w.count() + dropped, 20);
+    // The newest entries are kept.
+    let buf = &w.finish().into_stream().buf;
+    let last = &buf[buf.len() - 16..buf.len() - 8];
+    assert_eq!(last, &19.0f64.to_le_bytes());
+}
+
+#[test]
+fn test_background_error() {
+    let (w, gate) = gated();
+    let w = BackgroundWriter::new(w, 2, Backpressure::Error).unwrap();
+    let mut errors = 0;
+    for i in 0..10 {
+        match w.write_entry(i as f64, &[0.0]) {
+            Ok(()) => {},
+            Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock => { errors += 1; },
+            Err(e) => panic!("unexpected error: {:?}", e),
+        }
+    }
+    assert!(errors >= 7);
+    assert_eq!(w.dropped(), errors);
+    set_gate(&gate, true);
+    assert_eq!(w.finish().unwrap().count() + errors, 10);
+}
+
+#[test]
+fn test_background_invalid() {
+    let (w, gate) = gated();
+    set_gate(&gate, true);
+    match BackgroundWriter::new(w, 0, Backpressure::Block) {
+        Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::InvalidInput => {},
+        r => panic!("unexpected result: {:?}", r.map(|_| ())),
+    }
+
+    let (w, gate) = gated();
+    set_gate(&gate, true);
+    let w = BackgroundWriter::new(w, 4, Backpressure::Block).unwrap();
+    match w.write_entry(0.0, &[0.0, 1.0]) {
+        Err(Error::Shape) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+    assert_eq!(w.finish().unwrap().count(), 0);
+}
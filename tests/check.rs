@@ -0,0 +1,161 @@
+extern crate broto;
+
+use std::io::Cursor;
+use std::time::Duration;
+
+use broto::{Writer, LogBlockBuilder, LogBlock, BlockHeader, F64TSBlockBuilder};
+use broto::check::{self, Issue, Problem};
+
+fn log() -> LogBlock {
+    LogBlockBuilder::new()
+        .program("broto")
+        .info("creation")
+        .time(Duration::new(1_500_000_000, 0))
+        .build()
+}
+
+// Returns a file with a log and a series of four rows, and the offsets of
+// the log block, the series block and its entries.
+fn sample(entries: &[(f64, f64)]) -> (Vec<u8>, u64, u64, u64) {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    let log_pos = writer.position();
+    writer.write_log(&log()).unwrap();
+    let fts_pos = writer.position();
+    let block = F64TSBlockBuilder::new().index_len(1).value_len(1).name("x").build();
+    let mut fts = writer.write_f64ts_with_seek(block).unwrap();
+    let data_pos = fts.stream_mut().position();
+    for &(index, value) in entries {
+        fts.write_entry(index, &[value]).unwrap();
+    }
+    (fts.finalize().unwrap().finish().into_stream().into_inner(), log_pos, fts_pos, data_pos)
+}
+
+fn issues(buf: Vec<u8>) -> Vec<Issue> {
+    check::check(Cursor::new(buf)).unwrap().issues().to_vec()
+}
+
+#[test]
+fn test_check_valid() {
+    let (buf, _, _, data_pos) = sample(&[(0.0, 1.0), (1.0, f64::NAN), (0.5, 2.0), (2.0, f64::INFINITY)]);
+    let report = check::check(Cursor::new(buf)).unwrap();
+    assert_eq!((report.blocks(), report.series(), report.rows()), (2, 1, 4));
+    assert!(report.is_ok());
+    assert_eq!(report.issues(), &[
+        Issue { offset: data_pos + 32, problem: Problem::NonMonotonicIndex { row: 2, previous: 1.0, index: 0.5, count: 1 } },
+        Issue { offset: data_pos + 24, problem: Problem::NonFinite { row: 1, column: 1, count: 2 } },
+    ]);
+
+    let (buf, _, _, _) = sample(&[(0.0, 1.0), (0.0, 2.0)]);
+    assert_eq!(issues(buf), vec![]);
+}
+
+#[test]
+fn test_check_corrupt() {
+    let (clean, log_pos, fts_pos, data_pos) = sample(&[(0.0, 1.0), (1.0, 2.0)]);
+    let end = clean.len() as u64;
+
+    let mut buf = clean.clone();
+    buf.extend_from_slice(b"junk");
+    assert_eq!(issues(buf), vec![Issue { offset: end, problem: Problem::TrailingGarbage(4) }]);
+
+    let mut buf = clean.clone();
+    buf[log_pos as usize] = b'X';
+    assert_eq!(issues(buf), vec![Issue { offset: log_pos, problem: Problem::BlockMagic { skipped: fts_pos - log_pos } }]);
+
+    let mut buf = clean.clone();
+    buf.truncate(end as usize - 4);
+    assert_eq!(issues(buf), vec![Issue { offset: data_pos, problem: Problem::PayloadSize { expected: 32, available: 28 } }]);
+
+    let mut buf = clean.clone();
+    buf[0] = b'B';
+    assert_eq!(issues(buf), vec![Issue { offset: 0, problem: Problem::Magic }]);
+
+    // "broto" is the program name, stored after the time.
+    let mut buf = clean.clone();
+    let program = log_pos as usize + buf[log_pos as usize..].windows(5).position(|w| w == b"broto").unwrap();
+    buf[program] = 0xff;
+    assert_eq!(issues(buf), vec![Issue { offset: log_pos, problem: Problem::InvalidUtf8 }]);
+}
+
+#[test]
+fn test_check_unfinalized() {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    let block = F64TSBlockBuilder::new().index_len(1).value_len(1).build();
+    let mut fts = writer.write_f64ts_with_seek(block).unwrap();
+    let data_pos = fts.stream_mut().position();
+    for i in 0..3 {
+        fts.write_entry(i as f64, &[0.0]).unwrap();
+    }
+    let mut buf = fts.finalize().unwrap().finish().into_stream().into_inner();
+    // As left by a writer which stopped before finalizing the length, the
+    // last field of the block.
+    buf[data_pos as usize - 8..data_pos as usize].copy_from_slice(&[0; 8]);
+    assert_eq!(issues(buf), vec![Issue { offset: data_pos, problem: Problem::Unfinalized { available: 48 } }]);
+}
+
+#[test]
+fn test_check_block_size() {
+    let mut buf = Vec::new();
+    broto::Header::new().write_into(&mut buf).unwrap();
+    let log = log();
+    let header = BlockHeader::new("log", log.size() as u64 + 8);
+    header.write_into(&mut buf).unwrap();
+    log.write_into(&mut buf).unwrap();
+    buf.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+    let short = buf.len() as u64;
+    BlockHeader::new("log", log.size() as u64 - 1).write_into(&mut buf).unwrap();
+    log.write_into(&mut buf).unwrap();
+    let undefined = buf.len() as u64;
+    BlockHeader::new("frob", 3).write_into(&mut buf).unwrap();
+    buf.extend_from_slice(b"abc");
+
+    assert_eq!(issues(buf), vec![
+        Issue { offset: 56, problem: Problem::BlockSize { declared: log.size() as u64 + 8, consumed: log.size() as u64 } },
+        Issue { offset: short, problem: Problem::BlockOverrun { declared: log.size() as u64 - 1 } },
+        // The last byte of the log read as the next block.
+        Issue { offset: undefined - 1, problem: Problem::BlockMagic { skipped: 1 } },
+        Issue { offset: undefined, problem: Problem::UndefinedBlock("frob".to_string()) },
+    ]);
+}
+
+#[test]
+fn test_check_overflow() {
+    // A f64ts block whose entries have u64::MAX + 1 values.
+    let mut buf = Vec::new();
+    broto::Header::new().write_into(&mut buf).unwrap();
+    BlockHeader::new("f64ts", 24).write_into(&mut buf).unwrap();
+    for &n in [u64::MAX, 1, 1].iter() {
+        buf.extend_from_slice(&n.to_le_bytes());
+    }
+    let payload = buf.len() as u64;
+    buf.extend_from_slice(&[0; 16]);
+    assert_eq!(issues(buf), vec![
+        Issue { offset: payload, problem: Problem::PayloadSize { expected: u64::MAX, available: 16 } },
+    ]);
+
+    // A log whose time has more than a second of nanoseconds.
+    let mut buf = Vec::new();
+    broto::Header::new().write_into(&mut buf).unwrap();
+    BlockHeader::new("log", 28).write_into(&mut buf).unwrap();
+    buf.extend_from_slice(&u64::MAX.to_le_bytes());
+    buf.extend_from_slice(&2_000_000_000u32.to_le_bytes());
+    buf.extend_from_slice(&[0; 16]);
+    let report = check::check(Cursor::new(buf)).unwrap();
+    match report.issues() {
+        [Issue { offset: 56, problem: Problem::InvalidField(_) }] => {},
+        issues => panic!("unexpected issues: {:?}", issues),
+    }
+}
+
+#[test]
+fn test_check_version() {
+    let (mut buf, _, _, _) = sample(&[(0.0, 1.0)]);
+    buf[20..24].copy_from_slice(&99u32.to_le_bytes());
+    let report = check::check(Cursor::new(buf)).unwrap();
+    match report.issues() {
+        [Issue { offset: 16, problem: Problem::InvalidField(_) }] => {},
+        issues => panic!("unexpected issues: {:?}", issues),
+    }
+}
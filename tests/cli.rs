@@ -0,0 +1,304 @@
+extern crate broto;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{self, Command, Output};
+
+use broto::{Dataset, TimeSeries, LogBlockBuilder};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("broto-cli-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn sample_dataset() -> Dataset {
+    let mut dataset = Dataset::new();
+    let log = LogBlockBuilder::new()
+        .program("broto")
+        .info("creation")
+        .time(std::time::Duration::new(1_500_000_000, 0))
+        .build();
+    dataset.metadata_mut().get_logs_mut().push(log);
+    let accel = dataset.add_series(TimeSeries::new("accel", 2).with_group("/run1"));
+    for i in 0..20 {
+        accel.push(i as f64, vec![i as f64, -(i as f64)]);
+    }
+    dataset
+}
+
+fn write_sample(path: &PathBuf) {
+    let file = fs::File::create(path).unwrap();
+    broto::save(file, &sample_dataset()).unwrap();
+}
+
+fn broto(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_broto")).args(args).output().unwrap()
+}
+
+#[test]
+fn test_cli_info_dump() {
+    let dir = temp_dir("info");
+    let path = dir.join("sample.broto");
+    write_sample(&path);
+    let path = path.to_str().unwrap();
+
+    let out = broto(&["info", path]);
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("broto format 0.2"));
+    assert!(stdout.contains("2017-07-14T02:40:00Z broto: creation"));
+    assert!(stdout.contains("accel (id 0): 1 index + 2 values, 20 rows"));
+
+    let out = broto(&["dump", "-n", "2", path]);
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("series /run1/accel (2 values x 20 rows)"));
+    assert!(stdout.contains("  1\t1\t-1\n  ... 16 rows ...\n  18\t18\t-18\n"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_cli_dump_groups_help() {
+    let dir = temp_dir("groups");
+    let path = dir.join("groups.broto");
+    let mut dataset = Dataset::new();
+    dataset.group_mut("/run3").set_attr("operator", "kim");
+    dataset.add_series(TimeSeries::new("sensor1", 1).with_group("/run3/sensor1")).push(0.0, vec![1.0]);
+    broto::save(fs::File::create(&path).unwrap(), &dataset).unwrap();
+    let path = path.to_str().unwrap();
+
+    let out = broto(&["dump", path]);
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("group /run3\n  attr  operator = kim\n"));
+
+    let out = broto(&["dump", "--help"]);
+    assert!(out.status.success());
+    assert!(String::from_utf8(out.stdout).unwrap().starts_with("usage: broto dump"));
+    // Elsewhere -h is an argument like any other.
+    let out = broto(&["tail", "--series", "-h", path]);
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "");
+    assert_eq!(broto(&["dump", path, "-h"]).status.code(), Some(2));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_cli_exit_codes() {
+    let dir = temp_dir("exit");
+    let path = dir.join("garbage.broto");
+    fs::write(&path, b"not a broto file at all").unwrap();
+
+    assert_eq!(broto(&["info", path.to_str().unwrap()]).status.code(), Some(5));
+    assert_eq!(broto(&["info", dir.join("missing").to_str().unwrap()]).status.code(), Some(3));
+    assert_eq!(broto(&["frobnicate"]).status.code(), Some(2));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_cli_csv() {
+    let dir = temp_dir("csv");
+    let input = dir.join("weather.csv");
+    fs::write(&input, "time,temp,humidity\n0,20.5,40\n1,20.75,41\n").unwrap();
+    let file = dir.join("weather.broto");
+    let output = dir.join("weather.tsv");
+
+    let out = broto(&["csv", "import", "--group", "/station1", "-o",
+                      file.to_str().unwrap(), input.to_str().unwrap()]);
+    assert!(out.status.success());
+    let dataset = broto::load(fs::File::open(&file).unwrap()).unwrap();
+    let series = dataset.series("/station1/weather").unwrap();
+    assert_eq!(series.channel_names(), &["temp".to_string(), "humidity".to_string()]);
+    assert_eq!(series.entries()[1], (1.0, vec![20.75, 41.0]));
+
+    let out = broto(&["csv", "export", "--tsv", "-o", output.to_str().unwrap(),
+                      file.to_str().unwrap()]);
+    assert!(out.status.success());
+    assert_eq!(fs::read_to_string(&output).unwrap(),
+               "index\ttemp\thumidity\n0\t20.5\t40\n1\t20.75\t41\n");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_cli_npy() {
+    let dir = temp_dir("npy");
+    let path = dir.join("sample.broto");
+    write_sample(&path);
+    let npz = dir.join("sample.npz");
+    let npy = dir.join("accel.npy");
+    let imported = dir.join("imported.broto");
+
+    let out = broto(&["npy", "export", "-o", npz.to_str().unwrap(), path.to_str().unwrap()]);
+    assert!(out.status.success());
+    let out = broto(&["npy", "import", "-o", imported.to_str().unwrap(), npz.to_str().unwrap()]);
+    assert!(out.status.success());
+    let dataset = broto::load(fs::File::open(&imported).unwrap()).unwrap();
+    assert_eq!(dataset.series("/run1/accel").unwrap().entries(),
+               sample_dataset().series("accel").unwrap().entries());
+
+    let out = broto(&["npy", "export", "-o", npy.to_str().unwrap(), path.to_str().unwrap()]);
+    assert!(out.status.success());
+    let out = broto(&["npy", "import", "--group", "/g", "-o", imported.to_str().unwrap(),
+                      npy.to_str().unwrap()]);
+    assert!(out.status.success());
+    let dataset = broto::load(fs::File::open(&imported).unwrap()).unwrap();
+    assert_eq!(dataset.series("/g/accel").unwrap().entries()[3], (3.0, vec![3.0, -3.0]));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_cli_wav() {
+    use broto::wav::{SampleFormat, WavSpec};
+
+    let dir = temp_dir("wav");
+    let input = dir.join("mic.wav");
+    let file = dir.join("mic.broto");
+    let output = dir.join("out.wav");
+    let spec = WavSpec::new(2, 44100, SampleFormat::Pcm24);
+    let entries = (0..50).map(|n| Ok((0.0, vec![n as f64 / 64.0, -(n as f64) / 64.0])));
+    let mut wav = Vec::new();
+    broto::wav::write_wav(&mut wav, &spec, 50, entries).unwrap();
+    fs::write(&input, &wav).unwrap();
+
+    let out = broto(&["wav", "import", "--group", "/mics", "-o", file.to_str().unwrap(),
+                      input.to_str().unwrap()]);
+    assert!(out.status.success());
+    let dataset = broto::load(fs::File::open(&file).unwrap()).unwrap();
+    assert_eq!(dataset.group("/mics").unwrap().get_attr("wav.sample_rate"), Some("44100"));
+    assert_eq!(dataset.series("/mics/mic").unwrap().len(), 50);
+
+    let out = broto(&["wav", "export", "-o", output.to_str().unwrap(), file.to_str().unwrap()]);
+    assert!(out.status.success());
+    assert_eq!(fs::read(&output).unwrap(), wav);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_cli_json() {
+    let dir = temp_dir("json");
+    let path = dir.join("sample.broto");
+    write_sample(&path);
+
+    let out = broto(&["json", path.to_str().unwrap()]);
+    assert!(out.status.success());
+    let doc = String::from_utf8(out.stdout).unwrap();
+    assert!(doc.contains(r#""logs":[{"time":"2017-07-14T02:40:00Z","program":"broto","info":"creation"}]"#));
+    assert!(doc.contains(r#""path":"/run1/accel""#));
+
+    let out = broto(&["json", "rows", "--series", "accel", path.to_str().unwrap()]);
+    assert!(out.status.success());
+    let rows = String::from_utf8(out.stdout).unwrap();
+    assert_eq!(rows.lines().count(), 20);
+    assert_eq!(rows.lines().nth(2), Some(r#"{"series":"/run1/accel","index":2,"values":[2,-2]}"#));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_cli_check() {
+    let dir = temp_dir("check");
+    let good = dir.join("good.broto");
+    let bad = dir.join("bad.broto");
+    write_sample(&good);
+    let mut buf = fs::read(&good).unwrap();
+    let len = buf.len();
+    buf.extend_from_slice(b"junk");
+    fs::write(&bad, &buf).unwrap();
+
+    let out = broto(&["check", good.to_str().unwrap()]);
+    assert!(out.status.success());
+    assert!(String::from_utf8(out.stdout).unwrap().ends_with("0 errors, 0 warnings\n"));
+
+    let out = broto(&["check", "--quiet", good.to_str().unwrap(), bad.to_str().unwrap()]);
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(String::from_utf8(out.stdout).unwrap(),
+               format!("{}: error: offset {}: 4 bytes of trailing garbage\n", bad.to_str().unwrap(), len));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_cli_merge() {
+    let dir = temp_dir("merge");
+    let a = dir.join("a.broto");
+    let out = dir.join("out.broto");
+    write_sample(&a);
+
+    let status = broto(&["merge", "--duplicates", "error", "-o", out.to_str().unwrap(),
+                         a.to_str().unwrap(), a.to_str().unwrap()]).status;
+    assert_eq!(status.code(), Some(9));
+    let status = broto(&["merge", "-o", out.to_str().unwrap(), a.to_str().unwrap(), a.to_str().unwrap()]).status;
+    assert!(status.success());
+    let dataset = broto::load(fs::File::open(&out).unwrap()).unwrap();
+    assert_eq!(dataset.series("/run1/accel").unwrap().entries(),
+               sample_dataset().series("accel").unwrap().entries());
+    let logs = dataset.metadata().get_logs();
+    assert_eq!(logs.len(), 2);
+    assert_eq!(logs[1].program(), "broto merge");
+
+    // Files joined with cat.
+    let mut joined = fs::read(&a).unwrap();
+    joined.extend(fs::read(&a).unwrap());
+    let joined_path = dir.join("joined.broto");
+    fs::write(&joined_path, joined).unwrap();
+    assert_eq!(broto(&["dump", joined_path.to_str().unwrap()]).status.code(), Some(5));
+    let out = broto(&["dump", "--segments", joined_path.to_str().unwrap()]);
+    assert!(out.status.success());
+    assert!(String::from_utf8(out.stdout).unwrap().contains("series /run1/accel (2 values x 20 rows)"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_cli_slice_split() {
+    let dir = temp_dir("split");
+    let path = dir.join("sample.broto");
+    let sliced = dir.join("sliced.broto");
+    write_sample(&path);
+
+    let out = broto(&["slice", "--from", "5", "--to", "1970-01-01T00:00:08Z", "-o", sliced.to_str().unwrap(),
+                      path.to_str().unwrap()]);
+    assert!(out.status.success());
+    let dataset = broto::load(fs::File::open(&sliced).unwrap()).unwrap();
+    let entries = dataset.series("/run1/accel").unwrap().entries();
+    assert_eq!(entries.iter().map(|e| e.0).collect::<Vec<_>>(), vec![5.0, 6.0, 7.0]);
+    assert_eq!(dataset.metadata().get_logs()[1].info(),
+               format!("index [5, 1970-01-01T00:00:08Z) of {}", path.to_str().unwrap()));
+
+    let pattern = dir.join("part-{}.broto");
+    let out = broto(&["split", "--rows", "8", "-o", pattern.to_str().unwrap(), path.to_str().unwrap()]);
+    assert!(out.status.success());
+    let lengths: Vec<usize> = (1..4).map(|n| {
+        let dataset = broto::load(fs::File::open(dir.join(format!("part-{}.broto", n))).unwrap()).unwrap();
+        dataset.series("/run1/accel").unwrap().len()
+    }).collect();
+    assert_eq!(lengths, vec![8, 8, 4]);
+    assert!(!dir.join("part-4.broto").exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_cli_tail() {
+    let dir = temp_dir("tail");
+    let path = dir.join("sample.broto");
+    write_sample(&path);
+
+    let out = broto(&["tail", "-n", "2", path.to_str().unwrap()]);
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "series /run1/accel\n  18\t18\t-18\n  19\t19\t-19\n");
+
+    let out = broto(&["tail", "--series", "temp", path.to_str().unwrap()]);
+    assert!(out.status.success());
+    assert!(out.stdout.is_empty());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,58 @@
+extern crate broto;
+
+use std::io::Cursor;
+
+use broto::TimeSeries;
+use broto::csv::{self, Column, CsvFormat, CsvImport};
+
+#[test]
+fn test_csv_round_trip() {
+    let mut series = TimeSeries::new("accel", 3).with_channel_names(vec!["x", "y", "z"]);
+    for i in 0..10 {
+        let x = i as f64;
+        series.push(0.1 * x, vec![x, 0.5 * x, -x / 3.0]);
+    }
+
+    let mut out = Vec::new();
+    let entries = series.entries().iter().cloned().map(Ok);
+    let rows = csv::write_csv(&mut out, series.channel_names(), entries, &CsvFormat::csv()).unwrap();
+    assert_eq!(rows, 10);
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.starts_with("index,x,y,z\n0,0,0,-0\n0.1,1,0.5,-0.3333333333333333\n"));
+
+    let read = csv::read_csv(Cursor::new(text), &CsvImport::csv(), "accel").unwrap();
+    assert_eq!(series, read);
+}
+
+#[test]
+fn test_csv_format() {
+    let entries = vec![Ok((1.0, vec![1234.5678]))];
+    let mut out = Vec::new();
+    let format = CsvFormat::tsv().precision(2).scientific(true);
+    csv::write_csv(&mut out, &["value".to_string()], entries.into_iter(), &format).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "index\tvalue\n1.00e0\t1.23e3\n");
+}
+
+#[test]
+fn test_csv_import_columns() {
+    let text = "\
+label,temp,time,\"pressure, kPa\"
+a,20.5,0.0,101.3
+
+b,,1.0,101.4
+";
+    let spec = CsvImport::csv()
+        .index_column(Column::Name("time".to_string()))
+        .value_columns(vec![Column::Name("pressure, kPa".to_string()), Column::Number(1)]);
+    let series = csv::read_csv(Cursor::new(text), &spec, "weather").unwrap();
+    assert_eq!(series.channel_names(), &["pressure, kPa".to_string(), "temp".to_string()]);
+    assert_eq!(series.entries()[0], (0.0, vec![101.3, 20.5]));
+    assert_eq!(series.entries()[1].1[0], 101.4);
+    assert!(series.entries()[1].1[1].is_nan());
+
+    let spec = CsvImport::csv().index_column(Column::Name("time".to_string()));
+    match csv::read_csv(Cursor::new(text), &spec, "weather") {
+        Err(broto::Error::Parse(msg)) => assert_eq!(msg, "line 2: invalid number: a"),
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
@@ -0,0 +1,143 @@
+extern crate broto;
+
+use std::io::Cursor;
+
+use broto::{Dataset, TimeSeries, LogBlockBuilder};
+
+#[test]
+fn test_named_series() {
+    let mut dataset = Dataset::new();
+    let log = LogBlockBuilder::new().program("broto").info("session").build();
+    dataset.metadata_mut().get_logs_mut().push(log);
+
+    let channels = [("accel", 3), ("gyro", 3), ("temperature", 1)];
+    for &(name, value_len) in channels.iter() {
+        let series = dataset.add_series(TimeSeries::new(name, value_len));
+        for i in 0..100 {
+            let x = i as f64;
+            series.push(0.01 * x, vec![x; value_len as usize]);
+        }
+    }
+
+    let cur = broto::save(Cursor::new(Vec::new()), &dataset).unwrap();
+    let loaded = broto::load(Cursor::new(cur.into_inner())).unwrap();
+
+    assert_eq!(dataset, loaded);
+    assert_eq!(vec!["accel", "gyro", "temperature"], loaded.series_names());
+    let gyro = loaded.series("gyro").unwrap();
+    assert_eq!(gyro.id(), 1);
+    assert_eq!(gyro.entries()[7], (0.07, vec![7.0; 3]));
+    assert!(loaded.series("pressure").is_none());
+}
+
+#[test]
+fn test_unnamed_series_channel_names() {
+    let mut writer = broto::Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    for &value_len in [2, 1].iter() {
+        let names = if value_len == 2 { vec!["x", "y"] } else { vec!["t"] };
+        let fts = broto::F64TSBlockBuilder::new()
+            .index_len(1)
+            .value_len(value_len)
+            .length(1)
+            .channel_names(names)
+            .build();
+        let mut w = writer.write_f64ts(fts).unwrap();
+        w.write_entry(0.0, &vec![1.0; value_len as usize]).unwrap();
+        writer = w.finish();
+    }
+    let buf = writer.into_stream().into_inner();
+
+    let mut reader = broto::Reader::new(Cursor::new(buf.clone()));
+    reader.initialize().unwrap();
+    match reader.next_block().unwrap() {
+        broto::Block::F64TS(fts) => {
+            assert_eq!(fts.name(), None);
+            assert_eq!(fts.channel_names(), &["x", "y"]);
+        },
+        block => panic!("unexpected block: {:?}", block),
+    }
+
+    let loaded = broto::load(Cursor::new(buf)).unwrap();
+    assert_eq!(loaded.series_list().len(), 2);
+    assert_eq!(loaded.series_list()[1].channel_names(), &["t"]);
+
+    // Saved again, the series stay unnamed and separate.
+    let buf = broto::save(Cursor::new(Vec::new()), &loaded).unwrap().into_inner();
+    let reloaded = broto::load(Cursor::new(buf)).unwrap();
+    assert_eq!(reloaded.series_list().len(), 2);
+    assert_eq!(reloaded.series_list()[0].channel_names(), &["x", "y"]);
+}
+
+#[test]
+fn test_named_series_aligned_lazy() {
+    let cur = Cursor::new(Vec::new());
+    let mut writer = broto::Writer::new(cur).with_alignment(64);
+    writer.write_header().unwrap();
+    for &(id, name) in [(0, "accel"), (1, "gyro")].iter() {
+        let fts = broto::F64TSBlockBuilder::new()
+            .index_len(1)
+            .value_len(2)
+            .length(10)
+            .id(id)
+            .name(name)
+            .build();
+        let mut w = writer.write_f64ts(fts).unwrap();
+        for i in 0..10 {
+            w.write_entry(i as f64, &[id as f64, i as f64]).unwrap();
+        }
+        writer = w.finish();
+    }
+
+    let cur = Cursor::new(writer.into_stream().into_inner());
+    let mut lazy = broto::LazyReader::new(cur).unwrap();
+    assert_eq!(lazy.series()[1].name(), Some("gyro"));
+    assert_eq!(lazy.series()[1].offset() % 64, 0);
+    let n = lazy.find_series("gyro").unwrap();
+    let gyro = lazy.load_series(n).unwrap();
+    assert_eq!(gyro[3], (3.0, vec![1.0, 3.0]));
+}
+
+#[test]
+fn test_groups() {
+    use broto::Node;
+
+    let mut dataset = Dataset::new();
+    dataset.metadata_mut().set_attr("site", "lab");
+    for run in 1..4 {
+        for sensor in 1..3 {
+            let path = format!("/run{}/sensor{}", run, sensor);
+            let series = dataset.add_series(TimeSeries::new("raw", 1).with_group(&path));
+            for i in 0..10 {
+                series.push(i as f64, vec![(run * 10 + sensor) as f64]);
+            }
+        }
+    }
+    let log = LogBlockBuilder::new().program("broto").info("run 3 restarted").build();
+    dataset.group_mut("/run3").get_logs_mut().push(log.clone());
+    dataset.group_mut("/run3/sensor2/").set_attr("unit", "m/s^2");
+
+    let cur = broto::save(Cursor::new(Vec::new()), &dataset).unwrap();
+    let loaded = broto::load(Cursor::new(cur.into_inner())).unwrap();
+    assert_eq!(dataset, loaded);
+
+    assert_eq!(loaded.metadata().get_attr("site"), Some("lab"));
+    assert_eq!(loaded.group("/run3").unwrap().get_logs(), &vec![log]);
+    assert_eq!(loaded.group("run3/sensor2").unwrap().get_attr("unit"), Some("m/s^2"));
+
+    let raw = loaded.series("/run3/sensor2/raw").unwrap();
+    assert_eq!(raw.entries()[0].1, vec![32.0]);
+
+    let children: Vec<_> = loaded.children("/run3").into_iter().map(|n| match n {
+        Node::Group(p, _) => p.to_string(),
+        Node::Series(s) => s.path(),
+    }).collect();
+    assert_eq!(vec!["/run3/sensor1", "/run3/sensor2"], children);
+
+    let subtree: Vec<_> = loaded.subtree("/run2").into_iter().map(|n| match n {
+        Node::Group(p, _) => p.to_string(),
+        Node::Series(s) => s.path(),
+    }).collect();
+    assert_eq!(vec!["/run2", "/run2/sensor1", "/run2/sensor1/raw",
+                    "/run2/sensor2", "/run2/sensor2/raw"], subtree);
+}
@@ -0,0 +1,120 @@
+extern crate broto;
+
+use std::io::Cursor;
+
+use broto::EventBlock;
+use broto::edf::{self, EdfHeader};
+
+fn pad(s: &str, len: usize) -> Vec<u8> {
+    let mut field = s.as_bytes().to_vec();
+    field.resize(len, b' ');
+    field
+}
+
+// An EDF+ recording of two one-second records with an EEG signal of 4 Hz,
+// an ECG signal of 2 Hz and annotations.
+fn sample_edf() -> Vec<u8> {
+    let signals = [("EEG Fp1", "uV", "-100", "100", "-2048", "2047", 4),
+                   ("ECG", "mV", "-5", "5", "-32768", "32767", 2),
+                   ("EDF Annotations", "", "-1", "1", "-32768", "32767", 30)];
+    let mut edf = pad("0", 8);
+    edf.extend(pad("MCH-0234567 F 02-MAY-1951 Haagse_Harry", 80));
+    edf.extend(pad("Startdate 02-MAR-2002 EMG561 BK/JOP Sony", 80));
+    edf.extend(pad("02.03.02", 8));
+    edf.extend(pad("16.15.00", 8));
+    edf.extend(pad(&(256 * 4).to_string(), 8));
+    edf.extend(pad("EDF+C", 44));
+    edf.extend(pad("2", 8));
+    edf.extend(pad("1", 8));
+    edf.extend(pad("3", 4));
+    for s in signals.iter() { edf.extend(pad(s.0, 16)); }
+    for _ in signals.iter() { edf.extend(pad("AgAgCl electrode", 80)); }
+    for s in signals.iter() { edf.extend(pad(s.1, 8)); }
+    for s in signals.iter() { edf.extend(pad(s.2, 8)); }
+    for s in signals.iter() { edf.extend(pad(s.3, 8)); }
+    for s in signals.iter() { edf.extend(pad(s.4, 8)); }
+    for s in signals.iter() { edf.extend(pad(s.5, 8)); }
+    for _ in signals.iter() { edf.extend(pad("HP:0.1Hz", 80)); }
+    for s in signals.iter() { edf.extend(pad(&s.6.to_string(), 8)); }
+    for _ in signals.iter() { edf.extend(pad("", 32)); }
+
+    for record in 0..2 {
+        for k in 0..4i16 {
+            edf.extend(&(k * 1000 - 2048).to_le_bytes());
+        }
+        for k in 0..2i16 {
+            edf.extend(&(record * 100 + k).to_le_bytes());
+        }
+        let mut tal = format!("+{}\x14\x14\0", record).into_bytes();
+        if record == 1 {
+            tal.extend(b"+1.5\x150.25\x14Stimulus\x14Eyes closed\x14\0");
+        }
+        tal.resize(60, 0);
+        edf.extend(tal);
+    }
+    edf
+}
+
+#[test]
+fn test_edf_header() {
+    let header = EdfHeader::read_from(&mut Cursor::new(sample_edf())).unwrap();
+    assert!(header.is_edf_plus());
+    assert_eq!(header.records(), Some(2));
+    assert_eq!(header.signals().len(), 3);
+    assert_eq!(header.sample_rate(0), 4.0);
+    assert_eq!(header.start(), Some("2002-03-02T16:15:00".to_string()));
+
+    let eeg = &header.signals()[0];
+    assert_eq!(eeg.dimension(), "uV");
+    assert_eq!(eeg.physical(-2048), -100.0);
+    assert_eq!(eeg.physical(2047), 100.0);
+    assert!(header.signals()[2].is_annotations());
+}
+
+#[test]
+fn test_edf_import() {
+    let dataset = edf::read_edf(Cursor::new(sample_edf()), "/eeg").unwrap();
+    assert_eq!(dataset.series_names(), vec!["EEG Fp1", "ECG"]);
+
+    let eeg = dataset.series("/eeg/EEG Fp1").unwrap();
+    assert_eq!(eeg.len(), 8);
+    assert_eq!(eeg.entries()[5].0, 1.25);
+    let ecg = dataset.series("ECG").unwrap();
+    let scale = 10.0 / 65535.0;
+    assert_eq!(ecg.entries()[3], (1.5, vec![(101.0 + 32768.0) * scale - 5.0]));
+
+    let metadata = dataset.group("/eeg").unwrap();
+    assert_eq!(metadata.get_attr("edf.patient.name"), Some("Haagse Harry"));
+    assert_eq!(metadata.get_attr("edf.recording.equipment"), Some("Sony"));
+    assert_eq!(metadata.get_attr("edf.signal.ECG.dimension"), Some("mV"));
+    assert_eq!(metadata.get_attr("edf.signal.EEG Fp1.sample_rate"), Some("4"));
+    assert_eq!(metadata.get_events(), &[EventBlock::new(1.5, Some(0.25), "Stimulus"),
+                                        EventBlock::new(1.5, Some(0.25), "Eyes closed")]);
+
+    let buf = broto::save(Cursor::new(Vec::new()), &dataset).unwrap().into_inner();
+    let read = broto::load(Cursor::new(buf)).unwrap();
+    assert_eq!(dataset, read);
+}
+
+#[test]
+fn test_edf_labels_and_ranges() {
+    // Both ordinary signals labelled "EEG A1/A2".
+    let mut edf = sample_edf();
+    for n in 0..2 {
+        edf[256 + 16 * n..256 + 16 * (n + 1)].copy_from_slice(&pad("EEG A1/A2", 16));
+    }
+    let dataset = edf::read_edf(Cursor::new(edf.clone()), "/").unwrap();
+    assert_eq!(dataset.series_names(), vec!["EEG A1_A2", "EEG A1_A2_2"]);
+    let metadata = dataset.metadata();
+    assert_eq!(metadata.get_attr("edf.signal.EEG A1_A2.dimension"), Some("uV"));
+    assert_eq!(metadata.get_attr("edf.signal.EEG A1_A2_2.dimension"), Some("mV"));
+    assert_eq!(metadata.get_attr("edf.signal.EEG A1_A2_2.label"), Some("EEG A1/A2"));
+
+    // The digital maximum of the ECG signal equal to its minimum.
+    let offset = 256 + 3 * (16 + 80 + 8 + 8 + 8 + 8) + 8;
+    edf[offset..offset + 8].copy_from_slice(&pad("-32768", 8));
+    match EdfHeader::read_from(&mut Cursor::new(edf)) {
+        Err(broto::Error::Parse(_)) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
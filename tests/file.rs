@@ -0,0 +1,193 @@
+extern crate broto;
+
+use std::env;
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+
+use broto::{Block, CheckpointPolicy, Error, File, F64TSBlockBuilder, LogBlockBuilder, Reader, Writer};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("broto-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_file_create_open() {
+    let dir = temp_dir("file");
+    let path = dir.join("data.broto");
+
+    let log = LogBlockBuilder::new().program("broto").info("creation").build();
+    let mut writer = File::create(&path).unwrap();
+    writer.write_log(&log).unwrap();
+    let fts = F64TSBlockBuilder::new()
+        .index_len(1)
+        .value_len(2)
+        .build();
+    let mut w = writer.write_f64ts_with_seek(fts).unwrap();
+    for i in 0..100 {
+        let x = i as f64;
+        w.write_entry(x, &[x, -x]).unwrap();
+    }
+    let writer = w.finalize().unwrap().finish();
+    assert!(!path.exists());
+    writer.close().unwrap();
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+    let mut reader = File::open(&path).unwrap();
+    match reader.next_block().unwrap() {
+        Block::Log(l) => assert_eq!(log, l),
+        b => panic!("unexpected block: {:?}", b),
+    }
+    let fts = match reader.next_block().unwrap() {
+        Block::F64TS(fts) => fts,
+        b => panic!("unexpected block: {:?}", b),
+    };
+    let entries: Vec<_> = reader.f64ts_entries(&fts).map(|e| e.unwrap()).collect();
+    assert_eq!(entries.len(), 100);
+    assert_eq!(entries[10], (10.0, vec![10.0, -10.0]));
+    match reader.next_block() {
+        Err(Error::EndOfFile) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_file_create_dropped() {
+    let dir = temp_dir("dropped");
+    let path = dir.join("data.broto");
+
+    let log = LogBlockBuilder::new().program("broto").info("creation").build();
+    let mut writer = File::create(&path).unwrap();
+    writer.write_log(&log).unwrap();
+    drop(writer);
+
+    assert!(!path.exists());
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_checkpoint_recover() {
+    let dir = temp_dir("checkpoint");
+    let path = dir.join("data.broto");
+
+    let mut writer = Writer::new(fs::File::create(&path).unwrap());
+    writer.write_header().unwrap();
+    let fts = F64TSBlockBuilder::new().index_len(1).value_len(1).name("temp").build();
+    let mut w = writer.write_f64ts_with_seek(fts).unwrap()
+        .with_checkpoints(CheckpointPolicy::new().every_rows(10).sync(true));
+    for i in 0..25 {
+        w.write_entry(i as f64, &[20.0]).unwrap();
+    }
+    // The writer dies without finalizing.
+    drop(w);
+    match broto::load(fs::File::open(&path).unwrap()) {
+        Err(Error::Magic) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+
+    assert_eq!(File::recover(&path).unwrap(), 5 * 16);
+    let dataset = broto::load(fs::File::open(&path).unwrap()).unwrap();
+    assert_eq!(dataset.series("temp").unwrap().len(), 20);
+    assert_eq!(File::recover(&path).unwrap(), 0);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_recover_undefined_block() {
+    let dir = temp_dir("recover-undefined");
+    let path = dir.join("data.broto");
+
+    let log = LogBlockBuilder::new().program("broto").info("creation").build();
+    let mut writer = Writer::new(fs::File::create(&path).unwrap());
+    writer.write_header().unwrap();
+    writer.write_log(&log).unwrap();
+    let mut stream = writer.into_stream();
+    stream.write_all(b"block   ").unwrap();
+    stream.write_all(&6u64.to_le_bytes()).unwrap();
+    stream.write_all(b"future").unwrap();
+    stream.write_all(&4u64.to_le_bytes()).unwrap();
+    stream.write_all(&[1, 2, 3, 4]).unwrap();
+    let mut writer = Writer::new(stream);
+    writer.write_log(&log).unwrap();
+    drop(writer);
+
+    let size = fs::metadata(&path).unwrap().len();
+    match File::recover(&path) {
+        Err(Error::UndefinedBlock) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+    assert_eq!(fs::metadata(&path).unwrap().len(), size);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_recover_concatenated() {
+    let dir = temp_dir("recover-concatenated");
+    let path = dir.join("data.broto");
+
+    let part = |name: &str| {
+        let mut writer = Writer::new(Cursor::new(Vec::new()));
+        writer.write_header().unwrap();
+        writer.write_log(&LogBlockBuilder::new().program("broto").info(name).build()).unwrap();
+        let fts = F64TSBlockBuilder::new().index_len(1).value_len(1).length(1).name(name).build();
+        let mut w = writer.write_f64ts(fts).unwrap();
+        w.write_entry(0.0, &[1.0]).unwrap();
+        w.finish().into_stream().into_inner()
+    };
+    let mut buf = part("a");
+    buf.extend(part("b"));
+    let size = buf.len() as u64;
+    buf.extend_from_slice(&[1, 2, 3, 4, 5]);
+    fs::write(&path, &buf).unwrap();
+    assert_eq!(File::recover(&path).unwrap(), 5);
+    assert_eq!(fs::metadata(&path).unwrap().len(), size);
+
+    // A bad block magic number in the middle is not the tail of a crash.
+    buf.truncate(size as usize);
+    buf[56] = b'x';
+    fs::write(&path, &buf).unwrap();
+    match File::recover(&path) {
+        Err(Error::Magic) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+    assert_eq!(fs::metadata(&path).unwrap().len(), size);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_checkpoint_explicit_and_timed() {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    let fts = F64TSBlockBuilder::new().index_len(1).value_len(1).build();
+    let mut w = writer.write_f64ts_with_seek(fts).unwrap()
+        .with_checkpoints(CheckpointPolicy::new().every(Duration::from_secs(3600)));
+    let length = |w: &mut broto::F64TSWriter<Cursor<Vec<u8>>>| {
+        let mut reader = Reader::new(Cursor::new(w.stream_mut().get_ref().clone()));
+        reader.initialize().unwrap();
+        match reader.next_block().unwrap() {
+            Block::F64TS(fts) => fts.length().unwrap(),
+            block => panic!("unexpected block: {:?}", block),
+        }
+    };
+    w.write_entry(0.0, &[1.0]).unwrap();
+    w.write_entry(1.0, &[1.0]).unwrap();
+    assert_eq!(length(&mut w), 0);
+    w.checkpoint().unwrap();
+    assert_eq!(length(&mut w), 2);
+
+    let mut w = w.with_checkpoints(CheckpointPolicy::new().every(Duration::from_secs(0)));
+    w.write_entry(2.0, &[1.0]).unwrap();
+    assert_eq!(length(&mut w), 3);
+}
@@ -0,0 +1,145 @@
+extern crate broto;
+
+use std::env;
+use std::fs;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+use broto::{Block, File, F64TSBlockBuilder, LogBlockBuilder, Writer};
+use broto::follow::Item;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("broto-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn block(name: &str) -> broto::F64TSBlock {
+    F64TSBlockBuilder::new().index_len(1).value_len(1).name(name).build()
+}
+
+fn entry(item: Option<Item>) -> (f64, Vec<f64>) {
+    match item {
+        Some(Item::Entry(entry)) => entry,
+        item => panic!("unexpected item: {:?}", item),
+    }
+}
+
+#[test]
+fn test_follow_growing_file() {
+    let dir = temp_dir("follow");
+    let path = dir.join("live.broto");
+    let mut writer = Writer::new(fs::File::create(&path).unwrap());
+    let mut follower = File::follow(&path).unwrap();
+    assert!(follower.try_next().unwrap().is_none());
+    assert!(follower.header().is_none());
+
+    writer.write_header().unwrap();
+    writer.write_group("/run").unwrap();
+    writer.write_log(&LogBlockBuilder::new().program("daq").info("start").build()).unwrap();
+    match follower.try_next().unwrap() {
+        Some(Item::Block(Block::Group(_))) => {},
+        item => panic!("unexpected item: {:?}", item),
+    }
+    match follower.try_next().unwrap() {
+        Some(Item::Block(Block::Log(log))) => assert_eq!(log.program(), "daq"),
+        item => panic!("unexpected item: {:?}", item),
+    }
+
+    let mut w = writer.write_f64ts_with_seek(block("temp")).unwrap();
+    match follower.try_next().unwrap() {
+        Some(Item::Block(Block::F64TS(b))) => assert_eq!(b.length(), Some(0)),
+        item => panic!("unexpected item: {:?}", item),
+    }
+    assert_eq!(follower.current_group(), "/run");
+    assert!(follower.try_next().unwrap().is_none());
+    for i in 0..4 {
+        w.write_entry(i as f64, &[20.0 + i as f64]).unwrap();
+    }
+    // A partially written row is not read until it is complete.
+    let mut file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+    let len = file.metadata().unwrap().len();
+    file.set_len(len - 4).unwrap();
+    for i in 0..3 {
+        assert_eq!(entry(follower.try_next().unwrap()), (i as f64, vec![20.0 + i as f64]));
+    }
+    assert!(follower.try_next().unwrap().is_none());
+    file.seek(SeekFrom::End(0)).unwrap();
+    file.write_all(&23.0f64.to_le_bytes()[4..]).unwrap();
+    assert_eq!(entry(follower.try_next().unwrap()), (3.0, vec![23.0]));
+    assert!(follower.try_next().unwrap().is_none());
+
+    // An empty series followed by the next block.
+    let mut writer = w.finalize().unwrap().finish();
+    writer.write_log(&LogBlockBuilder::new().program("daq").info("next").build()).unwrap();
+    let w = writer.write_f64ts_with_seek(block("empty")).unwrap();
+    let mut writer = w.finalize().unwrap().finish();
+    writer.write_attr(&broto::AttrBlock::new("k", "v")).unwrap();
+    let kinds: Vec<&str> = (0..3).map(|_| match follower.try_next().unwrap() {
+        Some(Item::Block(Block::Log(_))) => "log",
+        Some(Item::Block(Block::F64TS(_))) => "f64ts",
+        Some(Item::Block(Block::Attr(_))) => "attr",
+        item => panic!("unexpected item: {:?}", item),
+    }).collect();
+    assert_eq!(kinds, vec!["log", "f64ts", "attr"]);
+    assert!(follower.try_next().unwrap().is_none());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_follow_waits() {
+    let dir = temp_dir("follow-wait");
+    let path = dir.join("live.broto");
+    let mut writer = Writer::new(fs::File::create(&path).unwrap());
+    writer.write_header().unwrap();
+    let mut w = writer.write_f64ts_with_seek(block("temp")).unwrap();
+
+    let mut follower = File::follow(&path).unwrap().poll_interval(Duration::from_millis(5));
+    let reader = thread::spawn(move || {
+        follower.by_ref().skip(1).take(5).map(|item| match item.unwrap() {
+            Item::Entry(entry) => entry.0,
+            item => panic!("unexpected item: {:?}", item),
+        }).collect::<Vec<_>>()
+    });
+    for i in 0..5 {
+        thread::sleep(Duration::from_millis(10));
+        w.write_entry(i as f64, &[0.0]).unwrap();
+    }
+    assert_eq!(reader.join().unwrap(), vec![0.0, 1.0, 2.0, 3.0, 4.0]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_follow_magic_entry() {
+    let mut writer = Writer::new(std::io::Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    let mut w = writer.write_f64ts_with_seek(block("temp")).unwrap();
+    // An index whose bytes are the block magic number.
+    let index = f64::from_le_bytes(*b"block   ");
+    w.write_entry(index, &[1.0]).unwrap();
+    let buf = w.stream_mut().get_ref().clone();
+
+    let mut follower = broto::follow::Follower::new(std::io::Cursor::new(buf.clone()));
+    match follower.try_next().unwrap() {
+        Some(Item::Block(Block::F64TS(_))) => {},
+        item => panic!("unexpected item: {:?}", item),
+    }
+    assert_eq!(entry(follower.try_next().unwrap()), (index, vec![1.0]));
+    assert!(follower.try_next().unwrap().is_none());
+
+    // A block whose name length does not fit.
+    let mut buf = buf[..56].to_vec();
+    buf.extend_from_slice(b"block   ");
+    buf.extend_from_slice(&u64::MAX.to_le_bytes());
+    let mut follower = broto::follow::Follower::new(std::io::Cursor::new(buf));
+    match follower.try_next() {
+        Err(broto::Error::Parse(_)) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
@@ -0,0 +1,94 @@
+extern crate broto;
+
+use std::io::Cursor;
+
+use broto::{Dataset, TimeSeries, LazyReader, Writer};
+use broto::influx::{self, Precision, Point};
+
+fn sample_dataset() -> Dataset {
+    let mut dataset = Dataset::new();
+    dataset.group_mut("/station 1").set_attr("site", "Kyoto, JP");
+    let weather = dataset.add_series(TimeSeries::new("weather", 2)
+                                     .with_group("/station 1")
+                                     .with_channel_names(vec!["temp", "humidity"]));
+    weather.push(1_500_000_000.0, vec![20.5, 40.0]);
+    weather.push(1_500_000_000.5, vec![f64::NAN, 41.0]);
+    let count = dataset.add_series(TimeSeries::new("count", 1));
+    count.push(1_500_000_001.0, vec![3.0]);
+    dataset
+}
+
+#[test]
+fn test_influx_export() {
+    let buf = broto::save(Cursor::new(Vec::new()), &sample_dataset()).unwrap().into_inner();
+    let mut lazy = LazyReader::new(Cursor::new(buf)).unwrap();
+    let mut out = Vec::new();
+    assert_eq!(influx::export(&mut lazy, &mut out, Precision::Milliseconds).unwrap(), 3);
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "count value=3 1500000001000\n\
+                weather,group=/station\\ 1,site=Kyoto\\,\\ JP temp=20.5,humidity=40 1500000000000\n\
+                weather,group=/station\\ 1,site=Kyoto\\,\\ JP humidity=41 1500000000500\n");
+}
+
+#[test]
+fn test_influx_parse_line() {
+    let point = influx::parse_line("cpu\\ load,host=a\\=b,dc=x usage=0.5,count=3i,up=t 1700000000").unwrap();
+    assert_eq!(point, Some(Point {
+        measurement : "cpu load".to_string(),
+        tags        : vec![("dc".to_string(), "x".to_string()), ("host".to_string(), "a=b".to_string())],
+        fields      : vec![("usage".to_string(), 0.5), ("count".to_string(), 3.0), ("up".to_string(), 1.0)],
+        timestamp   : 1_700_000_000,
+    }));
+    assert_eq!(influx::parse_line("  # comment").unwrap(), None);
+    assert!(influx::parse_line("cpu usage=0.5").is_err());
+    assert!(influx::parse_line("cpu msg=\"a b\" 1").is_err());
+}
+
+#[test]
+fn test_influx_import() {
+    let lines = "weather,group=/station\\ 1,site=Kyoto\\,\\ JP temp=20.5,humidity=40 1500000000000\n\
+                 count value=3 1500000001000\n\
+                 weather,group=/station\\ 1,site=Kyoto\\,\\ JP humidity=41 1500000000500\n\
+                 cpu,host=a usage=1 1500000000000\n\
+                 cpu,host=b usage=2 1500000000000\n\
+                 weather,group=/station\\ 1,site=Kyoto\\,\\ JP temp=21,humidity=42 1500000001000\n";
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    let mut importer = influx::Importer::new(writer, Precision::Milliseconds).chunk_rows(2);
+    assert_eq!(importer.read_lines(Cursor::new(lines)).unwrap(), 6);
+    let buf = importer.finish().unwrap().into_stream().into_inner();
+
+    // The weather series was written in two blocks.
+    let lazy = LazyReader::new(Cursor::new(buf.clone())).unwrap();
+    assert_eq!(lazy.series().len(), 5);
+
+    let dataset = broto::load(Cursor::new(buf)).unwrap();
+    let weather = dataset.series("/station 1/weather").unwrap();
+    assert_eq!(weather.channel_names(), &["temp".to_string(), "humidity".to_string()]);
+    assert_eq!(weather.len(), 3);
+    assert!(weather.entries()[1].1[0].is_nan());
+    assert_eq!(weather.entries()[2], (1_500_000_001.0, vec![21.0, 42.0]));
+    assert_eq!(dataset.group("/station 1").unwrap().get_attr("influx.series.weather"),
+               Some("weather,site=Kyoto\\,\\ JP"));
+    assert_eq!(dataset.series("count").unwrap().entries(), &[(1_500_000_001.0, vec![3.0])]);
+    assert_eq!(dataset.series("cpu").unwrap().entries()[0].1, vec![1.0]);
+    assert_eq!(dataset.series("cpu,host=b").unwrap().entries()[0].1, vec![2.0]);
+    assert_eq!(dataset.metadata().get_attr("influx.series.cpu,host=b"), Some("cpu,host=b"));
+}
+
+#[test]
+fn test_influx_series_tags() {
+    let lines = "cpu,host=a usage=1 1000\n\
+                 mem,host=b used=2 1000\n\
+                 cpu,host=b usage=3 1000\n\
+                 disk free=4 1000\n";
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    let writer = influx::import(Cursor::new(lines), writer, Precision::Seconds).unwrap();
+    let buf = writer.into_stream().into_inner();
+
+    let mut lazy = LazyReader::new(Cursor::new(buf)).unwrap();
+    let mut out = Vec::new();
+    influx::export(&mut lazy, &mut out, Precision::Seconds).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), lines);
+}
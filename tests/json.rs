@@ -0,0 +1,81 @@
+extern crate broto;
+
+use std::io::Cursor;
+use std::time::Duration;
+
+use broto::{Dataset, TimeSeries, LazyReader, LogBlockBuilder, EventBlock};
+use broto::json::{self, Value};
+
+fn sample_dataset() -> Dataset {
+    let mut dataset = Dataset::new();
+    let log = LogBlockBuilder::new()
+        .program("broto")
+        .info("say \"hi\"\n")
+        .time(Duration::new(1_500_000_000, 500_000_000))
+        .build();
+    dataset.metadata_mut().get_logs_mut().push(log);
+    dataset.group_mut("/run1").set_attr("site", "Kyoto");
+    dataset.group_mut("/run1").get_events_mut().push(EventBlock::new(0.5, None, "start"));
+    let accel = dataset.add_series(TimeSeries::new("accel", 2)
+                                   .with_group("/run1")
+                                   .with_channel_names(vec!["x", "y"]));
+    accel.push(0.0, vec![1.0, f64::NAN]);
+    accel.push(0.25, vec![1e-7, 2e300]);
+    let count = dataset.add_series(TimeSeries::new("count", 1));
+    count.push(1.0, vec![3.0]);
+    dataset
+}
+
+fn sample_reader() -> LazyReader<Cursor<Vec<u8>>> {
+    let buf = broto::save(Cursor::new(Vec::new()), &sample_dataset()).unwrap().into_inner();
+    LazyReader::new(Cursor::new(buf)).unwrap()
+}
+
+#[test]
+fn test_json_describe() {
+    let lazy = sample_reader();
+    let doc = json::describe(&lazy);
+
+    let groups = match doc.get("groups") {
+        Some(Value::Array(groups)) => groups.clone(),
+        v => panic!("unexpected groups: {:?}", v),
+    };
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[0].get("logs").unwrap().to_string(),
+               r#"[{"time":"2017-07-14T02:40:00.5Z","program":"broto","info":"say \"hi\"\n"}]"#);
+    assert_eq!(groups[1].get("path"), Some(&Value::from("/run1")));
+    assert_eq!(groups[1].get("attrs").unwrap().to_string(), r#"{"site":"Kyoto"}"#);
+    assert_eq!(groups[1].get("events").unwrap().to_string(),
+               r#"[{"onset":0.5,"duration":null,"text":"start"}]"#);
+
+    let series = match doc.get("series") {
+        Some(Value::Array(series)) => series.clone(),
+        v => panic!("unexpected series: {:?}", v),
+    };
+    assert_eq!(series[1].get("path"), Some(&Value::from("/run1/accel")));
+    assert_eq!(series[1].get("length"), Some(&Value::Int(2)));
+    assert_eq!(series[1].get("channels").unwrap().to_string(), r#"["x","y"]"#);
+    assert_eq!(series[1].get("offset"), Some(&Value::Int(lazy.series()[1].offset() as i64)));
+
+    let mut pretty = Vec::new();
+    json::write_json(&lazy, &mut pretty, true).unwrap();
+    let pretty = String::from_utf8(pretty).unwrap();
+    assert!(pretty.starts_with("{\n  \"header\": {\n    \"major_version\": "));
+    assert!(pretty.ends_with("\n}\n"));
+}
+
+#[test]
+fn test_json_rows() {
+    let mut lazy = sample_reader();
+    let mut out = Vec::new();
+    assert_eq!(json::export_ndjson(&mut lazy, &mut out).unwrap(), 3);
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "{\"series\":\"/count\",\"index\":1,\"values\":[3]}\n\
+                {\"series\":\"/run1/accel\",\"index\":0,\"values\":{\"x\":1,\"y\":null}}\n\
+                {\"series\":\"/run1/accel\",\"index\":0.25,\"values\":{\"x\":1e-7,\"y\":2e300}}\n");
+
+    let row = json::row_value("/zero", &[], &(-0.0, vec![-0.0, 0.0]));
+    let mut out = Vec::new();
+    row.write_into(&mut out, false).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), r#"{"series":"/zero","index":-0,"values":[-0,0]}"#);
+}
@@ -27,8 +27,8 @@ fn test_reader_writer_1() {
     let buf: Vec<u8> = Vec::new();
     let cur = Cursor::new(buf);
     let mut writer = Writer::new(cur);
-    let _ = writer.write_header().unwrap();
-    let _ = writer.write_log(&log).unwrap();
+    writer.write_header().unwrap();
+    writer.write_log(&log).unwrap();
 
     let fts = F64TSBlockBuilder::new()
         .index_len(1)
@@ -46,7 +46,7 @@ fn test_reader_writer_1() {
 
     let cur = Cursor::new(buf);
     let mut reader = Reader::new(cur);
-    let _ = reader.initialize().unwrap();
+    reader.initialize().unwrap();
 
     let mut read_data = Vec::new();
 
@@ -80,10 +80,11 @@ fn test_reader_writer_1() {
                     for x in value.iter() {
                         print!(" {},", x);
                     }
-                    println!("");
+                    println!();
                     read_data.push(value);
                 }
             },
+            b => { println!("Other block was found: {:?}", b); },
         }
     }
 
@@ -108,8 +109,8 @@ fn test_reader_writer_2() {
     let buf: Vec<u8> = Vec::new();
     let cur = Cursor::new(buf);
     let mut writer = Writer::new(cur);
-    let _ = writer.write_header().unwrap();
-    let _ = writer.write_log(&log).unwrap();
+    writer.write_header().unwrap();
+    writer.write_log(&log).unwrap();
 
     let fts = F64TSBlockBuilder::new()
         .index_len(1)
@@ -158,3 +159,225 @@ fn test_reader_writer_3() {
     assert_eq!(data, entries);
     assert_eq!(metadata, read_meta);
 }
+
+#[test]
+fn test_aligned_payload() {
+    use std::io::Seek;
+
+    let mut data = Vec::new();
+    for i in 0..10 {
+        let x = i as f64;
+        data.push((x, vec![0.5 * x, 1.5 * x]));
+    }
+
+    for &alignment in [8, 64, 4096].iter() {
+        let cur = Cursor::new(Vec::new());
+        let mut writer = Writer::new(cur).with_alignment(alignment);
+        writer.write_header().unwrap();
+        let log = LogBlockBuilder::new().program("broto").info("aligned").build();
+        writer.write_log(&log).unwrap();
+
+        let fts = F64TSBlockBuilder::new()
+            .index_len(1)
+            .value_len(2)
+            .build();
+        let mut w = writer.write_f64ts_with_seek(fts).unwrap();
+        assert_eq!(w.stream_mut().stream_position().unwrap() % alignment, 0);
+        for &(index, ref value) in data.iter() {
+            w.write_entry(index, value).unwrap();
+        }
+        let writer = w.finalize().unwrap().finish();
+
+        let cur = Cursor::new(writer.into_stream().into_inner());
+        let (entries, metadata) = broto::load_f64ts(cur).unwrap();
+        assert_eq!(data, entries);
+        assert_eq!(vec![log], *metadata.get_logs());
+    }
+}
+
+#[test]
+fn test_header_version() {
+    let mut buf = Vec::new();
+    Header::new().write_into(&mut buf).unwrap();
+    assert_eq!(Header::read_from(&mut &buf[..]).unwrap().minor_version(), broto::MINOR_VERSION);
+    // Files of version 0.1 are still read.
+    buf[20..24].copy_from_slice(&1u32.to_le_bytes());
+    assert_eq!(Header::read_from(&mut &buf[..]).unwrap().minor_version(), 1);
+    buf[20..24].copy_from_slice(&(broto::MINOR_VERSION + 1).to_le_bytes());
+    match Header::read_from(&mut &buf[..]) {
+        Err(Error::Parse(_)) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
+
+#[test]
+fn test_aligned_payload_appended() {
+    use std::io::{Seek, SeekFrom};
+
+    // The writer starts in the middle of the stream, as when appending.
+    let mut cur = Cursor::new(vec![0; 13]);
+    cur.seek(SeekFrom::End(0)).unwrap();
+    let mut writer = Writer::new(cur).with_alignment(64);
+    writer.write_header().unwrap();
+    let fts = F64TSBlockBuilder::new().index_len(1).value_len(2).build();
+    let mut w = writer.write_f64ts_with_seek(fts).unwrap();
+    assert_eq!(w.stream_mut().stream_position().unwrap() % 64, 0);
+    w.write_entry(1.0, &[2.0, 3.0]).unwrap();
+    let buf = w.finalize().unwrap().finish().into_stream().into_inner();
+
+    let (entries, _) = broto::load_f64ts(Cursor::new(&buf[13..])).unwrap();
+    assert_eq!(entries, vec![(1.0, vec![2.0, 3.0])]);
+}
+
+// Reads a file as the reader before `Writer::with_alignment` did: the size
+// of a block is ignored and the entries follow the three fixed fields of
+// a f64ts block.
+fn read_as_baseline(buf: &[u8]) -> (Vec<broto::LogBlock>, Vec<broto::Entry>) {
+    use std::io::Read;
+
+    let read_u64 = |cur: &mut Cursor<&[u8]>| {
+        let mut b = [0; 8];
+        cur.read_exact(&mut b).unwrap();
+        b
+    };
+    let mut cur = Cursor::new(buf);
+    Header::read_from(&mut cur).unwrap();
+    let (mut logs, mut entries) = (Vec::new(), Vec::new());
+    loop {
+        let bheader = match broto::BlockHeader::read_from(&mut cur) {
+            Ok(bheader) => bheader,
+            Err(Error::EndOfFile) => break,
+            Err(e) => panic!("{:?}", e),
+        };
+        match bheader.clone_name().as_str() {
+            "log" => { logs.push(broto::LogBlock::read_from(&mut cur).unwrap()); },
+            "f64ts" => {
+                let index_len = u64::from_le_bytes(read_u64(&mut cur));
+                let value_len = u64::from_le_bytes(read_u64(&mut cur));
+                let length = u64::from_le_bytes(read_u64(&mut cur));
+                assert_eq!(index_len, 1);
+                for _ in 0..length {
+                    let index = f64::from_le_bytes(read_u64(&mut cur));
+                    let values = (0..value_len).map(|_| f64::from_le_bytes(read_u64(&mut cur))).collect();
+                    entries.push((index, values));
+                }
+            },
+            name => panic!("undefined block {}", name),
+        }
+    }
+    (logs, entries)
+}
+
+#[test]
+fn test_aligned_payload_baseline() {
+    let mut data = Vec::new();
+    for i in 0..7 {
+        let x = i as f64;
+        data.push((x, vec![-x]));
+    }
+
+    for &alignment in [8, 64, 4096].iter() {
+        let mut writer = Writer::new(Cursor::new(Vec::new())).with_alignment(alignment);
+        writer.write_header().unwrap();
+        let log = LogBlockBuilder::new().program("broto").info("aligned").build();
+        writer.write_log(&log).unwrap();
+        for _ in 0..2 {
+            let fts = F64TSBlockBuilder::new().index_len(1).value_len(1).length(7).build();
+            let mut w = writer.write_f64ts(fts).unwrap();
+            assert_eq!(w.position() % alignment, 0);
+            for &(index, ref value) in data.iter() {
+                w.write_entry(index, value).unwrap();
+            }
+            writer = w.finish();
+        }
+        let buf = writer.into_stream().into_inner();
+
+        // The padding reads as extra logs, and the entries are intact.
+        let (logs, entries) = read_as_baseline(&buf);
+        assert_eq!(logs[0], log);
+        assert!(logs[1..].iter().all(|l| l.is_padding()));
+        assert!(logs.len() > 1);
+        assert_eq!(entries.len(), 14);
+        assert_eq!(entries[..7], data[..]);
+        assert_eq!(entries[7..], data[..]);
+
+        let (entries, metadata) = broto::load_f64ts(Cursor::new(buf)).unwrap();
+        assert_eq!(entries.len(), 14);
+        assert_eq!(vec![log], *metadata.get_logs());
+    }
+}
+
+#[test]
+fn test_write_entry_shape() {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    let fts = F64TSBlockBuilder::new().index_len(1).value_len(2).length(1).build();
+    let mut w = writer.write_f64ts(fts).unwrap();
+    match w.write_entry(0.0, &[1.0]) {
+        Err(Error::Shape) => {},
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert_eq!(w.count(), 0);
+    w.write_entry(0.0, &[1.0, 2.0]).unwrap();
+}
+
+#[test]
+fn test_lazy_reader() {
+    let cur = Cursor::new(Vec::new());
+    let mut writer = Writer::new(cur);
+    writer.write_header().unwrap();
+    let log = LogBlockBuilder::new().program("broto").info("creation").build();
+    writer.write_log(&log).unwrap();
+
+    for &(value_len, length) in [(3, 20), (1, 50)].iter() {
+        let fts = F64TSBlockBuilder::new()
+            .index_len(1)
+            .value_len(value_len)
+            .build();
+        let mut w = writer.write_f64ts_with_seek(fts).unwrap();
+        for i in 0..length {
+            let values = vec![i as f64; value_len as usize];
+            w.write_entry(i as f64, &values).unwrap();
+        }
+        writer = w.finalize().unwrap().finish();
+    }
+
+    let cur = Cursor::new(writer.into_stream().into_inner());
+    let mut lazy = broto::LazyReader::new(cur).unwrap();
+    assert_eq!(vec![log], *lazy.metadata().get_logs());
+    let shapes: Vec<_> = lazy.series().iter().map(|s| (s.value_len(), s.length())).collect();
+    assert_eq!(vec![(3, 20), (1, 50)], shapes);
+
+    let second = lazy.load_series(1).unwrap();
+    assert_eq!(second.len(), 50);
+    assert_eq!(second[49], (49.0, vec![49.0]));
+    let first = lazy.load_series(0).unwrap();
+    assert_eq!(first[5], (5.0, vec![5.0; 3]));
+}
+
+#[test]
+fn test_lazy_reader_errors() {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    let fts = F64TSBlockBuilder::new().index_len(1).value_len(1).length(1).build();
+    let mut w = writer.write_f64ts(fts).unwrap();
+    w.write_entry(0.0, &[1.0]).unwrap();
+    let buf = w.finish().into_stream().into_inner();
+
+    let mut lazy = broto::LazyReader::new(Cursor::new(buf)).unwrap();
+    assert!(lazy.entries(1).is_err());
+    assert!(lazy.entries_from(1, 0).is_err());
+    assert!(lazy.load_series(1).is_err());
+    assert!(lazy.joined_entries(1).is_err());
+    assert!(lazy.chunks(1).is_empty());
+
+    // A corrupt length whose entries would not fit in a u64.
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    let fts = F64TSBlockBuilder::new().index_len(1).value_len(1).length(u64::MAX / 8).build();
+    let buf = writer.write_f64ts(fts).unwrap().finish().into_stream().into_inner();
+    match broto::LazyReader::new(Cursor::new(buf)) {
+        Err(Error::Parse(_)) => {},
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+}
@@ -0,0 +1,114 @@
+extern crate broto;
+
+use std::io::Cursor;
+use std::time::Duration;
+
+use broto::{Dataset, Error, TimeSeries, LogBlockBuilder, EventBlock};
+use broto::mat::{self, MatValue, MatWriter};
+use broto::timestamp;
+
+#[test]
+fn test_mat_round_trip() {
+    let mut dataset = Dataset::new();
+    let log = LogBlockBuilder::new()
+        .program("broto")
+        .info("creation")
+        .time(Duration::new(1_500_000_000, 250_000_000))
+        .build();
+    dataset.metadata_mut().get_logs_mut().push(log);
+    dataset.metadata_mut().set_attr("site", "Kyoto");
+    let accel = dataset.add_series(TimeSeries::new("accel-x", 2)
+                                   .with_group("/run1")
+                                   .with_channel_names(vec!["x", "y"]));
+    for i in 0..5 {
+        accel.push(0.1 * i as f64, vec![i as f64, 1.0 / (i + 1) as f64]);
+    }
+    dataset.add_series(TimeSeries::new("empty", 1));
+    let events = dataset.group_mut("/run1").get_events_mut();
+    events.push(EventBlock::new(0.2, None, "start"));
+    events.push(EventBlock::new(0.3, Some(0.1), "spike"));
+
+    let buf = mat::write_mat(Vec::new(), &dataset).unwrap();
+    assert_eq!(&buf[..19], b"MATLAB 5.0 MAT-file");
+    assert_eq!(buf.len() % 8, 0);
+
+    let vars = mat::read_vars(&mut Cursor::new(buf.clone())).unwrap();
+    let names: Vec<&str> = vars.iter().map(|v| v.0.as_str()).collect();
+    assert_eq!(vec!["run1_accel_x", "empty", "series", "attrs", "logs", "events"], names);
+    match vars[0].1 {
+        MatValue::Double { rows, cols, ref data } => {
+            assert_eq!((rows, cols), (5, 3));
+            assert_eq!(data[5..10], [0.0, 1.0, 2.0, 3.0, 4.0]);
+        },
+        ref v => panic!("unexpected value: {:?}", v),
+    }
+
+    let read = mat::read_mat(&mut Cursor::new(buf)).unwrap();
+    assert_eq!(dataset, read);
+}
+
+#[test]
+fn test_mat_foreign_file() {
+    let mut writer = MatWriter::new(Vec::new()).unwrap();
+    writer.write_var("ecg", &MatValue::Double { rows: 2, cols: 2, data: vec![0.0, 0.5, 7.0, 8.0] }).unwrap();
+    writer.write_var("note", &MatValue::Char("ignored".to_string())).unwrap();
+    let mut buf = writer.into_inner();
+
+    // An integral 1x3 double stored compactly as miUINT8, with the name in
+    // the small data element format.
+    for &word in [14u32, 48, 6, 8, 6, 0, 5, 8, 1, 3, 0x0003_0001].iter() {
+        buf.extend_from_slice(&word.to_le_bytes());
+    }
+    buf.extend_from_slice(b"abc\0");
+    buf.extend_from_slice(&0x0003_0002u32.to_le_bytes());
+    buf.extend_from_slice(&[1, 2, 3, 0]);
+
+    let dataset = mat::read_mat(&mut Cursor::new(buf)).unwrap();
+    assert_eq!(dataset.series_names(), vec!["ecg", "abc"]);
+    assert_eq!(dataset.series("ecg").unwrap().entries(), &[(0.0, vec![7.0]), (0.5, vec![8.0])]);
+    assert_eq!(dataset.series("abc").unwrap().entries(), &[(1.0, vec![2.0, 3.0])]);
+}
+
+#[test]
+fn test_mat_malformed() {
+    // A `series` table whose rows have no columns.
+    let mut writer = MatWriter::new(Vec::new()).unwrap();
+    writer.write_var("x", &MatValue::Double { rows: 1, cols: 1, data: vec![0.0] }).unwrap();
+    writer.write_var("series", &MatValue::Cell { rows: 1, cols: 0, items: Vec::new() }).unwrap();
+    match mat::read_mat(&mut Cursor::new(writer.into_inner())) {
+        Err(Error::Parse(_)) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+
+    // A cell array of i32::MAX by i32::MAX items without any item.
+    let mut buf = MatWriter::new(Vec::new()).unwrap().into_inner();
+    for &word in [14u32, 40, 6, 8, 1, 0, 5, 8, i32::MAX as u32, i32::MAX as u32, 0x0001_0001].iter() {
+        buf.extend_from_slice(&word.to_le_bytes());
+    }
+    buf.extend_from_slice(b"c\0\0\0");
+    match mat::read_mat(&mut Cursor::new(buf)) {
+        Err(Error::Shape) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+
+    // An element which claims almost 4 GiB.
+    let mut buf = MatWriter::new(Vec::new()).unwrap().into_inner();
+    for &word in [14u32, 0xffff_fff0, 6, 8].iter() {
+        buf.extend_from_slice(&word.to_le_bytes());
+    }
+    match mat::read_mat(&mut Cursor::new(buf)) {
+        Err(Error::EndOfFile) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
+
+#[test]
+fn test_parse_rfc3339() {
+    let time = Duration::new(1_528_025_696, 789_000_000);
+    assert_eq!(timestamp::parse_rfc3339("2018-06-03T11:34:56.789Z"), Some(time));
+    assert_eq!(timestamp::parse_rfc3339("2018-06-03T20:34:56.789+09:00"), Some(time));
+    assert_eq!(timestamp::format_rfc3339(time), "2018-06-03T11:34:56.789Z");
+    assert_eq!(timestamp::parse_rfc3339("2018-06-03 11:34:56Z"), Some(Duration::new(1_528_025_696, 0)));
+    assert_eq!(timestamp::parse_rfc3339("2018-13-03T11:34:56Z"), None);
+    assert_eq!(timestamp::parse_rfc3339("1969-12-31T23:59:59Z"), None);
+}
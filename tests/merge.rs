@@ -0,0 +1,199 @@
+extern crate broto;
+
+use std::io;
+use std::io::Cursor;
+use std::time::Duration;
+
+use broto::{Dataset, TimeSeries, LazyReader, Reader, Block, Writer, LogBlockBuilder, Error};
+use broto::merge::{self, Duplicates};
+
+fn reader(dataset: &Dataset) -> LazyReader<Cursor<Vec<u8>>> {
+    let buf = broto::save(Cursor::new(Vec::new()), dataset).unwrap().into_inner();
+    LazyReader::new(Cursor::new(buf)).unwrap()
+}
+
+fn recorder(name: &str, indices: &[f64], value: f64) -> Dataset {
+    let mut dataset = Dataset::new();
+    let log = LogBlockBuilder::new()
+        .program(name)
+        .info("recording")
+        .time(Duration::new(1_500_000_000, 0))
+        .build();
+    dataset.metadata_mut().get_logs_mut().push(log);
+    dataset.group_mut("/run").set_attr("recorder", name);
+    let accel = dataset.add_series(TimeSeries::new("accel", 1).with_group("/run"));
+    for &index in indices {
+        accel.push(index, vec![value]);
+    }
+    dataset
+}
+
+fn merge_buf(inputs: &[&Dataset], duplicates: Duplicates) -> broto::Result<Vec<u8>> {
+    let mut inputs: Vec<_> = inputs.iter().map(|d| reader(d)).collect();
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    let log = LogBlockBuilder::new().program("merge").info("merged").build();
+    let writer = merge::merge(&mut inputs, writer, duplicates, Some(&log))?;
+    Ok(writer.into_stream().into_inner())
+}
+
+fn run(inputs: &[&Dataset], duplicates: Duplicates) -> broto::Result<Dataset> {
+    broto::load(Cursor::new(merge_buf(inputs, duplicates)?))
+}
+
+#[test]
+fn test_merge() {
+    let a = recorder("a", &[0.0, 2.0, 4.0], 1.0);
+    let b = recorder("b", &[1.0, 2.0, 5.0], 2.0);
+
+    // Series with another channel layout are kept apart.
+    let mut c = Dataset::new();
+    c.add_series(TimeSeries::new("accel", 2).with_group("/run")).push(3.0, vec![0.0, 0.0]);
+    let buf = merge_buf(&[&a, &c, &b], Duplicates::First).unwrap();
+    let lazy = LazyReader::new(Cursor::new(buf)).unwrap();
+    let shapes: Vec<(u64, u64)> = lazy.series().iter().map(|s| (s.value_len(), s.length())).collect();
+    assert_eq!(shapes, vec![(1, 5), (2, 1)]);
+
+    let merged = run(&[&a, &b], Duplicates::First).unwrap();
+    let logs: Vec<String> = merged.metadata().get_logs().iter().map(|l| l.program()).collect();
+    assert_eq!(logs, vec!["a", "b", "merge"]);
+    assert_eq!(merged.group("/run").unwrap().get_attr("recorder"), Some("b"));
+    let accel = merged.series("/run/accel").unwrap();
+    assert_eq!(accel.value_len(), 1);
+    assert_eq!(accel.entries(), &[(0.0, vec![1.0]), (1.0, vec![2.0]), (2.0, vec![1.0]),
+                                  (4.0, vec![1.0]), (5.0, vec![2.0])]);
+
+    let merged = run(&[&a, &b], Duplicates::Last).unwrap();
+    assert_eq!(merged.series("/run/accel").unwrap().entries()[2], (2.0, vec![2.0]));
+
+    match run(&[&a, &b], Duplicates::Error) {
+        Err(Error::Parse(ref msg)) => assert_eq!(msg, "duplicate index 2 in /run/accel"),
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
+
+#[test]
+fn test_merge_streaming() {
+    let even: Vec<f64> = (0..5000).map(|n| (2 * n) as f64).collect();
+    let odd: Vec<f64> = (0..5000).map(|n| (2 * n + 1) as f64).collect();
+    let merged = run(&[&recorder("a", &even, 1.0), &recorder("b", &odd, 2.0)], Duplicates::Error).unwrap();
+    let entries = merged.series("/run/accel").unwrap().entries();
+    assert_eq!(entries.len(), 10000);
+    assert!(entries.iter().enumerate().all(|(n, e)| e.0 == n as f64 && e.1[0] == 1.0 + (n % 2) as f64));
+
+    match run(&[&recorder("a", &[1.0, 0.0], 1.0)], Duplicates::First) {
+        Err(Error::Parse(ref msg)) => assert_eq!(msg, "the index of /run/accel in input 1 is not in order"),
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
+
+#[test]
+fn test_concat() {
+    let a = recorder("a", &[0.0, 2.0, 4.0], 1.0);
+    let b = recorder("b", &[1.0, 3.0], 2.0);
+    let mut inputs = vec![reader(&a), reader(&b)];
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    let buf = merge::concat(&mut inputs, writer, None).unwrap().into_stream().into_inner();
+    let joined = broto::load(Cursor::new(buf)).unwrap();
+    let indices: Vec<f64> = joined.series("/run/accel").unwrap().entries().iter().map(|e| e.0).collect();
+    assert_eq!(indices, vec![0.0, 2.0, 4.0, 1.0, 3.0]);
+    assert_eq!(joined.metadata().get_logs().len(), 2);
+}
+
+#[test]
+fn test_merge_index_len() {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    let block = broto::F64TSBlockBuilder::new().index_len(1).value_len(1).length(0).build();
+    let mut buf = writer.write_f64ts(block).unwrap().finish().into_stream().into_inner();
+    // An index of two columns, which the builder does not allow.
+    let end = buf.len();
+    buf[end - 24] = 2;
+    let a = recorder("a", &[0.0], 1.0);
+    for concat in [false, true].iter() {
+        let mut inputs = vec![reader(&a), LazyReader::new(Cursor::new(buf.clone())).unwrap()];
+        let mut writer = Writer::new(Cursor::new(Vec::new()));
+        writer.write_header().unwrap();
+        let result = if *concat {
+            merge::concat(&mut inputs, writer, None)
+        } else {
+            merge::merge(&mut inputs, writer, Duplicates::First, None)
+        };
+        match result {
+            Err(Error::Shape) => {},
+            r => panic!("unexpected result: {:?}", r.map(|_| ())),
+        }
+    }
+}
+
+#[test]
+fn test_read_concatenated() {
+    let mut buf = broto::save(Vec::new(), &recorder("a", &[0.0], 1.0)).unwrap();
+    buf.extend(broto::save(Vec::new(), &recorder("b", &[1.0], 2.0)).unwrap());
+
+    let mut reader = Reader::new(Cursor::new(buf.clone()));
+    reader.initialize().unwrap();
+    let mut blocks = 0;
+    let err = loop {
+        match reader.next_block() {
+            Ok(Block::F64TS(fts)) => { reader.skip_f64ts(&fts).unwrap(); blocks += 1; },
+            Ok(_) => { blocks += 1; },
+            Err(e) => break e,
+        }
+    };
+    assert_eq!(blocks, 4);
+    match err {
+        Error::Magic => {},
+        e => panic!("unexpected error: {:?}", e),
+    }
+
+    let mut reader = Reader::new(Cursor::new(buf)).accept_segments(true);
+    reader.initialize().unwrap();
+    let mut series = Vec::new();
+    loop {
+        match reader.next_block() {
+            Ok(Block::F64TS(fts)) => {
+                let entries: Vec<_> = reader.f64ts_entries(&fts).collect::<broto::Result<_>>().unwrap();
+                series.push((reader.segment(), reader.current_group().to_string(), entries));
+            },
+            Ok(Block::Log(_)) => { assert_eq!(reader.current_group(), "/"); },
+            Ok(_) => {},
+            Err(Error::EndOfFile) => break,
+            Err(e) => panic!("unexpected error: {:?}", e),
+        }
+    }
+    assert_eq!(series, vec![(0, "/run".to_string(), vec![(0.0, vec![1.0])]),
+                            (1, "/run".to_string(), vec![(1.0, vec![2.0])])]);
+}
+
+// A stream which returns at most one byte per read.
+struct Trickle(Cursor<Vec<u8>>);
+
+impl io::Read for Trickle {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(1);
+        self.0.read(&mut buf[..len])
+    }
+}
+
+#[test]
+fn test_read_segments() {
+    let mut buf = broto::save(Vec::new(), &recorder("a", &[0.0], 1.0)).unwrap();
+    buf.extend(broto::save(Vec::new(), &recorder("b", &[1.0], 2.0)).unwrap());
+
+    let reader = Reader::new(Trickle(Cursor::new(buf.clone()))).accept_segments(true);
+    let dataset = broto::load_from_reader(reader).unwrap();
+    assert_eq!(dataset.series("/run/accel").unwrap().len(), 2);
+    let reader = Reader::new(Cursor::new(buf.clone())).accept_segments(true);
+    let lazy = LazyReader::from_reader(reader).unwrap();
+    assert_eq!(lazy.chunks(0), vec![0, 1]);
+
+    // A file which ends inside a block magic number.
+    buf.extend_from_slice(b"blo");
+    let reader = Reader::new(Cursor::new(buf)).accept_segments(true);
+    match broto::load_from_reader(reader) {
+        Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::UnexpectedEof => {},
+        r => panic!("unexpected result: {:?}", r.map(|_| ())),
+    }
+}
@@ -0,0 +1,91 @@
+extern crate broto;
+
+use std::io::Cursor;
+use std::thread;
+
+use broto::{Error, F64TSBlockBuilder, LazyReader, Writer};
+use broto::mux::MuxWriter;
+
+fn block(name: &str, value_len: u64) -> broto::F64TSBlock {
+    F64TSBlockBuilder::new().index_len(1).value_len(value_len).name(name).build()
+}
+
+#[test]
+fn test_mux_threads() {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    let mux = MuxWriter::new(writer).chunk_rows(7);
+    let threads: Vec<_> = (0..4).map(|n| {
+        let mut handle = mux.add_series(if n % 2 == 0 { "/" } else { "/odd" }, block(&format!("s{}", n), n + 1)).unwrap();
+        thread::spawn(move || {
+            for i in 0..100 {
+                let values = vec![(n * 1000 + i) as f64; n as usize + 1];
+                handle.write_entry(i as f64, &values).unwrap();
+            }
+            handle.close().unwrap();
+        })
+    }).collect();
+    let empty = mux.add_series("/odd", block("empty", 1)).unwrap();
+    drop(empty);
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let buf = mux.finish().unwrap().into_stream().into_inner();
+
+    let dataset = broto::load(Cursor::new(buf.clone())).unwrap();
+    for n in 0..4u64 {
+        let path = format!("{}/s{}", if n % 2 == 0 { "" } else { "/odd" }, n);
+        let entries = dataset.series(&path).unwrap().entries();
+        assert_eq!(entries.len(), 100);
+        assert!(entries.iter().enumerate().all(|(i, e)| e.0 == i as f64 && e.1[0] == (n * 1000 + i as u64) as f64));
+    }
+    assert!(dataset.series("/odd/empty").unwrap().is_empty());
+
+    let mut lazy = LazyReader::new(Cursor::new(buf)).unwrap();
+    let n = lazy.series().iter().position(|s| s.name() == Some("s3")).unwrap();
+    assert_eq!(lazy.chunks(n).len(), 15);
+    let entries: Vec<_> = lazy.joined_entries(n).unwrap().map(|e| e.unwrap()).collect();
+    assert_eq!(entries.len(), 100);
+    assert_eq!(entries[99], (99.0, vec![3099.0; 4]));
+}
+
+#[test]
+fn test_mux_reopen() {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    let mux = MuxWriter::new(writer);
+    let mut a = mux.add_series("/", block("a", 1)).unwrap();
+    let mut b = mux.add_series("/", block("b", 1)).unwrap();
+    a.write_entry(0.0, &[1.0]).unwrap();
+    b.write_entry(0.0, &[2.0]).unwrap();
+    a.flush().unwrap();
+    drop(a);
+    drop(b);
+    let mut a = mux.add_series("/", block("a", 1)).unwrap();
+    a.write_entry(1.0, &[1.0]).unwrap();
+    drop(a);
+    let buf = mux.finish().unwrap().into_stream().into_inner();
+
+    let mut lazy = LazyReader::new(Cursor::new(buf)).unwrap();
+    assert_eq!(lazy.series().len(), 3);
+    let entries: Vec<_> = lazy.joined_entries(0).unwrap().map(|e| e.unwrap().0).collect();
+    assert_eq!(entries, vec![0.0, 1.0]);
+}
+
+#[test]
+fn test_mux_errors() {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    let mux = MuxWriter::new(writer);
+    let unnamed = F64TSBlockBuilder::new().index_len(1).value_len(1).build();
+    assert!(mux.add_series("/", unnamed).is_err());
+    let mut a = mux.add_series("/", block("a", 1)).unwrap();
+    assert!(mux.add_series("/", block("a", 1)).is_err());
+    match a.write_entry(0.0, &[1.0, 2.0]) {
+        Err(Error::Shape) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+    assert!(mux.clone().finish().is_err());
+    a.close().unwrap();
+    assert!(mux.finish().is_ok());
+}
@@ -0,0 +1,106 @@
+extern crate broto;
+
+use std::io::Cursor;
+
+use broto::{Dataset, TimeSeries, LazyReader};
+use broto::npy::{self, Array, Part};
+
+fn sample_dataset() -> Dataset {
+    let mut dataset = Dataset::new();
+    let accel = dataset.add_series(TimeSeries::new("accel", 2).with_group("/run1"));
+    for i in 0..10 {
+        accel.push(0.5 * i as f64, vec![i as f64, -(i as f64)]);
+    }
+    let temp = dataset.add_series(TimeSeries::new("temp", 1));
+    for i in 0..3 {
+        temp.push(i as f64, vec![20.0 + i as f64]);
+    }
+    dataset
+}
+
+#[test]
+fn test_npy_round_trip() {
+    let array = Array::new(vec![2, 3], vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+    let mut buf = Vec::new();
+    npy::write_npy(&mut buf, &array).unwrap();
+    assert_eq!(buf.len() as u64, npy::npy_size(&[2, 3]));
+    assert_eq!(&buf[..8], b"\x93NUMPY\x01\x00");
+    // The data starts at a multiple of 64 bytes.
+    assert_eq!((buf.len() - 48) % 64, 0);
+
+    let read = npy::read_npy(&mut Cursor::new(buf)).unwrap();
+    assert_eq!(array, read);
+    assert_eq!(read.row(1), &[4.0, 5.0, 6.0]);
+
+    let series = &sample_dataset().series_list()[0].clone();
+    let mut buf = Vec::new();
+    let entries = series.entries().iter().cloned().map(Ok);
+    npy::write_npy_entries(&mut buf, series.len(), 2, entries, Part::Both).unwrap();
+    let array = npy::read_npy(&mut Cursor::new(buf)).unwrap();
+    assert_eq!(array.shape(), &[10, 3]);
+    let read = npy::series_from_array("accel", &array).unwrap();
+    assert_eq!(series.entries(), read.entries());
+}
+
+#[test]
+fn test_npy_fortran_order() {
+    let header = "{'descr': '<f8', 'fortran_order': True, 'shape': (2, 3), }";
+    let mut buf = b"\x93NUMPY\x01\x00".to_vec();
+    buf.push(header.len() as u8);
+    buf.push(0);
+    buf.extend_from_slice(header.as_bytes());
+    for &x in [1.0f64, 4.0, 2.0, 5.0, 3.0, 6.0].iter() {
+        buf.extend_from_slice(&x.to_bits().to_le_bytes());
+    }
+    let array = npy::read_npy(&mut Cursor::new(buf)).unwrap();
+    assert_eq!(array.data(), &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+
+    let bad = b"\x93NUMPY\x01\x00\x2d\x00{'descr': '<i4', 'fortran_order': False, 'shape': (1,), }".to_vec();
+    match npy::read_npy(&mut Cursor::new(bad)) {
+        Err(broto::Error::Parse(_)) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
+
+#[test]
+fn test_npy_bad_shape() {
+    for &(shape, parse) in [("(4294967296, 4294967296)", true), ("(1000000000, 1000)", false)].iter() {
+        let header = format!("{{'descr': '<f8', 'fortran_order': False, 'shape': {}, }}", shape);
+        let mut buf = b"\x93NUMPY\x01\x00".to_vec();
+        buf.push(header.len() as u8);
+        buf.push(0);
+        buf.extend_from_slice(header.as_bytes());
+        buf.extend_from_slice(&[0; 16]);
+        match npy::read_npy(&mut Cursor::new(buf)) {
+            Err(broto::Error::Parse(_)) if parse => {},
+            Err(broto::Error::EndOfFile) if !parse => {},
+            r => panic!("unexpected result for {}: {:?}", shape, r),
+        }
+    }
+}
+
+#[test]
+fn test_npz_round_trip() {
+    let dataset = sample_dataset();
+    let buf = broto::save(Cursor::new(Vec::new()), &dataset).unwrap().into_inner();
+    let mut lazy = LazyReader::new(Cursor::new(buf)).unwrap();
+    let npz = npy::write_npz(Vec::new(), &mut lazy).unwrap();
+
+    let arrays = npy::read_npz(&mut Cursor::new(npz.clone())).unwrap();
+    let names: Vec<&str> = arrays.iter().map(|a| a.0.as_str()).collect();
+    assert_eq!(vec!["temp/index", "temp/values", "run1/accel/index", "run1/accel/values"], names);
+    assert_eq!(arrays[3].1.shape(), &[10, 2]);
+
+    let read = npy::read_npz_dataset(&mut Cursor::new(npz), "series").unwrap();
+    for series in dataset.series_list() {
+        let path = series.path();
+        assert_eq!(series.entries(), read.series(&path).unwrap().entries());
+    }
+
+    let mut npz = npy::NpzWriter::new(Vec::new());
+    npz.write_array("index", &Array::new(vec![2], vec![0.0, 1.0])).unwrap();
+    npz.write_array("values", &Array::new(vec![2, 1], vec![5.0, 6.0])).unwrap();
+    let npz = npz.finish().unwrap();
+    let read = npy::read_npz_dataset(&mut Cursor::new(npz), "signal").unwrap();
+    assert_eq!(read.series("/signal").unwrap().entries(), &[(0.0, vec![5.0]), (1.0, vec![6.0])]);
+}
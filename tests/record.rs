@@ -0,0 +1,83 @@
+extern crate broto;
+#[macro_use]
+extern crate broto_derive;
+
+use std::io::Cursor;
+
+use broto::{Writer, Reader, Block, Error};
+use broto::record::{self, Record};
+
+#[derive(Debug,PartialEq,BrotoRecord)]
+struct Sample {
+    temp     : f64,
+    #[broto(index)]
+    time     : f64,
+    #[broto(rename = "rh")]
+    humidity : f32,
+    count    : u32,
+    alarm    : bool,
+    #[broto(skip)]
+    note     : String,
+}
+
+#[test]
+fn test_record_layout() {
+    assert_eq!(Sample::CHANNELS, &["temp", "rh", "count", "alarm"]);
+    let sample = Sample { temp: 20.5, time: 1.5, humidity: 0.25, count: 3, alarm: true, note: "x".to_string() };
+    assert_eq!(sample.to_entry(), (1.5, vec![20.5, 0.25, 3.0, 1.0]));
+
+    let decoded = Sample::decode(&sample.to_entry()).unwrap();
+    assert_eq!(decoded, Sample { note: String::new(), ..sample });
+    match Sample::decode(&(0.0, vec![1.0])) {
+        Err(Error::Shape) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+    match Sample::from_entry(0.0, &[1.0, 2.0]) {
+        Err(Error::Shape) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
+
+#[test]
+fn test_record_field_range() {
+    use broto::record::Field;
+    assert_eq!(u8::from_f64(255.0).unwrap(), 255);
+    assert_eq!(i64::from_f64(-9_223_372_036_854_775_808.0).unwrap(), i64::MIN);
+    for &x in [256.0, -1.0, 0.5, f64::NAN].iter() {
+        assert!(u8::from_f64(x).is_err(), "{}", x);
+    }
+    assert!(i64::from_f64(9_223_372_036_854_775_808.0).is_err());
+    assert!(u64::from_f64(18_446_744_073_709_551_616.0).is_err());
+    assert!(f32::from_f64(1e300).is_err());
+    assert!(f32::from_f64(f64::INFINITY).unwrap().is_infinite());
+
+    match Sample::decode(&(0.0, vec![20.0, 0.5, -3.0, 0.0])) {
+        Err(Error::Parse(_)) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
+
+#[test]
+fn test_record_write_read() {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    let block = record::block_builder::<Sample>().name("weather").build();
+    let mut fts = writer.write_f64ts_with_seek(block).unwrap();
+    let samples: Vec<Sample> = (0..5).map(|n| Sample {
+        temp: 20.0 + n as f64, time: n as f64, humidity: 0.5, count: n, alarm: n % 2 == 0, note: String::new(),
+    }).collect();
+    for sample in samples.iter() {
+        fts.write_record(sample).unwrap();
+    }
+    let buf = fts.finalize().unwrap().finish().into_stream().into_inner();
+
+    let mut reader = Reader::new(Cursor::new(buf));
+    reader.initialize().unwrap();
+    let block = match reader.next_block().unwrap() {
+        Block::F64TS(block) => block,
+        b => panic!("unexpected block: {:?}", b),
+    };
+    assert_eq!(block.channel_names(), &["temp", "rh", "count", "alarm"]);
+    let read: Vec<Sample> = reader.f64ts_entries(&block).records().collect::<broto::Result<_>>().unwrap();
+    assert_eq!(read, samples);
+}
@@ -0,0 +1,116 @@
+extern crate broto;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use broto::{Dataset, F64TSBlockBuilder, Metadata};
+use broto::rolling::{self, RollingWriter};
+use broto::timestamp;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("broto-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn writer(template: &Path) -> RollingWriter {
+    let block = F64TSBlockBuilder::new()
+        .index_len(1)
+        .value_len(2)
+        .name("accel")
+        .channel_names(vec!["x", "y"])
+        .build();
+    let mut metadata = Metadata::new();
+    metadata.set_attr("site", "Kyoto");
+    RollingWriter::new(template.to_str().unwrap(), block).unwrap()
+        .group("/sensor")
+        .metadata(metadata)
+}
+
+fn load(path: &Path) -> Dataset {
+    broto::load(fs::File::open(path).unwrap()).unwrap()
+}
+
+#[test]
+fn test_file_name() {
+    let t = timestamp::parse_rfc3339("2018-06-01T12:34:56.5Z").unwrap().as_secs_f64();
+    assert_eq!(rolling::file_name("sensor-%Y%m%d-%H%M%S.broto", t, 3), "sensor-20180601-123456.broto");
+    assert_eq!(rolling::file_name("part-%n-%%-%q.broto", t, 3), "part-3-%-%q.broto");
+    assert_eq!(rolling::file_name("%Y", -1.0, 1), "1970");
+    assert_eq!(rolling::file_name("x-%Y.broto", 1e20, 1), "x-9999.broto");
+}
+
+#[test]
+fn test_rolling_rows() {
+    let dir = temp_dir("rolling-rows");
+    let mut w = writer(&dir.join("part-%n.broto")).max_rows(40);
+    for i in 0..100 {
+        let x = i as f64;
+        w.write_entry(x, &[x, -x]).unwrap();
+    }
+    match w.write_entry(100.0, &[0.0]) {
+        Err(broto::Error::Shape) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+    assert_eq!(w.files().len(), 2);
+    assert_eq!(w.current_path(), Some(dir.join("part-3.broto").as_path()));
+    let files = w.close().unwrap();
+    assert_eq!(files, (1..4).map(|n| dir.join(format!("part-{}.broto", n))).collect::<Vec<_>>());
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+
+    let mut next = 0.0;
+    for (path, len) in files.iter().zip(&[40, 40, 20]) {
+        let dataset = load(path);
+        assert_eq!(dataset.metadata().get_attr("site"), Some("Kyoto"));
+        let series = dataset.series("/sensor/accel").unwrap();
+        assert_eq!(series.channel_names(), &["x", "y"]);
+        assert_eq!(series.len(), *len);
+        assert_eq!(series.entries()[0], (next, vec![next, -next]));
+        next += *len as f64;
+    }
+}
+
+#[test]
+fn test_rolling_span() {
+    let dir = temp_dir("rolling-span");
+    let start = timestamp::parse_rfc3339("2018-06-01T11:30:00Z").unwrap().as_secs_f64();
+    let mut w = writer(&dir.join("sensor-%Y%m%d-%H.broto")).max_span(3600.0);
+    for i in 0..12 {
+        let t = start + i as f64 * 900.0;
+        w.write_entry(t, &[0.0, 0.0]).unwrap();
+    }
+    let files = w.close().unwrap();
+    let names: Vec<_> = files.iter().map(|p| p.file_name().unwrap().to_str().unwrap().to_string()).collect();
+    assert_eq!(names, vec!["sensor-20180601-11.broto", "sensor-20180601-12.broto",
+                           "sensor-20180601-13.broto", "sensor-20180601-14.broto"]);
+    let lens: Vec<_> = files.iter().map(|p| load(p).series("/sensor/accel").unwrap().len()).collect();
+    assert_eq!(lens, vec![2, 4, 4, 2]);
+
+    // Existing files are kept and the new ones are numbered.
+    let mut w = writer(&dir.join("sensor-%Y%m%d-%H.broto")).max_span(3600.0);
+    w.write_entry(start, &[1.0, 1.0]).unwrap();
+    let files = w.close().unwrap();
+    assert_eq!(files, vec![dir.join("sensor-20180601-11-2.broto")]);
+    assert_eq!(load(&dir.join("sensor-20180601-11.broto")).series("/sensor/accel").unwrap().len(), 2);
+}
+
+#[test]
+fn test_rolling_bytes() {
+    let dir = temp_dir("rolling-bytes");
+    let mut w = writer(&dir.join("part-%n.broto")).max_bytes(1000);
+    for i in 0..100 {
+        let x = i as f64;
+        w.write_entry(x, &[x, -x]).unwrap();
+    }
+    let files = w.close().unwrap();
+    assert!(files.len() > 1);
+    let mut total = 0;
+    for path in files.iter() {
+        assert!(fs::metadata(path).unwrap().len() <= 1000);
+        total += load(path).series("/sensor/accel").unwrap().len();
+    }
+    assert_eq!(total, 100);
+}
@@ -0,0 +1,57 @@
+#![cfg(feature = "serde")]
+
+extern crate broto;
+extern crate serde_json;
+
+use std::time::Duration;
+
+use broto::{Header, Metadata, LogBlockBuilder, LogBlock, F64TSBlock, F64TSBlockBuilder, EventBlock};
+
+#[test]
+fn test_serde_metadata() {
+    let mut metadata = Metadata::new();
+    metadata.get_logs_mut().push(LogBlockBuilder::new()
+                                 .program("broto")
+                                 .info("creation")
+                                 .time(Duration::new(1_500_000_000, 500_000_000))
+                                 .build());
+    metadata.set_attr("site", "Kyoto");
+    metadata.get_events_mut().push(EventBlock::new(0.5, None, "start"));
+
+    let json = serde_json::to_string(&metadata).unwrap();
+    assert_eq!(json, r#"{"logs":[{"time":"2017-07-14T02:40:00.5Z","program":"broto","info":"creation"}],"attrs":[{"key":"site","value":"Kyoto"}],"events":[{"onset":0.5,"duration":null,"text":"start"}]}"#);
+    assert_eq!(serde_json::from_str::<Metadata>(&json).unwrap(), metadata);
+    assert_eq!(serde_json::from_str::<Metadata>("{}").unwrap(), Metadata::new());
+
+    let log: LogBlock = serde_json::from_str(
+        r#"{"time":"2017-07-14T11:40:00.5+09:00","program":"broto","info":"creation"}"#).unwrap();
+    assert_eq!(log, metadata.get_logs()[0]);
+    assert!(serde_json::from_str::<LogBlock>(r#"{"time":"yesterday","program":"","info":""}"#).is_err());
+
+    let header = Header::new();
+    let json = serde_json::to_string(&header).unwrap();
+    assert_eq!(json, r#"{"major_version":0,"minor_version":2}"#);
+    assert_eq!(serde_json::from_str::<Header>(&json).unwrap(), header);
+}
+
+#[test]
+fn test_serde_f64ts() {
+    let block = F64TSBlockBuilder::new()
+        .index_len(1)
+        .value_len(2)
+        .length(10)
+        .id(3)
+        .name("accel")
+        .channel_names(vec!["x", "y"])
+        .build();
+    let json = serde_json::to_string(&block).unwrap();
+    assert_eq!(serde_json::from_str::<F64TSBlock>(&json).unwrap(), block);
+
+    let minimal: F64TSBlock = serde_json::from_str(r#"{"index_len":1,"value_len":3}"#).unwrap();
+    assert_eq!(minimal, F64TSBlockBuilder::new().index_len(1).value_len(3).build());
+
+    let err = serde_json::from_str::<F64TSBlock>(r#"{"index_len":2,"value_len":3}"#).unwrap_err();
+    assert!(err.to_string().contains("index_len must be 1"));
+    assert!(serde_json::from_str::<F64TSBlock>(
+        r#"{"index_len":1,"value_len":3,"channels":["x","y"]}"#).is_err());
+}
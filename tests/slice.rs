@@ -0,0 +1,122 @@
+extern crate broto;
+
+use std::io::Cursor;
+
+use broto::{Dataset, TimeSeries, LazyReader, Writer, LogBlockBuilder, Error};
+use broto::slice::{self, Range, Split};
+
+fn sample() -> LazyReader<Cursor<Vec<u8>>> {
+    let mut dataset = Dataset::new();
+    dataset.metadata_mut().set_attr("site", "Kyoto");
+    let accel = dataset.add_series(TimeSeries::new("accel", 2).with_group("/run"));
+    for i in 0..100 {
+        accel.push(i as f64 * 0.5, vec![i as f64, -(i as f64)]);
+    }
+    let temp = dataset.add_series(TimeSeries::new("temp", 1));
+    for i in 0..10 {
+        temp.push(i as f64 * 5.0, vec![20.0]);
+    }
+    let buf = broto::save(Cursor::new(Vec::new()), &dataset).unwrap().into_inner();
+    LazyReader::new(Cursor::new(buf)).unwrap()
+}
+
+fn extract(input: &mut LazyReader<Cursor<Vec<u8>>>, range: Range) -> (Vec<u8>, Dataset) {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    let log = LogBlockBuilder::new().program("slice").info("part").build();
+    let buf = slice::extract(input, writer, range, Some(&log)).unwrap().into_stream().into_inner();
+    let dataset = broto::load(Cursor::new(buf.clone())).unwrap();
+    (buf, dataset)
+}
+
+#[test]
+fn test_slice_index() {
+    let mut input = sample();
+    let (_, part) = extract(&mut input, Range::Index { from: Some(10.0), to: Some(20.0) });
+    assert_eq!(part.metadata().get_attr("site"), Some("Kyoto"));
+    assert_eq!(part.metadata().get_logs()[0].program(), "slice");
+    let accel = part.series("/run/accel").unwrap().entries();
+    assert_eq!(accel.len(), 20);
+    assert_eq!((accel[0].0, accel[19].0), (10.0, 19.5));
+    assert_eq!(part.series("temp").unwrap().entries().len(), 2);
+
+    let (_, part) = extract(&mut input, Range::Index { from: None, to: Some(0.0) });
+    assert!(part.series("/run/accel").unwrap().entries().is_empty());
+}
+
+#[test]
+fn test_split() {
+    let mut input = sample();
+    let log = LogBlockBuilder::new().program("slice").info("part").build();
+    assert_eq!(slice::rows_per_part(&mut input, Split::Rows(30), &log).unwrap(), (30, 4));
+
+    let (rows, parts) = slice::rows_per_part(&mut input, Split::Bytes(2000), &log).unwrap();
+    assert_eq!(parts, 100u64.div_ceil(rows));
+    let mut total = 0;
+    for part in 0..parts {
+        let (buf, dataset) = extract(&mut input, Range::Rows { start: part * rows, end: (part + 1) * rows });
+        assert!(buf.len() <= 2000);
+        total += dataset.series("/run/accel").unwrap().len();
+    }
+    assert_eq!(total, 100);
+
+    match slice::rows_per_part(&mut input, Split::Bytes(100), &log) {
+        Err(Error::Parse(_)) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
+
+#[test]
+fn test_slice_chunked() {
+    // A series written in two blocks of five rows.
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    for chunk in 0..2 {
+        let block = broto::F64TSBlockBuilder::new().index_len(1).value_len(1).length(5).name("accel").build();
+        let mut w = writer.write_f64ts(block).unwrap();
+        for i in 0..5 {
+            w.write_entry((chunk * 5 + i) as f64, &[0.0]).unwrap();
+        }
+        writer = w.finish();
+    }
+    let mut input = LazyReader::new(Cursor::new(writer.into_stream().into_inner())).unwrap();
+
+    let (buf, part) = extract(&mut input, Range::Rows { start: 3, end: 8 });
+    assert_eq!(LazyReader::new(Cursor::new(buf)).unwrap().series().len(), 1);
+    let indices: Vec<f64> = part.series("accel").unwrap().entries().iter().map(|e| e.0).collect();
+    assert_eq!(indices, vec![3.0, 4.0, 5.0, 6.0, 7.0]);
+
+    let log = LogBlockBuilder::new().program("slice").info("part").build();
+    assert_eq!(slice::rows_per_part(&mut input, Split::Rows(4), &log).unwrap(), (4, 3));
+    match slice::rows_per_part(&mut input, Split::Rows(0), &log) {
+        Err(Error::Parse(_)) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
+
+#[test]
+fn test_slice_index_len() {
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    let block = broto::F64TSBlockBuilder::new().index_len(1).value_len(1).length(1).build();
+    let mut w = writer.write_f64ts(block).unwrap();
+    w.write_entry(0.0, &[1.0]).unwrap();
+    let mut buf = w.finish().into_stream().into_inner();
+    // An index of two columns, which the builder does not allow.
+    let end = buf.len();
+    buf[end - 40] = 2;
+    buf.extend_from_slice(&[0; 8]);
+    let mut input = LazyReader::new(Cursor::new(buf)).unwrap();
+
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    writer.write_header().unwrap();
+    match slice::extract(&mut input, writer, Range::Rows { start: 0, end: 1 }, None) {
+        Err(Error::Shape) => {},
+        r => panic!("unexpected result: {:?}", r.map(|_| ())),
+    }
+    let log = LogBlockBuilder::new().program("slice").info("part").build();
+    match slice::rows_per_part(&mut input, Split::Rows(1), &log) {
+        Err(Error::Shape) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
@@ -0,0 +1,77 @@
+extern crate broto;
+
+use std::io::Cursor;
+
+use broto::Metadata;
+use broto::wav::{self, SampleFormat, WavSpec};
+
+fn tone(channels: usize, frames: usize) -> Vec<broto::Entry> {
+    (0..frames).map(|n| {
+        let values = (0..channels).map(|c| ((n * (c + 1)) % 64) as f64 / 64.0 - 0.5).collect();
+        (n as f64 / 8000.0, values)
+    }).collect()
+}
+
+#[test]
+fn test_wav_round_trip() {
+    let specs = vec![
+        WavSpec::new(1, 8000, SampleFormat::Pcm16),
+        WavSpec::new(2, 8000, SampleFormat::Pcm24),
+        WavSpec::new(3, 8000, SampleFormat::Float32),
+        WavSpec::new(2, 8000, SampleFormat::Pcm16).channel_mask(0x3),
+    ];
+    for spec in specs {
+        let entries = tone(spec.channels() as usize, 101);
+        let mut buf = Vec::new();
+        wav::write_wav(&mut buf, &spec, 101, entries.iter().cloned().map(Ok)).unwrap();
+
+        let (series, read_spec) = wav::read_wav(Cursor::new(buf.clone()), "tone").unwrap();
+        assert_eq!(spec, read_spec);
+        assert_eq!(entries, series.entries());
+
+        let mut metadata = Metadata::new();
+        metadata.get_attrs_mut().extend(read_spec.to_attrs());
+        let spec = WavSpec::from_metadata(&metadata).unwrap().unwrap();
+        let mut written = Vec::new();
+        wav::write_wav(&mut written, &spec, 101, series.entries().iter().cloned().map(Ok)).unwrap();
+        assert_eq!(buf, written);
+    }
+}
+
+#[test]
+fn test_wav_clipping_and_errors() {
+    let spec = WavSpec::new(1, 100, SampleFormat::Pcm16);
+    let entries = vec![(0.0, vec![2.0]), (0.01, vec![-2.0])];
+    let mut buf = Vec::new();
+    wav::write_wav(&mut buf, &spec, 2, entries.into_iter().map(Ok)).unwrap();
+    let (series, _) = wav::read_wav(Cursor::new(buf), "clipped").unwrap();
+    assert_eq!(series.entries(), &[(0.0, vec![32767.0 / 32768.0]), (0.01, vec![-1.0])]);
+
+    let mut buf = Vec::new();
+    match wav::write_wav(&mut buf, &spec, 1, vec![Ok((0.0, vec![0.0, 0.0]))].into_iter()) {
+        Err(broto::Error::Shape) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+    match wav::read_wav(Cursor::new(b"RIFX\0\0\0\0WAVE".to_vec()), "bad") {
+        Err(broto::Error::Magic) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
+
+#[test]
+fn test_wav_invalid_spec() {
+    let spec = WavSpec::new(2, u32::MAX, SampleFormat::Float32);
+    match wav::write_wav(&mut Vec::new(), &spec, 0, Vec::new().into_iter()) {
+        Err(broto::Error::Parse(_)) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+
+    let mut metadata = Metadata::new();
+    metadata.set_attr("wav.format", "pcm16");
+    metadata.set_attr("wav.channels", "0");
+    metadata.set_attr("wav.sample_rate", "44100");
+    match WavSpec::from_metadata(&metadata) {
+        Err(broto::Error::Parse(_)) => {},
+        r => panic!("unexpected result: {:?}", r),
+    }
+}